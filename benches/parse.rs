@@ -0,0 +1,67 @@
+// criterion needs a newer toolchain than the library's rust-version anyway
+#![allow(clippy::incompatible_msrv)]
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use json_stream_parser::{parse_stream, JsonStreamParser};
+use serde_json::Value;
+use std::hint::black_box;
+
+const LARGE_ARRAY: &str = include_str!("../tests/large_array.json");
+
+fn complete_input(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_array");
+    group.throughput(Throughput::Bytes(LARGE_ARRAY.len() as u64));
+    group.bench_function("serde_json", |b| {
+        b.iter(|| serde_json::from_str::<Value>(black_box(LARGE_ARRAY)).unwrap())
+    });
+    group.bench_function("parse_stream", |b| {
+        b.iter(|| parse_stream(black_box(LARGE_ARRAY)).unwrap())
+    });
+    group.bench_function("add_str_4k_chunks", |b| {
+        b.iter(|| {
+            let mut parser = JsonStreamParser::new();
+            let mut rest = black_box(LARGE_ARRAY);
+            while !rest.is_empty() {
+                let mut end = rest.len().min(4096);
+                while !rest.is_char_boundary(end) {
+                    end += 1;
+                }
+                parser.add_str(&rest[..end]).unwrap();
+                rest = &rest[end..];
+            }
+            parser.into_result()
+        })
+    });
+    group.bench_function("add_char", |b| {
+        b.iter(|| {
+            let mut parser = JsonStreamParser::new();
+            for c in black_box(LARGE_ARRAY).chars() {
+                parser.add_char(c).unwrap();
+            }
+            parser.into_result()
+        })
+    });
+    group.finish();
+}
+
+// mostly string content, as in a streamed LLM response
+fn long_strings(c: &mut Criterion) {
+    let paragraph = "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(20);
+    let document: Value = (0..500)
+        .map(|i| serde_json::json!({"id": i, "text": paragraph}))
+        .collect();
+    let json = serde_json::to_string(&document).unwrap();
+
+    let mut group = c.benchmark_group("long_strings");
+    group.throughput(Throughput::Bytes(json.len() as u64));
+    group.bench_function("serde_json", |b| {
+        b.iter(|| serde_json::from_str::<Value>(black_box(&json)).unwrap())
+    });
+    group.bench_function("parse_stream", |b| {
+        b.iter(|| parse_stream(black_box(&json)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, complete_input, long_strings);
+criterion_main!(benches);
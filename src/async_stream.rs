@@ -0,0 +1,187 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use serde_json::Value;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::utf8::Utf8Decoder;
+use crate::{JsonStreamParser, ParseError};
+
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+#[derive(Debug)]
+pub enum AsyncParseError<E> {
+    // The underlying stream returned an error.
+    Source(E),
+    // The received text is not valid (partial) JSON or not valid UTF-8.
+    Parse(ParseError),
+}
+
+impl<E: fmt::Display> fmt::Display for AsyncParseError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsyncParseError::Source(e) => write!(f, "stream error: {e}"),
+            AsyncParseError::Parse(e) => write!(f, "parse error: {e}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for AsyncParseError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AsyncParseError::Source(e) => Some(e),
+            AsyncParseError::Parse(e) => Some(e),
+        }
+    }
+}
+
+// Wraps a stream of text or byte chunks (`String`, `bytes::Bytes`, `Vec<u8>`, ...) and yields a
+// snapshot of the partial document after each non-empty chunk.
+//
+// The inner stream is only polled when the snapshot stream itself is polled, so a slow consumer
+// slows down the producer. All parsing state lives in the adapter: dropping a pending `next()`
+// future, for example in a `select!`, loses no input.
+pub struct SnapshotStream<S> {
+    inner: S,
+    parser: JsonStreamParser,
+    decoder: Utf8Decoder,
+    done: bool,
+}
+
+impl<S> SnapshotStream<S> {
+    pub fn new(inner: S) -> SnapshotStream<S> {
+        Self::with_parser(inner, JsonStreamParser::new())
+    }
+
+    // Uses a preconfigured parser, for example one created with `JsonStreamParser::with_limits`.
+    pub fn with_parser(inner: S, parser: JsonStreamParser) -> SnapshotStream<S> {
+        SnapshotStream {
+            inner,
+            parser,
+            decoder: Utf8Decoder::default(),
+            done: false,
+        }
+    }
+
+    pub fn parser(&self) -> &JsonStreamParser {
+        &self.parser
+    }
+
+    pub fn into_parser(self) -> JsonStreamParser {
+        self.parser
+    }
+}
+
+impl<S, B, E> Stream for SnapshotStream<S>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    type Item = Result<Value, AsyncParseError<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            let chunk = match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => {
+                    this.done = true;
+                    return match this.decoder.finish() {
+                        Ok(()) => Poll::Ready(None),
+                        Err(e) => Poll::Ready(Some(Err(AsyncParseError::Parse(e)))),
+                    };
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(AsyncParseError::Source(e))));
+                }
+                Poll::Ready(Some(Ok(chunk))) => chunk,
+            };
+            let bytes = chunk.as_ref();
+            if bytes.is_empty() {
+                continue;
+            }
+            let parser = &mut this.parser;
+            if let Err(e) = this.decoder.decode(bytes, |s| parser.add_str(s)) {
+                this.done = true;
+                return Poll::Ready(Some(Err(AsyncParseError::Parse(e))));
+            }
+            return Poll::Ready(Some(Ok(this.parser.get_result().clone())));
+        }
+    }
+}
+
+// Reads from a `tokio::io::AsyncRead` through a bounded buffer and yields a snapshot of the
+// partial document after each successful read. Parse errors are reported as
+// `io::ErrorKind::InvalidData`.
+pub struct ReaderSnapshotStream<R> {
+    reader: R,
+    parser: JsonStreamParser,
+    decoder: Utf8Decoder,
+    buffer: Box<[u8]>,
+    done: bool,
+}
+
+impl<R> ReaderSnapshotStream<R> {
+    pub fn new(reader: R) -> ReaderSnapshotStream<R> {
+        Self::with_parser(reader, JsonStreamParser::new())
+    }
+
+    pub fn with_parser(reader: R, parser: JsonStreamParser) -> ReaderSnapshotStream<R> {
+        ReaderSnapshotStream {
+            reader,
+            parser,
+            decoder: Utf8Decoder::default(),
+            buffer: vec![0; READ_BUFFER_SIZE].into_boxed_slice(),
+            done: false,
+        }
+    }
+
+    pub fn parser(&self) -> &JsonStreamParser {
+        &self.parser
+    }
+
+    pub fn into_parser(self) -> JsonStreamParser {
+        self.parser
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for ReaderSnapshotStream<R> {
+    type Item = io::Result<Value>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let mut buf = ReadBuf::new(&mut this.buffer);
+        match Pin::new(&mut this.reader).poll_read(cx, &mut buf) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(e)) => {
+                this.done = true;
+                Poll::Ready(Some(Err(e)))
+            }
+            Poll::Ready(Ok(())) if buf.filled().is_empty() => {
+                this.done = true;
+                match this.decoder.finish() {
+                    Ok(()) => Poll::Ready(None),
+                    Err(e) => Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::InvalidData, e)))),
+                }
+            }
+            Poll::Ready(Ok(())) => {
+                let parser = &mut this.parser;
+                if let Err(e) = this.decoder.decode(buf.filled(), |s| parser.add_str(s)) {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::InvalidData, e))));
+                }
+                Poll::Ready(Some(Ok(this.parser.get_result().clone())))
+            }
+        }
+    }
+}
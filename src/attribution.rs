@@ -0,0 +1,37 @@
+use std::ops::RangeInclusive;
+
+use crate::{JsonStreamParser, ParseError, ValueBuilder};
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // Parses a token of model output, such as `{"na`, like `add_str`, and with `record_spans`
+    // enabled, remembers which values it wrote into under `id`, for `token_range` and
+    // `token_paths`.
+    pub fn add_token(&mut self, token: &str, id: usize) -> Result<(), ParseError> {
+        if let Some(spans) = &mut self.spans {
+            spans.token = Some(id);
+        }
+        let result = self.add_str(token);
+        if let Some(spans) = &mut self.spans {
+            spans.token = None;
+            if result.is_ok() {
+                spans.latest = Some(id);
+            }
+        }
+        result
+    }
+
+    // The ids of the first and last tokens that wrote into the value at `pointer`, its key or the
+    // values in it. For a value that is still open, the last one is the latest token.
+    pub fn token_range(&self, pointer: &str) -> Option<RangeInclusive<usize>> {
+        self.spans.as_ref()?.token_range(pointer)
+    }
+
+    // The JSON Pointers of the values that the token `id` wrote into directly, in document order.
+    // A key belongs to its member, and the brackets, commas and whitespace of a container to the
+    // container.
+    pub fn token_paths(&self, id: usize) -> Vec<String> {
+        self.spans
+            .as_ref()
+            .map_or_else(Vec::new, |spans| spans.token_paths(id))
+    }
+}
@@ -0,0 +1,687 @@
+#[cfg(feature = "checkpoint")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Number, Value};
+
+#[cfg(feature = "shared")]
+use crate::shared::{SharedValue, SharedValueBuilder};
+
+// A string-less JSON scalar.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Scalar {
+    Null,
+    Bool(bool),
+    Number(Number),
+}
+
+impl From<Scalar> for Value {
+    fn from(scalar: Scalar) -> Self {
+        match scalar {
+            Scalar::Null => Value::Null,
+            Scalar::Bool(b) => Value::Bool(b),
+            Scalar::Number(n) => Value::Number(n),
+        }
+    }
+}
+
+// Builds the output document from the events of a `JsonStreamParser`, in document order.
+//
+// Values are written into the current slot of the innermost open container: the member added by
+// the last `insert_key`, the element added by the last `push_item`, or the root when no container
+// is open. A value either is a container, which is open from `start_object`/`start_array` to
+// `end_container`, or a string or scalar, which is written by `start_string`/`append_string` or by
+// one or more `set_scalar` calls and is complete at `end_value`. A partial number or literal at the
+// root is set as soon as it is known and set again when it grows; inside containers, scalars are
+// only set once they are complete.
+pub trait ValueBuilder {
+    type Output;
+
+    fn start_object(&mut self);
+    fn start_array(&mut self);
+    // Ends the innermost open container, which is then complete.
+    fn end_container(&mut self);
+    // Adds a member to the innermost object. It is null until its value starts.
+    fn insert_key(&mut self, key: &str);
+    // Adds an element to the innermost array. It is null until its value starts.
+    fn push_item(&mut self);
+    // Adds another value for a key the innermost object already has, under
+    // `DuplicateKeys::Collect`. The member becomes an array of every value given for the key, with
+    // its current value wrapped in one if `wrap` is set, and this value is appended to it, null
+    // until it starts. By default the last value wins.
+    fn collect_key(&mut self, key: &str, wrap: bool) {
+        let _ = wrap;
+        self.insert_key(key);
+    }
+    fn start_string(&mut self);
+    fn append_string(&mut self, s: &str);
+    fn set_scalar(&mut self, scalar: Scalar);
+    // The string or scalar in the current slot has its final value.
+    fn end_value(&mut self);
+    // Removes the current slot, which holds a malformed value under `Recovery::Drop`, or a value
+    // left out by `JsonStreamParser::project`, possibly a container that has just ended. An array
+    // element left out that way is then added again with `push_item` and `end_value`, as null. The
+    // root becomes null. By default the slot is kept as it is.
+    fn discard(&mut self) {}
+    // Called after every chunk of input, when the partial document may be looked at.
+    fn end_chunk(&mut self) {}
+    // The number of containers the builder has open, if it keeps count, which a checkpoint is
+    // checked against when it is restored.
+    fn open_containers(&self) -> Option<usize> {
+        None
+    }
+    fn into_output(self) -> Self::Output;
+}
+
+// An open container while events are being applied. Between chunks it lives in the current slot
+// of its parent, and `value` is null.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+struct Container {
+    value: Value,
+    // the key of the current member, kept from one member to the next so that its buffer is reused
+    key: String,
+    // whether the current member collects the values of a duplicate key, and is written to at the
+    // end of its array
+    collected: bool,
+}
+
+impl Container {
+    fn slot_mut(&mut self) -> &mut Value {
+        let slot = match &mut self.value {
+            Value::Array(arr) => arr.last_mut(),
+            Value::Object(map) => map.get_mut(&self.key),
+            _ => None,
+        };
+        if self.collected {
+            slot.and_then(Value::as_array_mut)
+                .and_then(|values| values.last_mut())
+        } else {
+            slot
+        }
+        .expect("values are written into a slot")
+    }
+}
+
+// A change to a `SerdeValueBuilder`, logged with what `SerdeValueBuilder::rewind` needs to undo
+// it once the parser is rewindable.
+#[derive(Clone, Debug)]
+pub(crate) enum Change {
+    // a container was started
+    Started,
+    // the innermost container ended, with its current member
+    Ended {
+        key: String,
+        collected: bool,
+    },
+    // the current member was `key`, and the slot of the new one held `replaced` if it was there
+    Key {
+        key: String,
+        collected: bool,
+        replaced: Option<Value>,
+    },
+    // the current member was `key`, and the value of the new one was wrapped in an array if `wrap`
+    Collected {
+        key: String,
+        collected: bool,
+        wrap: bool,
+    },
+    Pushed,
+    // the current slot held the value before it was set
+    Set(Value),
+    // the bytes appended to the string in the current slot
+    Appended(usize),
+    // the last element of the innermost array, or of the values of its collected member, was
+    // removed
+    Popped(Option<Value>),
+    // the current member was removed, from this index if it was not the last one
+    Removed(Value, Option<usize>),
+    Root(Value),
+}
+
+// A `SerdeValueBuilder` as it was at a mark of `JsonStreamParser::rewindable`, along with the
+// changes made from there to the next mark, so that the document is not copied at every mark.
+#[derive(Clone, Debug)]
+pub(crate) struct BuilderMark {
+    open: bool,
+    #[cfg(feature = "shared")]
+    shared: Option<SharedValueBuilder>,
+    pub(crate) changes: Vec<Change>,
+}
+
+// Builds a `serde_json::Value` in place, so that the partial document is complete after every
+// chunk.
+#[derive(Clone, Debug, Default)]
+pub struct SerdeValueBuilder {
+    root: Value,
+    // the open containers, outermost first
+    containers: Vec<Container>,
+    // whether the open containers have been moved out of their parents for the current chunk
+    detached: bool,
+    // whether the current slot of the innermost container or the root holds an incomplete value
+    open: bool,
+    // a persistent copy of the document kept for `JsonStreamParser::snapshot`, if enabled
+    #[cfg(feature = "shared")]
+    shared: Option<SharedValueBuilder>,
+    // the changes since the last mark, if the parser is rewindable
+    journal: Option<Vec<Change>>,
+}
+
+impl SerdeValueBuilder {
+    pub fn new() -> SerdeValueBuilder {
+        Self::default()
+    }
+
+    // The partial document as of the end of the last chunk.
+    pub fn value(&self) -> &Value {
+        &self.root
+    }
+
+    // Moves every open container out of its parent and into `containers`, so that the innermost
+    // one can be written to without walking down from the root. This costs one step per open
+    // container for each chunk rather than for each event, and is skipped for chunks that only
+    // extend a key or a scalar.
+    fn detach(&mut self) {
+        if self.detached {
+            return;
+        }
+        self.detached = true;
+        for index in 0..self.containers.len() {
+            let (parents, children) = self.containers.split_at_mut(index);
+            let slot = match parents.last_mut() {
+                Some(parent) => parent.slot_mut(),
+                None => &mut self.root,
+            };
+            children[0].value = std::mem::take(slot);
+        }
+    }
+
+    // Puts the open containers back into their parents.
+    fn attach(&mut self) {
+        if !self.detached {
+            return;
+        }
+        self.detached = false;
+        for index in (0..self.containers.len()).rev() {
+            let (parents, children) = self.containers.split_at_mut(index);
+            let value = std::mem::take(&mut children[0].value);
+            match parents.last_mut() {
+                Some(parent) => *parent.slot_mut() = value,
+                None => self.root = value,
+            }
+        }
+    }
+
+    fn slot_mut(&mut self) -> &mut Value {
+        self.detach();
+        match self.containers.last_mut() {
+            Some(container) => container.slot_mut(),
+            None => &mut self.root,
+        }
+    }
+
+    fn start_container(&mut self, value: Value) {
+        self.detach();
+        self.log(|| Change::Started);
+        self.containers.push(Container {
+            value,
+            key: String::new(),
+            collected: false,
+        });
+        self.open = false;
+    }
+
+    fn log(&mut self, change: impl FnOnce() -> Change) {
+        if let Some(journal) = &mut self.journal {
+            journal.push(change());
+        }
+    }
+
+    // Saves the builder for a mark, between two chunks, and logs the changes from here on.
+    pub(crate) fn mark(&mut self) -> BuilderMark {
+        self.journal = Some(Vec::new());
+        BuilderMark {
+            open: self.open,
+            #[cfg(feature = "shared")]
+            shared: self.shared.clone(),
+            changes: Vec::new(),
+        }
+    }
+
+    // The changes logged since the last mark.
+    pub(crate) fn take_changes(&mut self) -> Vec<Change> {
+        self.journal
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    // Undoes `changes`, latest first.
+    pub(crate) fn rewind(&mut self, changes: &mut Vec<Change>) {
+        self.detach();
+        for change in changes.drain(..).rev() {
+            match change {
+                Change::Started => {
+                    self.containers.pop();
+                }
+                Change::Ended { key, collected } => {
+                    let value = std::mem::take(self.slot_mut());
+                    self.containers.push(Container {
+                        value,
+                        key,
+                        collected,
+                    });
+                }
+                Change::Key {
+                    key,
+                    collected,
+                    replaced,
+                } => {
+                    let container = self.containers.last_mut().unwrap();
+                    if let Value::Object(map) = &mut container.value {
+                        match replaced {
+                            Some(value) => {
+                                if let Some(slot) = map.get_mut(&container.key) {
+                                    *slot = value;
+                                }
+                            }
+                            #[cfg(feature = "preserve_order")]
+                            None => {
+                                map.shift_remove(&container.key);
+                            }
+                            #[cfg(not(feature = "preserve_order"))]
+                            None => {
+                                map.remove(&container.key);
+                            }
+                        }
+                    }
+                    container.key = key;
+                    container.collected = collected;
+                }
+                Change::Collected {
+                    key,
+                    collected,
+                    wrap,
+                } => {
+                    let container = self.containers.last_mut().unwrap();
+                    if let Some(Value::Array(values)) = container.value.get_mut(&container.key) {
+                        values.pop();
+                        if wrap {
+                            let value = values.pop().unwrap_or_default();
+                            *container.value.get_mut(&container.key).unwrap() = value;
+                        }
+                    }
+                    container.key = key;
+                    container.collected = collected;
+                }
+                Change::Pushed => {
+                    if let Some(Container {
+                        value: Value::Array(arr),
+                        ..
+                    }) = self.containers.last_mut()
+                    {
+                        arr.pop();
+                    }
+                }
+                Change::Set(value) => *self.slot_mut() = value,
+                Change::Appended(len) => {
+                    if let Value::String(string) = self.slot_mut() {
+                        string.truncate(string.len() - len);
+                    }
+                }
+                Change::Popped(value) => {
+                    let container = self.containers.last_mut().unwrap();
+                    let values = match &mut container.value {
+                        Value::Array(arr) => Some(arr),
+                        Value::Object(map) => match map.get_mut(&container.key) {
+                            Some(Value::Array(values)) => Some(values),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if let (Some(values), Some(value)) = (values, value) {
+                        values.push(value);
+                    }
+                }
+                Change::Removed(value, index) => {
+                    let container = self.containers.last_mut().unwrap();
+                    if let Value::Object(map) = &mut container.value {
+                        let key = container.key.clone();
+                        match index {
+                            #[cfg(feature = "preserve_order")]
+                            Some(index) => insert_at(map, index, key, value),
+                            _ => {
+                                map.insert(key, value);
+                            }
+                        }
+                    }
+                }
+                Change::Root(value) => self.root = value,
+            }
+        }
+    }
+
+    // Puts back what `mark` saved, once the changes since it have been undone, and logs the
+    // changes from here on.
+    pub(crate) fn restore(&mut self, mark: &BuilderMark) {
+        self.attach();
+        self.open = mark.open;
+        #[cfg(feature = "shared")]
+        {
+            self.shared = mark.shared.clone();
+        }
+        self.journal = Some(Vec::new());
+    }
+
+    #[cfg(feature = "shared")]
+    pub(crate) fn share(&mut self) {
+        if self.shared.is_some() {
+            return;
+        }
+        self.attach();
+        let keys: Vec<(&str, bool)> = self
+            .containers
+            .iter()
+            .map(|c| (c.key.as_str(), c.collected))
+            .collect();
+        self.shared = Some(SharedValueBuilder::split(&self.root, &keys, self.open));
+    }
+
+    #[cfg(feature = "shared")]
+    pub(crate) fn snapshot(&self) -> SharedValue {
+        match &self.shared {
+            Some(shared) => shared.snapshot(),
+            None => SharedValue::from(&self.root),
+        }
+    }
+}
+
+// A `SerdeValueBuilder` in a checkpoint, borrowed when saved and owned when restored. The shared
+// copy of the document is rebuilt from the document on restore, so only whether there is one is
+// kept.
+#[cfg(feature = "checkpoint")]
+#[derive(Serialize, Deserialize)]
+struct BuilderState<V, C> {
+    root: V,
+    containers: C,
+    detached: bool,
+    open: bool,
+    shared: bool,
+}
+
+#[cfg(feature = "checkpoint")]
+impl Serialize for SerdeValueBuilder {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BuilderState {
+            root: &self.root,
+            containers: &self.containers,
+            detached: self.detached,
+            open: self.open,
+            #[cfg(feature = "shared")]
+            shared: self.shared.is_some(),
+            #[cfg(not(feature = "shared"))]
+            shared: false,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "checkpoint")]
+impl<'de> Deserialize<'de> for SerdeValueBuilder {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = BuilderState::<Value, Vec<Container>>::deserialize(deserializer)?;
+        #[allow(unused_mut)]
+        let mut builder = SerdeValueBuilder {
+            root: state.root,
+            containers: state.containers,
+            detached: state.detached,
+            open: state.open,
+            #[cfg(feature = "shared")]
+            shared: None,
+            journal: None,
+        };
+        #[cfg(feature = "shared")]
+        if state.shared {
+            builder.share();
+        }
+        Ok(builder)
+    }
+}
+
+// Forwards an event to the shared copy of the document, if there is one.
+macro_rules! share {
+    ($builder:expr, $event:ident $(, $arg:expr)*) => {
+        #[cfg(feature = "shared")]
+        if let Some(shared) = &mut $builder.shared {
+            shared.$event($($arg),*);
+        }
+    };
+}
+
+impl ValueBuilder for SerdeValueBuilder {
+    fn open_containers(&self) -> Option<usize> {
+        Some(self.containers.len())
+    }
+
+    type Output = Value;
+
+    fn start_object(&mut self) {
+        share!(self, start_object);
+        self.start_container(Value::Object(Map::new()));
+    }
+
+    fn start_array(&mut self) {
+        share!(self, start_array);
+        self.start_container(Value::Array(vec![]));
+    }
+
+    fn end_container(&mut self) {
+        share!(self, end_container);
+        self.detach();
+        let child = self.containers.pop().expect("a container is open");
+        *self.slot_mut() = child.value;
+        self.log(|| Change::Ended {
+            key: child.key,
+            collected: child.collected,
+        });
+        self.open = false;
+    }
+
+    fn insert_key(&mut self, key: &str) {
+        share!(self, insert_key, key);
+        self.detach();
+        let container = self.containers.last_mut().expect("an object is open");
+        let old = (self.journal.is_some()).then(|| std::mem::take(&mut container.key));
+        let collected = container.collected;
+        container.key.clear();
+        container.key.push_str(key);
+        container.collected = false;
+        let mut replaced = None;
+        if let Value::Object(map) = &mut container.value {
+            match map.get_mut(key) {
+                Some(slot) => replaced = Some(std::mem::take(slot)),
+                None => {
+                    map.insert(key.to_string(), Value::Null);
+                }
+            }
+        }
+        if let Some(key) = old {
+            self.log(|| Change::Key {
+                key,
+                collected,
+                replaced,
+            });
+        }
+        self.open = true;
+    }
+
+    fn collect_key(&mut self, key: &str, wrap: bool) {
+        share!(self, collect_key, key, wrap);
+        self.detach();
+        let container = self.containers.last_mut().expect("an object is open");
+        let old = (self.journal.is_some()).then(|| std::mem::take(&mut container.key));
+        let collected = container.collected;
+        container.key.clear();
+        container.key.push_str(key);
+        container.collected = true;
+        if let Some(slot) = container.value.get_mut(key) {
+            if wrap {
+                *slot = Value::Array(vec![std::mem::take(slot)]);
+            }
+            if let Value::Array(values) = slot {
+                values.push(Value::Null);
+            }
+        }
+        if let Some(key) = old {
+            self.log(|| Change::Collected {
+                key,
+                collected,
+                wrap,
+            });
+        }
+        self.open = true;
+    }
+
+    fn push_item(&mut self) {
+        share!(self, push_item);
+        self.detach();
+        if let Some(Container {
+            value: Value::Array(arr),
+            ..
+        }) = self.containers.last_mut()
+        {
+            arr.push(Value::Null);
+        }
+        self.log(|| Change::Pushed);
+        self.open = true;
+    }
+
+    fn start_string(&mut self) {
+        share!(self, start_string);
+        let old = std::mem::replace(self.slot_mut(), Value::String(String::new()));
+        self.log(|| Change::Set(old));
+        self.open = true;
+    }
+
+    fn append_string(&mut self, s: &str) {
+        share!(self, append_string, s);
+        if let Value::String(string) = self.slot_mut() {
+            string.push_str(s);
+            self.log(|| Change::Appended(s.len()));
+        }
+    }
+
+    fn set_scalar(&mut self, scalar: Scalar) {
+        share!(self, set_scalar, scalar.clone());
+        let old = std::mem::replace(self.slot_mut(), scalar.into());
+        self.log(|| Change::Set(old));
+        self.open = true;
+    }
+
+    fn end_value(&mut self) {
+        share!(self, end_value);
+        self.open = false;
+    }
+
+    fn discard(&mut self) {
+        share!(self, discard);
+        self.detach();
+        let logged = self.journal.is_some();
+        let change = match self.containers.last_mut() {
+            Some(container) => match &mut container.value {
+                Value::Array(arr) => Some(Change::Popped(arr.pop())),
+                Value::Object(map) if container.collected => match map.get_mut(&container.key) {
+                    Some(Value::Array(values)) => Some(Change::Popped(values.pop())),
+                    _ => None,
+                },
+                // keeps the other members in order with `preserve_order`
+                #[cfg(feature = "preserve_order")]
+                Value::Object(map) => {
+                    // the index is only needed if the member was not the last one
+                    let index = match map.keys().next_back() {
+                        Some(last) if !logged || *last == container.key => None,
+                        _ => map.keys().position(|key| *key == container.key),
+                    };
+                    map.shift_remove(&container.key)
+                        .map(|value| Change::Removed(value, index))
+                }
+                #[cfg(not(feature = "preserve_order"))]
+                Value::Object(map) => map
+                    .remove(&container.key)
+                    .map(|value| Change::Removed(value, None)),
+                _ => None,
+            },
+            None => Some(Change::Root(std::mem::take(&mut self.root))),
+        };
+        if let (true, Some(change)) = (logged, change) {
+            self.log(|| change);
+        }
+        self.open = false;
+    }
+
+    fn end_chunk(&mut self) {
+        self.attach();
+    }
+
+    fn into_output(mut self) -> Value {
+        self.attach();
+        std::mem::take(&mut self.root)
+    }
+}
+
+// Dropping a `Value` recurses once per level of nesting, which overflows the stack on very deep
+// documents, so the partial document is taken apart with an explicit stack instead.
+impl Drop for SerdeValueBuilder {
+    fn drop(&mut self) {
+        let mut containers = vec![std::mem::take(&mut self.root)];
+        containers.extend(self.containers.drain(..).map(|container| container.value));
+        take_apart(containers, self.journal.take().unwrap_or_default());
+    }
+}
+
+// The values in a log are taken apart the same way.
+impl Drop for BuilderMark {
+    fn drop(&mut self) {
+        take_apart(Vec::new(), std::mem::take(&mut self.changes));
+    }
+}
+
+// Drops `value` with an explicit stack, for values that may be nested very deeply.
+pub(crate) fn drop_value(value: Value) {
+    take_apart(vec![value], Vec::new());
+}
+
+fn take_apart(mut containers: Vec<Value>, changes: Vec<Change>) {
+    for change in changes {
+        match change {
+            Change::Key {
+                replaced: Some(value),
+                ..
+            }
+            | Change::Set(value)
+            | Change::Popped(Some(value))
+            | Change::Removed(value, _)
+            | Change::Root(value) => containers.push(value),
+            _ => {}
+        }
+    }
+    while let Some(value) = containers.pop() {
+        match value {
+            Value::Array(arr) => containers.extend(arr.into_iter().filter(is_container)),
+            Value::Object(map) => {
+                containers.extend(map.into_iter().map(|(_, value)| value).filter(is_container))
+            }
+            _ => {}
+        }
+    }
+}
+
+// Inserts a member at `index`, which `Map` cannot do, by moving the members after it behind it.
+#[cfg(feature = "preserve_order")]
+fn insert_at(map: &mut Map<String, Value>, index: usize, key: String, value: Value) {
+    let mut members: Vec<(String, Value)> = std::mem::take(map).into_iter().collect();
+    members.insert(index.min(members.len()), (key, value));
+    map.extend(members);
+}
+
+fn is_container(value: &Value) -> bool {
+    value.is_array() || value.is_object()
+}
@@ -0,0 +1,240 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::glob::Glob;
+use crate::recovery::{Recovery, Resync};
+use crate::redact::SavedRedaction;
+use crate::spans::Spans;
+use crate::transaction::{Events, Undo};
+use crate::utf8::Utf8Decoder;
+use crate::{
+    next_generation, DuplicateKeys, Frame, FrameState, JsonStreamParser, Limits, ParseError,
+    Redaction, Token, ValueBuilder,
+};
+
+// The version of the checkpoint format. It is written first, and a checkpoint with another
+// version is rejected rather than restored into state that means something else. It changes
+// whenever the state of the parser does.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+// The state of a `JsonStreamParser` between two chunks, borrowed when saved and owned when
+// restored. The builder calls and undo log of the current chunk are empty between chunks, and the
+// input kept for rewinding, the clock of a timed parser, its stop conditions and the matcher of its
+// redaction are left out. A string held back for redaction cannot be saved, so that it never
+// leaves the parser.
+#[derive(Serialize, Deserialize)]
+struct State<'a, B> {
+    builder: B,
+    stack: Cow<'a, [Frame]>,
+    token: Token,
+    number: Cow<'a, str>,
+    duplicate_keys: DuplicateKeys,
+    skip: Option<usize>,
+    redaction: Option<SavedRedaction<'a>>,
+    redacted: Option<usize>,
+    projection: Cow<'a, Option<Vec<Glob>>>,
+    selected: Option<usize>,
+    holding: usize,
+    limits: Limits,
+    offset: usize,
+    processed_chars: usize,
+    string_length: usize,
+    nodes: usize,
+    memory: usize,
+    utf8: Cow<'a, Utf8Decoder>,
+    deferred_error: Cow<'a, Option<ParseError>>,
+    recovery: Cow<'a, Option<Recovery>>,
+    resync: Option<Resync>,
+    diagnostics: Cow<'a, [ParseError]>,
+    spans: Cow<'a, Option<Spans>>,
+}
+
+impl<B: ValueBuilder> State<'_, B> {
+    // Whether the saved frames agree with each other and with the builder, so that a checkpoint
+    // put together from two parsers is rejected rather than left to panic later on.
+    fn check(&self) -> Result<(), &'static str> {
+        let depth = self.stack.len();
+        if depth == 0 {
+            return Err("checkpoint has no root frame");
+        }
+        let within = |index: Option<usize>| index.map_or(true, |index| index < depth);
+        if !within(self.skip)
+            || !within(self.redacted)
+            || !within(self.selected)
+            || self.holding > depth
+        {
+            return Err("checkpoint refers to a frame it does not have");
+        }
+        // the containers in a value that is skipped or redacted are lexed but not built
+        let built = self.skip.into_iter().chain(self.redacted).min();
+        let open = self
+            .stack
+            .iter()
+            .enumerate()
+            .take_while(|&(index, _)| built.map_or(true, |built| index <= built))
+            .filter(|&(index, frame)| {
+                index > 0 || !matches!(frame.state, FrameState::Ready | FrameState::Done)
+            })
+            .count();
+        match self.builder.open_containers() {
+            Some(containers) if containers != open => {
+                Err("checkpoint has another number of open containers than its builder")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Saves the parser as a checkpoint: `CHECKPOINT_VERSION` followed by its state, which
+// `Deserialize` restores so that parsing goes on exactly where it stopped. A parser that redacts
+// strings with a matcher is restored by `JsonStreamParser::restore_with` instead.
+impl<B: Serialize> Serialize for JsonStreamParser<B> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.held.is_some() {
+            return Err(ser::Error::custom(
+                "cannot save a string held back for redaction",
+            ));
+        }
+        let state = State {
+            builder: &self.builder,
+            stack: Cow::Borrowed(&self.stack),
+            token: self.token,
+            number: Cow::Borrowed(&self.number),
+            duplicate_keys: self.duplicate_keys,
+            skip: self.skip,
+            redaction: self.redaction.as_ref().map(SavedRedaction::from),
+            redacted: self.redacted,
+            projection: Cow::Borrowed(&self.projection),
+            selected: self.selected,
+            holding: self.holding,
+            limits: self.limits,
+            offset: self.offset,
+            processed_chars: self.processed_chars,
+            string_length: self.string_length,
+            nodes: self.nodes,
+            memory: self.memory,
+            utf8: Cow::Borrowed(&self.utf8),
+            deferred_error: Cow::Borrowed(&self.deferred_error),
+            recovery: Cow::Borrowed(&self.recovery),
+            resync: self.resync,
+            diagnostics: Cow::Borrowed(&self.diagnostics),
+            spans: Cow::Borrowed(&self.spans),
+        };
+        (CHECKPOINT_VERSION, state).serialize(serializer)
+    }
+}
+
+impl<'de, B: ValueBuilder + Deserialize<'de>> Deserialize<'de> for JsonStreamParser<B> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(2, CheckpointVisitor(PhantomData, None))
+    }
+}
+
+impl<B> JsonStreamParser<B> {
+    // Restores a checkpoint of a parser that redacts, with `redaction` in place of the saved one.
+    // It must have a matcher if the saved one did, since a matcher cannot be saved.
+    pub fn restore_with<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        redaction: Redaction,
+    ) -> Result<Self, D::Error>
+    where
+        B: ValueBuilder + Deserialize<'de>,
+    {
+        deserializer.deserialize_tuple(2, CheckpointVisitor(PhantomData, Some(redaction)))
+    }
+}
+
+// Restores a checkpoint, with the redaction given to `restore_with` if there is one.
+struct CheckpointVisitor<B>(PhantomData<B>, Option<Redaction>);
+
+impl<'de, B: ValueBuilder + Deserialize<'de>> Visitor<'de> for CheckpointVisitor<B> {
+    type Value = JsonStreamParser<B>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JsonStreamParser checkpoint")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let version: u32 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        if version != CHECKPOINT_VERSION {
+            return Err(de::Error::custom(format!(
+                "unsupported checkpoint version {version}, expected {CHECKPOINT_VERSION}"
+            )));
+        }
+        let state: State<B> = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        state.check().map_err(de::Error::custom)?;
+        let redaction = match state.redaction {
+            Some(saved) => Some(saved.restore(self.1).map_err(de::Error::custom)?),
+            None => self.1,
+        };
+        Ok(JsonStreamParser {
+            builder: state.builder,
+            stack: state.stack.into_owned(),
+            token: state.token,
+            number: state.number.into_owned(),
+            duplicate_keys: state.duplicate_keys,
+            skip: state.skip,
+            limits: state.limits,
+            offset: state.offset,
+            generation: next_generation(),
+            processed_chars: state.processed_chars,
+            string_length: state.string_length,
+            nodes: state.nodes,
+            memory: state.memory,
+            utf8: state.utf8.into_owned(),
+            deferred_error: state.deferred_error.into_owned(),
+            recovery: state.recovery.into_owned(),
+            resync: state.resync,
+            diagnostics: state.diagnostics.into_owned(),
+            spans: state.spans.into_owned(),
+            clock: None,
+            stop_conditions: Vec::new(),
+            redaction,
+            redacted: state.redacted,
+            held: None,
+            projection: state.projection.into_owned(),
+            selected: state.selected,
+            holding: state.holding,
+            events: Events::default(),
+            undo: Undo::default(),
+            history: None,
+        })
+    }
+}
+
+// `Token::Literal` keeps the text of the literal as a static string, which is saved as the string
+// and restored as the static one.
+pub(crate) mod literal {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(
+        text: &&'static [u8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(std::str::from_utf8(text).unwrap_or_default())
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<&'static [u8], D::Error> {
+        let text = String::deserialize(deserializer)?;
+        match text.as_str() {
+            "true" => Ok(b"true"),
+            "false" => Ok(b"false"),
+            "null" => Ok(b"null"),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Str(&text),
+                &"true, false or null",
+            )),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut, BytesMut};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{continues_number, JsonStreamParser};
+
+#[derive(Debug)]
+pub enum CodecError {
+    Io(io::Error),
+    // The received bytes are not valid JSON, not valid UTF-8, or exceed a limit.
+    Parse(String),
+    // A complete value could not be converted to or from the frame type.
+    Serde(serde_json::Error),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "io error: {e}"),
+            CodecError::Parse(e) => write!(f, "parse error: {e}"),
+            CodecError::Serde(e) => write!(f, "serde error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Io(e) => Some(e),
+            CodecError::Parse(_) => None,
+            CodecError::Serde(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self {
+        CodecError::Io(e)
+    }
+}
+
+// Frames back-to-back JSON values, optionally separated by whitespace, out of a byte stream. A
+// frame is returned as soon as its root value closes; the partially parsed next value is kept
+// between calls to `decode`. `max_depth` and `max_length` apply to each frame separately.
+//
+// A number at the root has no closing token, so it is only returned once the following byte
+// (whitespace or the start of the next value) or the end of the stream has been received.
+pub struct JsonCodec<T = Value> {
+    parser: JsonStreamParser,
+    max_depth: Option<usize>,
+    max_length: Option<usize>,
+    frame: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for JsonCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> JsonCodec<T> {
+    pub fn new() -> JsonCodec<T> {
+        Self::with_limits(None, None)
+    }
+
+    pub fn with_limits(max_depth: Option<usize>, max_length: Option<usize>) -> JsonCodec<T> {
+        JsonCodec {
+            parser: JsonStreamParser::with_limits(max_depth, max_length),
+            max_depth,
+            max_length,
+            frame: PhantomData,
+        }
+    }
+
+    fn take_frame(&mut self) -> Value {
+        let parser = std::mem::replace(
+            &mut self.parser,
+            JsonStreamParser::with_limits(self.max_depth, self.max_length),
+        );
+        parser.into_result()
+    }
+
+    // takes a number at the root as a frame, unless it is not a whole number such as `-` or `1e`
+    fn take_number(&mut self) -> Result<Value, CodecError> {
+        if let Err(e) = self.parser.end_root_number() {
+            self.reset();
+            return Err(CodecError::Parse(e.to_string()));
+        }
+        Ok(self.take_frame())
+    }
+
+    fn reset(&mut self) {
+        self.parser = JsonStreamParser::with_limits(self.max_depth, self.max_length);
+    }
+}
+
+impl<T: DeserializeOwned> JsonCodec<T> {
+    fn convert(value: Value) -> Result<T, CodecError> {
+        serde_json::from_value(value).map_err(CodecError::Serde)
+    }
+}
+
+impl<T: DeserializeOwned> Decoder for JsonCodec<T> {
+    type Item = T;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<T>, CodecError> {
+        let text = match std::str::from_utf8(src) {
+            Ok(text) => text,
+            // keep a UTF-8 sequence split at the end of the buffer for the next call
+            Err(e) if e.error_len().is_none() => std::str::from_utf8(&src[..e.valid_up_to()])
+                .map_err(|e| {
+                    self.reset();
+                    CodecError::Parse(e.to_string())
+                })?,
+            Err(e) => {
+                self.reset();
+                return Err(CodecError::Parse(format!("invalid UTF-8: {e}")));
+            }
+        };
+
+        let mut consumed = text.len();
+        let mut frame = None;
+        for (offset, c) in text.char_indices() {
+            if self.parser.is_root_number() && !(c.is_ascii() && continues_number(c as u8)) {
+                consumed = offset;
+                frame = Some(self.take_number()?);
+                break;
+            }
+            if let Err(e) = self.parser.add_char(c) {
+                self.reset();
+                return Err(CodecError::Parse(e.to_string()));
+            }
+            if self.parser.is_complete() {
+                consumed = offset + c.len_utf8();
+                frame = Some(self.take_frame());
+                break;
+            }
+        }
+        src.advance(consumed);
+
+        frame.map(Self::convert).transpose()
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<T>, CodecError> {
+        if let Some(frame) = self.decode(src)? {
+            return Ok(Some(frame));
+        }
+        if !src.is_empty() {
+            self.reset();
+            return Err(CodecError::Parse(
+                "incomplete UTF-8 sequence at end of input".to_string(),
+            ));
+        }
+        if self.parser.is_root_number() {
+            return Self::convert(self.take_number()?).map(Some);
+        }
+        if self.parser.is_started() {
+            self.reset();
+            return Err(CodecError::Parse(
+                "unexpected end of input inside a value".to_string(),
+            ));
+        }
+        Ok(None)
+    }
+}
+
+// Each value is followed by a newline, so that consecutive root numbers stay separate frames.
+impl<T, I: Serialize> Encoder<I> for JsonCodec<T> {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: I, dst: &mut BytesMut) -> Result<(), CodecError> {
+        serde_json::to_writer(dst.writer(), &item).map_err(CodecError::Serde)?;
+        dst.put_u8(b'\n');
+        Ok(())
+    }
+}
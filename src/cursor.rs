@@ -0,0 +1,83 @@
+use crate::{push_pointer_token, FrameState, JsonStreamParser, Token, ValueBuilder};
+
+// What the parser is in the middle of, as of the end of the last chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ParseState {
+    // Before the root value, after `[`, or after a `,` in an array or a `:` or key in an object.
+    ExpectingValue,
+    // After `{` or after a `,` in an object.
+    ExpectingKey,
+    InKey,
+    InString,
+    InNumber,
+    // In `true`, `false` or `null`.
+    InLiteral,
+    // After a value in a container, waiting for `,` or the closing bracket.
+    AfterValue,
+    // The root value is complete, or recovery mode has given up on it.
+    Closed,
+}
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    pub fn state(&self) -> ParseState {
+        match self.token {
+            Token::Key(_) => ParseState::InKey,
+            Token::String(_) => ParseState::InString,
+            Token::Number => ParseState::InNumber,
+            Token::Literal { .. } => ParseState::InLiteral,
+            Token::None => match self.stack.last().unwrap().state {
+                FrameState::Ready | FrameState::ExpectValue | FrameState::ExpectColon => {
+                    ParseState::ExpectingValue
+                }
+                FrameState::ExpectKey => ParseState::ExpectingKey,
+                FrameState::AfterValue | FrameState::Nested => ParseState::AfterValue,
+                FrameState::Done => ParseState::Closed,
+            },
+        }
+    }
+
+    // The JSON Pointer of the innermost value being written: the string or scalar being lexed, the
+    // member whose key is being lexed or whose value comes next, or else the innermost open
+    // container. A partial key is part of the path as it is so far.
+    pub fn current_path(&self) -> String {
+        let mut path = String::new();
+        let root = &self.stack[0];
+        if matches!(root.state, FrameState::Ready | FrameState::Done) {
+            return path;
+        }
+        let innermost = self.stack.len() - 1;
+        for (index, frame) in self.stack.iter().enumerate() {
+            let slot = index < innermost
+                || self.token != Token::None
+                || (frame.object
+                    && matches!(
+                        frame.state,
+                        FrameState::ExpectColon | FrameState::ExpectValue
+                    ));
+            if !slot {
+                break;
+            }
+            if frame.object {
+                push_pointer_token(&mut path, &frame.key);
+            } else {
+                push_pointer_token(&mut path, &(frame.members - 1).to_string());
+            }
+        }
+        path
+    }
+
+    // The unescaped tokens of the path of the value whose slot is in the innermost frame: the
+    // member whose key is the frame's, or the last element.
+    pub(crate) fn slot_path(&self) -> Vec<String> {
+        self.stack
+            .iter()
+            .map(|frame| {
+                if frame.object {
+                    frame.key.clone()
+                } else {
+                    (frame.members - 1).to_string()
+                }
+            })
+            .collect()
+    }
+}
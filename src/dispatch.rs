@@ -0,0 +1,226 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{pointer_tokens, ErrorKind, JsonStreamParser, ParseError};
+
+// Receives the partial document every time a `Dispatch` is updated after its variant was selected.
+pub trait PartialTarget {
+    fn update(&mut self, partial: &Value) -> Result<(), ParseError>;
+
+    // Called by `Dispatch` with the parser itself, so that a target can tell when nothing it
+    // looks at has changed since its last update. Defaults to `update` with the partial document.
+    fn update_from(&mut self, parser: &JsonStreamParser) -> Result<(), ParseError> {
+        self.update(parser.get_result())
+    }
+}
+
+impl<F> PartialTarget for F
+where
+    F: FnMut(&Value) -> Result<(), ParseError>,
+{
+    fn update(&mut self, partial: &Value) -> Result<(), ParseError> {
+        self(partial)
+    }
+}
+
+impl PartialTarget for Box<dyn PartialTarget + '_> {
+    fn update(&mut self, partial: &Value) -> Result<(), ParseError> {
+        (**self).update(partial)
+    }
+
+    fn update_from(&mut self, parser: &JsonStreamParser) -> Result<(), ParseError> {
+        (**self).update_from(parser)
+    }
+}
+
+// A target that deserializes the partial document (or the part of it at a JSON Pointer) into `T`,
+// keeping the last value that deserialized successfully. Partial documents are usually missing
+// fields, so `T` should give them defaults, for example with `#[serde(default)]`. When it is
+// updated by a `Dispatch`, it is not deserialized again until more input has been parsed, nor
+// once the value it reads has been completely parsed.
+pub struct Typed<T> {
+    pointer: Option<String>,
+    value: Option<T>,
+    // the parser generation and offset at the last update, and whether the value was complete then
+    seen: Option<(usize, usize, bool)>,
+}
+
+impl<T> Default for Typed<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Typed<T> {
+    pub fn new() -> Typed<T> {
+        Typed {
+            pointer: None,
+            value: None,
+            seen: None,
+        }
+    }
+
+    pub fn at(pointer: &str) -> Typed<T> {
+        Typed {
+            pointer: Some(pointer.to_string()),
+            value: None,
+            seen: None,
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    pub fn into_inner(self) -> Option<T> {
+        self.value
+    }
+}
+
+impl<T: DeserializeOwned> PartialTarget for Typed<T> {
+    fn update(&mut self, partial: &Value) -> Result<(), ParseError> {
+        let source = match &self.pointer {
+            Some(pointer) => match partial.pointer(pointer) {
+                Some(value) => value,
+                None => return Ok(()),
+            },
+            None => partial,
+        };
+        // a failure only means not enough of the document has arrived yet
+        if let Ok(value) = T::deserialize(source) {
+            self.value = Some(value);
+        }
+        Ok(())
+    }
+
+    fn update_from(&mut self, parser: &JsonStreamParser) -> Result<(), ParseError> {
+        // the same offset only holds the same input until the parser goes back
+        let (generation, offset) = (parser.generation, parser.offset);
+        match self.seen {
+            Some((seen_generation, seen, complete))
+                if seen_generation == generation
+                    && (seen == offset || (complete && seen < offset)) =>
+            {
+                return Ok(())
+            }
+            _ => {}
+        }
+        let complete = parser
+            .completed_value(self.pointer.as_deref().unwrap_or(""))
+            .is_some();
+        self.seen = Some((generation, offset, complete));
+        self.update(parser.get_result())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DispatchState {
+    // The tag has not been completely parsed yet.
+    Pending,
+    // The tag was completed by the input fed since the last update and a target was selected.
+    // This is only returned once.
+    Selected,
+    // A target was selected earlier and has been updated with the latest partial document.
+    Streaming,
+}
+
+// Routes a tagged-union document, such as `{"type": "search", ...}`, to a per-variant target as
+// soon as the string at `tag_path` closes. The tag does not need to be the first field: fields
+// that arrived before it are part of the partial document handed to the target when it is
+// selected.
+pub struct Dispatch<T> {
+    tag_path: String,
+    variants: Vec<(String, T)>,
+    fallback: Option<T>,
+    tag: Option<String>,
+    selected: Option<Selection>,
+}
+
+#[derive(Clone, Copy)]
+enum Selection {
+    Variant(usize),
+    Fallback,
+}
+
+impl<T: PartialTarget> Dispatch<T> {
+    pub fn new(tag_path: &str) -> Dispatch<T> {
+        Dispatch {
+            tag_path: tag_path.to_string(),
+            variants: vec![],
+            fallback: None,
+            tag: None,
+            selected: None,
+        }
+    }
+
+    pub fn variant(mut self, tag: &str, target: T) -> Dispatch<T> {
+        self.variants.push((tag.to_string(), target));
+        self
+    }
+
+    // The target used for tags without a registered variant. Without a fallback, an unknown tag
+    // is reported as an error.
+    pub fn fallback(mut self, target: T) -> Dispatch<T> {
+        self.fallback = Some(target);
+        self
+    }
+
+    // Checks the parser for a completed tag and feeds the selected target. Call it after every
+    // `add_char` (or chunk of them) that should be observed.
+    pub fn update(&mut self, parser: &JsonStreamParser) -> Result<DispatchState, ParseError> {
+        let error = |message: String| ParseError::new(ErrorKind::Target(message), parser.offset);
+        let state = if self.selected.is_some() {
+            DispatchState::Streaming
+        } else {
+            if pointer_tokens(&self.tag_path).is_none() {
+                return Err(error(format!("invalid tag path {}", self.tag_path)));
+            }
+            let tag = match parser.completed_value(&self.tag_path) {
+                Some(Value::String(tag)) => tag.clone(),
+                Some(other) => {
+                    let message = format!("tag at {} is not a string: {other}", self.tag_path);
+                    return Err(error(message));
+                }
+                None => return Ok(DispatchState::Pending),
+            };
+            let selection = match self.variants.iter().position(|(name, _)| *name == tag) {
+                Some(index) => Selection::Variant(index),
+                None if self.fallback.is_some() => Selection::Fallback,
+                None => return Err(error(format!("no variant registered for tag {tag}"))),
+            };
+            self.tag = Some(tag);
+            self.selected = Some(selection);
+            DispatchState::Selected
+        };
+
+        if let Some(target) = self.target_mut() {
+            target.update_from(parser)?;
+        }
+        Ok(state)
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    pub fn target(&self) -> Option<&T> {
+        match self.selected? {
+            Selection::Variant(index) => Some(&self.variants[index].1),
+            Selection::Fallback => self.fallback.as_ref(),
+        }
+    }
+
+    pub fn target_mut(&mut self) -> Option<&mut T> {
+        match self.selected? {
+            Selection::Variant(index) => Some(&mut self.variants[index].1),
+            Selection::Fallback => self.fallback.as_mut(),
+        }
+    }
+
+    pub fn into_target(mut self) -> Option<T> {
+        match self.selected? {
+            Selection::Variant(index) => Some(self.variants.swap_remove(index).1),
+            Selection::Fallback => self.fallback,
+        }
+    }
+}
@@ -0,0 +1,569 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+use serde_json::{Map, Value};
+
+use crate::builder::drop_value;
+use crate::spans::Spans;
+use crate::{push_pointer_token, JsonStreamParser, ParseError};
+
+// The chars between two marks of the parser of a document, unless it was made rewindable with
+// another interval.
+const INTERVAL: usize = 4096;
+
+// A JSON text that is edited in place, such as an editor buffer, along with its parsed value.
+//
+// The document keeps the parser it has given the text to, with a mark every few thousand chars
+// and the spans of the values. An edit goes back to the last mark before it and parses the text
+// from there on. The spans tell which containers the edit is in, and only the values in them that
+// it can have changed are compared with what they were. A text with an error is parsed up to the
+// error, and its value is the partial document at that point. A parser that redacts keeps no
+// input to go back to, so under a redaction every edit parses the whole text again.
+pub struct Document {
+    text: String,
+    // the number of chars in `text`, which is its length while it is ASCII
+    chars: usize,
+    // the parser the document was made with, before it was given any of the text
+    template: JsonStreamParser,
+    parser: JsonStreamParser,
+    // the error the parser stopped at
+    error: Option<ParseError>,
+}
+
+// The outcome of an edit.
+#[derive(Debug)]
+pub struct Update<'a> {
+    // the value of the edited text
+    pub value: &'a Value,
+    // the JSON Pointers of the outermost values that were added, removed or changed
+    pub changed: BTreeSet<String>,
+}
+
+// What an edit can change, saved before it is made.
+enum Before {
+    // the whole value
+    Value(Value),
+    // the containers the edit is in, outermost first, none if it is after the root value
+    Levels(Vec<Level>),
+}
+
+// A container that an edit is in, as it was before the edit.
+struct Level {
+    name: Name,
+    // the byte offset of its opening bracket, which comes before the edit
+    start: usize,
+    shape: Shape,
+}
+
+// Where a container is in its parent.
+#[derive(PartialEq)]
+enum Name {
+    Root,
+    Key(String),
+    Index(usize),
+}
+
+// The values in a container that an edit can change. The container of the next level is left
+// out.
+enum Shape {
+    // The elements after the first `kept` ones, which ended before the edit and stay as they are,
+    // with null in place of the next level.
+    Array {
+        len: usize,
+        kept: usize,
+        rest: Vec<Value>,
+    },
+    // The offsets of the members that ended before the edit, which stay as they are unless a
+    // member with the same key follows, and copies of the others.
+    Object {
+        kept: HashMap<String, usize>,
+        rest: Map<String, Value>,
+    },
+}
+
+impl Document {
+    pub fn new() -> Document {
+        Document::with_parser(JsonStreamParser::new())
+    }
+
+    // A document parsed by `parser`, which has not been given any input yet, with its limits,
+    // duplicate key policy and recovery mode.
+    pub fn with_parser(parser: JsonStreamParser) -> Document {
+        let mut parser = parser.record_spans();
+        if parser.redaction.is_none() && parser.interval().is_none() {
+            parser = parser.rewindable(INTERVAL);
+        }
+        Document {
+            text: String::new(),
+            chars: 0,
+            template: parser.clone(),
+            parser,
+            error: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn value(&self) -> &Value {
+        self.parser.get_result()
+    }
+
+    // The error the text was parsed up to, if any.
+    pub fn error(&self) -> Option<&ParseError> {
+        self.error.as_ref()
+    }
+
+    // Inserts `text` at the char offset `offset`.
+    pub fn insert(&mut self, offset: usize, text: &str) -> Update<'_> {
+        self.replace(offset..offset, text)
+    }
+
+    // Removes the chars in `range`.
+    pub fn delete(&mut self, range: Range<usize>) -> Update<'_> {
+        self.replace(range, "")
+    }
+
+    // Replaces the chars in `range` with `text`, and parses the text again from the last mark
+    // before the edit. Panics if the range is out of bounds.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) -> Update<'_> {
+        let start = self.byte_offset(range.start);
+        let end = self.byte_offset(range.end);
+        let before = self.before(&(start..end));
+        let removed = self.text[start..end].to_string();
+        self.text.replace_range(start..end, text);
+        self.chars = self.chars - range.len() + text.chars().count();
+
+        let offset = range.start.min(self.parser.char_offset());
+        if offset < self.parser.char_offset() && self.parser.truncate_to(offset).is_err() {
+            self.parser = self.template.clone();
+        }
+        self.error = parse(&mut self.parser, &self.text);
+
+        let mut changed = BTreeSet::new();
+        let old = match before {
+            Before::Value(old) => Some(old),
+            Before::Levels(levels) => {
+                let spans = self.parser.spans().unwrap();
+                let value = self.parser.get_result();
+                if compare(&levels, spans, value, &mut changed) {
+                    None
+                } else {
+                    changed.clear();
+                    Some(self.value_before(start..start + text.len(), &removed))
+                }
+            }
+        };
+        if let Some(old) = old {
+            diff(&old, self.parser.get_result(), String::new(), &mut changed);
+            drop_value(old);
+        }
+        Update {
+            value: self.parser.get_result(),
+            changed,
+        }
+    }
+
+    // What the edit of the bytes in `range` can change. Unless the values can depend on more
+    // than their own text, that is the containers the edit is in, and in them the values that
+    // have not ended before it. If the whole text has to be parsed again, the value is taken from
+    // the parser and a new one takes its place.
+    fn before(&mut self, range: &Range<usize>) -> Before {
+        let parser = &self.parser;
+        let value = parser.get_result();
+        let fresh = match parser.spans().unwrap().root_node() {
+            // a parser that redacts keeps no input to go back to
+            _ if parser.redaction.is_some() => true,
+            Some(_) if parser.projection.is_some() || parser.recovery.is_some() => false,
+            Some(root) => {
+                let spans = parser.spans().unwrap();
+                let span = spans.node_span(root);
+                // a number could go on, but any other value that has ended stays as it is
+                if span.end.map_or(false, |end| {
+                    range.start > end.byte || (range.start == end.byte && !value.is_number())
+                }) {
+                    return Before::Levels(Vec::new());
+                }
+                if is_container(value) && around(spans, root, range) {
+                    return Before::Levels(levels(spans, root, value, range));
+                }
+                range.start <= span.start.byte
+            }
+            None => false,
+        };
+        if fresh {
+            let parser = std::mem::replace(&mut self.parser, self.template.clone());
+            Before::Value(parser.into_result())
+        } else {
+            Before::Value(copy_value(value))
+        }
+    }
+
+    // The value of the text as it was before the bytes in `range` replaced `removed`, parsed
+    // again from the start.
+    fn value_before(&self, range: Range<usize>, removed: &str) -> Value {
+        let mut text = String::with_capacity(self.text.len() - range.len() + removed.len());
+        text.push_str(&self.text[..range.start]);
+        text.push_str(removed);
+        text.push_str(&self.text[range.end..]);
+        let mut parser = self.template.clone();
+        parse(&mut parser, &text);
+        parser.into_result()
+    }
+
+    fn byte_offset(&self, offset: usize) -> usize {
+        if offset > self.chars {
+            panic!("char offset {offset} is out of bounds");
+        }
+        if self.chars == self.text.len() {
+            return offset;
+        }
+        self.text
+            .char_indices()
+            .nth(offset)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Dropping a `Value` recurses once per level of nesting, like in `SerdeValueBuilder`.
+impl Drop for Level {
+    fn drop(&mut self) {
+        let values = match &mut self.shape {
+            Shape::Array { rest, .. } => Value::Array(std::mem::take(rest)),
+            Shape::Object { rest, .. } => Value::Object(std::mem::take(rest)),
+        };
+        drop_value(values);
+    }
+}
+
+// Gives `parser` the rest of `text`, up to its first error, in chunks of the interval of its
+// marks so that an edit can go back to a mark just before it. Returns the error.
+fn parse(parser: &mut JsonStreamParser, text: &str) -> Option<ParseError> {
+    let interval = parser.interval().unwrap_or(usize::MAX);
+    let mut from = parser.offset;
+    while from < text.len() {
+        let mut to = from.saturating_add(interval).min(text.len());
+        while !text.is_char_boundary(to) {
+            to += 1;
+        }
+        if let Err(error) = parser.add_str(&text[from..to]) {
+            // the input before the error is fine on its own
+            let _ = parser.add_str(&text[from..error.offset()]);
+            return Some(error);
+        }
+        from = to;
+    }
+    None
+}
+
+fn is_container(value: &Value) -> bool {
+    value.is_array() || value.is_object()
+}
+
+// Whether the value at `index` starts before the bytes in `range` and ends after them.
+fn around(spans: &Spans, index: usize, range: &Range<usize>) -> bool {
+    let span = spans.node_span(index);
+    span.start.byte < range.start && span.end.map_or(true, |end| range.end < end.byte)
+}
+
+// Whether the value at `index` ended before the byte offset `offset`.
+fn ended_before(spans: &Spans, index: usize, offset: usize) -> bool {
+    spans
+        .node_span(index)
+        .end
+        .map_or(false, |end| end.byte < offset)
+}
+
+// The containers from `root` down that the bytes in `range` are in.
+fn levels(spans: &Spans, root: usize, value: &Value, range: &Range<usize>) -> Vec<Level> {
+    let mut levels = Vec::new();
+    let mut next = Some((Name::Root, root, value));
+    while let Some((name, node, value)) = next.take() {
+        let children = spans.children(node);
+        let shape = match value {
+            Value::Array(items) => {
+                let kept = children
+                    .partition_point(|&child| ended_before(spans, child, range.start))
+                    .min(items.len());
+                next = children
+                    .get(kept)
+                    .filter(|&&child| around(spans, child, range))
+                    .zip(items.get(kept).filter(|item| is_container(item)))
+                    .map(|(&child, item)| (Name::Index(kept), child, item));
+                let rest = items[kept..]
+                    .iter()
+                    .enumerate()
+                    .map(|(index, item)| match next {
+                        Some(_) if index == 0 => Value::Null,
+                        _ => copy_value(item),
+                    })
+                    .collect();
+                Shape::Array {
+                    len: items.len(),
+                    kept,
+                    rest,
+                }
+            }
+            Value::Object(members) => {
+                // the last member to start before the edit, if the edit is in its value
+                let last = children
+                    .partition_point(|&child| spans.node_span(child).start.byte < range.start);
+                next = last
+                    .checked_sub(1)
+                    .map(|index| children[index])
+                    .filter(|&child| around(spans, child, range))
+                    .and_then(|child| {
+                        let key = spans.name(child)?;
+                        let member = members.get(key).filter(|member| is_container(member))?;
+                        let resolved = spans.member(node, key) == Some(child)
+                            && spans.pending_key(node) != Some(key);
+                        resolved.then(|| (Name::Key(key.to_string()), child, member))
+                    });
+                let mut kept = HashMap::new();
+                let mut rest = Map::new();
+                for (key, member) in members {
+                    if matches!(&next, Some((Name::Key(next), ..)) if next == key) {
+                        continue;
+                    }
+                    match spans.member(node, key) {
+                        Some(child)
+                            if ended_before(spans, child, range.start)
+                                && spans.pending_key(node) != Some(key) =>
+                        {
+                            kept.insert(key.clone(), spans.node_span(child).start.byte);
+                        }
+                        _ => {
+                            rest.insert(key.clone(), copy_value(member));
+                        }
+                    }
+                }
+                Shape::Object { kept, rest }
+            }
+            _ => unreachable!("only containers have levels"),
+        };
+        levels.push(Level {
+            name,
+            start: spans.node_span(node).start.byte,
+            shape,
+        });
+    }
+    levels
+}
+
+// Adds the paths where the containers of `levels` now differ from what they were. Returns false
+// if the edit has moved one of them, or a member kept from before it, which then have to be
+// compared as a whole.
+fn compare(
+    levels: &[Level],
+    spans: &Spans,
+    mut value: &Value,
+    changed: &mut BTreeSet<String>,
+) -> bool {
+    let mut path = String::new();
+    let mut node = None;
+    for (index, level) in levels.iter().enumerate() {
+        let found = match (&level.name, node) {
+            (Name::Root, _) => spans.root_node(),
+            (Name::Index(position), Some(parent)) => {
+                push_pointer_token(&mut path, &position.to_string());
+                value = match value.get(position) {
+                    Some(value) => value,
+                    None => return false,
+                };
+                spans.children(parent).get(*position).copied()
+            }
+            (Name::Key(key), Some(parent)) => {
+                if spans.pending_key(parent) == Some(key) {
+                    return false;
+                }
+                push_pointer_token(&mut path, key);
+                value = match value.get(key) {
+                    Some(value) => value,
+                    None => return false,
+                };
+                spans.member(parent, key)
+            }
+            _ => None,
+        };
+        let Some(found) = found.filter(|&found| spans.node_span(found).start.byte == level.start)
+        else {
+            return false;
+        };
+        node = Some(found);
+        let next = levels.get(index + 1).map(|level| &level.name);
+        let child_path = |token: &str| {
+            let mut child = path.clone();
+            push_pointer_token(&mut child, token);
+            child
+        };
+        match (&level.shape, value) {
+            (Shape::Array { len, kept, rest }, Value::Array(items)) => {
+                for position in *kept..(*len).max(items.len()) {
+                    if next == Some(&Name::Index(position)) {
+                        continue;
+                    }
+                    let path = child_path(&position.to_string());
+                    match (rest.get(position - kept), items.get(position)) {
+                        (Some(old), Some(new)) => diff(old, new, path, changed),
+                        _ => {
+                            changed.insert(path);
+                        }
+                    }
+                }
+            }
+            (Shape::Object { kept, rest }, Value::Object(members)) => {
+                if spans
+                    .pending_key(found)
+                    .map_or(false, |key| kept.contains_key(key))
+                {
+                    return false;
+                }
+                for (key, start) in kept {
+                    let same = spans
+                        .member(found, key)
+                        .map(|child| spans.node_span(child).start.byte);
+                    if same != Some(*start) {
+                        return false;
+                    }
+                }
+                for (key, old) in rest {
+                    match members.get(key) {
+                        Some(new) => diff(old, new, child_path(key), changed),
+                        None => {
+                            changed.insert(child_path(key));
+                        }
+                    }
+                }
+                for key in members.keys() {
+                    let next = matches!(next, Some(Name::Key(next)) if next == key);
+                    if !next && !kept.contains_key(key) && !rest.contains_key(key) {
+                        changed.insert(child_path(key));
+                    }
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+// Copies `value` with an explicit stack, for values that may be nested very deeply.
+fn copy_value(value: &Value) -> Value {
+    // a container being copied, with the copy so far and the key of the member being copied
+    enum Copying<'a> {
+        Array(std::slice::Iter<'a, Value>, Vec<Value>),
+        Object(serde_json::map::Iter<'a>, Map<String, Value>, String),
+    }
+    let mut stack = Vec::new();
+    let mut next = value;
+    loop {
+        let mut copied = match next {
+            Value::Array(items) => {
+                stack.push(Copying::Array(
+                    items.iter(),
+                    Vec::with_capacity(items.len()),
+                ));
+                None
+            }
+            Value::Object(members) => {
+                stack.push(Copying::Object(members.iter(), Map::new(), String::new()));
+                None
+            }
+            scalar => Some(scalar.clone()),
+        };
+        // hands the copies that are done to their containers, up to one with a value left
+        loop {
+            let Some(top) = stack.last_mut() else {
+                return copied.unwrap();
+            };
+            if let Some(copy) = copied.take() {
+                match top {
+                    Copying::Array(_, items) => items.push(copy),
+                    Copying::Object(_, members, key) => {
+                        members.insert(std::mem::take(key), copy);
+                    }
+                }
+            }
+            let following = match top {
+                Copying::Array(items, _) => items.next(),
+                Copying::Object(members, _, key) => members.next().map(|(name, member)| {
+                    key.clone_from(name);
+                    member
+                }),
+            };
+            if let Some(following) = following {
+                next = following;
+                break;
+            }
+            copied = Some(match stack.pop().unwrap() {
+                Copying::Array(_, items) => Value::Array(items),
+                Copying::Object(_, members, _) => Value::Object(members),
+            });
+        }
+    }
+}
+
+// A step from a value to one in it.
+enum Part<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+// Adds the paths under `path` where `old` and `new` differ. Like the rest of this crate it walks
+// the values with an explicit stack, and a path is only copied once it has changed.
+fn diff(old: &Value, new: &Value, mut path: String, changed: &mut BTreeSet<String>) {
+    let mut pending = vec![(path.len(), None, old, new)];
+    while let Some((len, part, old, new)) = pending.pop() {
+        path.truncate(len);
+        match part {
+            Some(Part::Key(key)) => push_pointer_token(&mut path, key),
+            Some(Part::Index(index)) => push_pointer_token(&mut path, &index.to_string()),
+            None => {}
+        }
+        let len = path.len();
+        match (old, new) {
+            (Value::Object(old), Value::Object(new)) => {
+                for (key, value) in old {
+                    match new.get(key) {
+                        Some(other) => pending.push((len, Some(Part::Key(key)), value, other)),
+                        None => {
+                            push_pointer_token(&mut path, key);
+                            changed.insert(path.clone());
+                            path.truncate(len);
+                        }
+                    }
+                }
+                for key in new.keys().filter(|key| !old.contains_key(*key)) {
+                    push_pointer_token(&mut path, key);
+                    changed.insert(path.clone());
+                    path.truncate(len);
+                }
+            }
+            (Value::Array(old), Value::Array(new)) => {
+                for index in 0..old.len().max(new.len()) {
+                    match (old.get(index), new.get(index)) {
+                        (Some(value), Some(other)) => {
+                            pending.push((len, Some(Part::Index(index)), value, other))
+                        }
+                        _ => {
+                            push_pointer_token(&mut path, &index.to_string());
+                            changed.insert(path.clone());
+                            path.truncate(len);
+                        }
+                    }
+                }
+            }
+            _ if old != new => {
+                changed.insert(path.clone());
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+// Why the input was rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub enum ErrorKind {
+    // The input is not valid JSON.
+    Syntax(String),
+    // The input bytes are not valid UTF-8.
+    InvalidUtf8(String),
+    // A key appeared twice in an object under `DuplicateKeys::Error`.
+    DuplicateKey(String),
+    // One variant per field of `Limits`.
+    MaxDepth,
+    MaxLength,
+    MaxStringLength,
+    MaxKeyLength,
+    MaxNumberLength,
+    MaxArrayLength,
+    MaxObjectMembers,
+    MaxNodes,
+    MaxMemory,
+    // A `Dispatch` could not route the document, or its target rejected it.
+    Target(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Syntax(message)
+            | ErrorKind::InvalidUtf8(message)
+            | ErrorKind::Target(message) => f.write_str(message),
+            ErrorKind::DuplicateKey(key) => write!(f, "Duplicate key {key:?}"),
+            ErrorKind::MaxDepth => f.write_str("max depth exceeded"),
+            ErrorKind::MaxLength => f.write_str("input length limit exceeded"),
+            ErrorKind::MaxStringLength => f.write_str("string length limit exceeded"),
+            ErrorKind::MaxKeyLength => f.write_str("key length limit exceeded"),
+            ErrorKind::MaxNumberLength => f.write_str("number length limit exceeded"),
+            ErrorKind::MaxArrayLength => f.write_str("array length limit exceeded"),
+            ErrorKind::MaxObjectMembers => f.write_str("object member limit exceeded"),
+            ErrorKind::MaxNodes => f.write_str("node limit exceeded"),
+            ErrorKind::MaxMemory => f.write_str("memory limit exceeded"),
+        }
+    }
+}
+
+// An error with the byte offset in the input where it was detected: the offending byte, or the
+// first byte that exceeds a limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParseError {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+impl ParseError {
+    pub fn new(kind: ErrorKind, offset: usize) -> ParseError {
+        ParseError { kind, offset }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // Moves an error found in a chunk of input to its position in the whole input.
+    pub(crate) fn shifted(mut self, offset: usize) -> ParseError {
+        self.offset += offset;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
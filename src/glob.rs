@@ -0,0 +1,52 @@
+use crate::pointer_tokens;
+
+// A JSON Pointer whose tokens can be `*`, for any one token, or `**`, for any number of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Glob {
+    tokens: Vec<String>,
+}
+
+impl Glob {
+    pub(crate) fn new(pattern: &str) -> Option<Glob> {
+        Some(Glob {
+            tokens: pointer_tokens(pattern)?,
+        })
+    }
+
+    // whether the value at `path`, given as its unescaped tokens, is matched
+    pub(crate) fn matches(&self, path: &[String]) -> bool {
+        matches(&self.tokens, path)
+    }
+
+    // whether the value at `path` or one in it can be matched
+    pub(crate) fn reaches(&self, path: &[String]) -> bool {
+        reaches(&self.tokens, path)
+    }
+}
+
+fn matches(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skipped| matches(rest, &path[skipped..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((token, path)) => (first == "*" || first == token) && matches(rest, path),
+            None => false,
+        },
+    }
+}
+
+fn reaches(pattern: &[String], path: &[String]) -> bool {
+    let (token, path) = match path.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+    match pattern.split_first() {
+        // the rest of the path can all be matched by `**`
+        Some((first, _)) if first == "**" => true,
+        Some((first, rest)) => (first == "*" || first == token) && reaches(rest, path),
+        None => false,
+    }
+}
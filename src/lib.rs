@@ -1,666 +1,1238 @@
-use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-fn ends_with_odd_backslashes(s: &str) -> bool {
-    s.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
-}
+use serde_json::{Number, Value};
 
-fn decode_json_string(raw: &str) -> Result<String, String> {
-    serde_json::from_str::<String>(&format!("\"{raw}\"")).map_err(|e| e.to_string())
-}
+#[cfg(feature = "async")]
+mod async_stream;
+mod attribution;
+mod builder;
+#[cfg(feature = "checkpoint")]
+mod checkpoint;
+#[cfg(feature = "codec")]
+mod codec;
+mod cursor;
+mod dispatch;
+mod document;
+mod error;
+mod glob;
+mod limits;
+mod partial;
+mod projection;
+mod reader;
+mod recovery;
+mod redact;
+mod rewind;
+#[cfg(feature = "shared")]
+mod shared;
+mod spans;
+mod stop;
+mod timing;
+mod transaction;
+mod utf8;
+mod write;
 
-#[derive(Clone, Debug)]
-enum ObjectStatus {
-    // We are ready to start a new object.
+#[cfg(feature = "async")]
+pub use async_stream::{AsyncParseError, ReaderSnapshotStream, SnapshotStream};
+pub use builder::{Scalar, SerdeValueBuilder, ValueBuilder};
+#[cfg(feature = "checkpoint")]
+pub use checkpoint::CHECKPOINT_VERSION;
+#[cfg(feature = "codec")]
+pub use codec::{CodecError, JsonCodec};
+pub use cursor::ParseState;
+pub use dispatch::{Dispatch, DispatchState, PartialTarget, Typed};
+pub use document::{Document, Update};
+pub use error::{ErrorKind, ParseError};
+pub use limits::Limits;
+pub use partial::{PartialNode, PartialValue};
+pub use reader::{parse_reader, Granularity, ReaderSnapshots, Snapshot};
+pub use recovery::Recovery;
+pub use redact::Redaction;
+pub use rewind::{CheckpointId, RewindError};
+#[cfg(feature = "shared")]
+pub use shared::{SharedMap, SharedValue, SharedValueBuilder};
+pub use spans::{Location, Span, Spans, Timing};
+pub use stop::{Feed, StopWhen};
+
+use glob::Glob;
+use limits::{overflow, MEMBER_SIZE, NODE_SIZE};
+use recovery::Resync;
+use rewind::History;
+use timing::Clock;
+use transaction::{Event, Events, Undo};
+use utf8::Utf8Decoder;
+
+// What a frame expects next. Every open container has a frame, and the root value always has the
+// bottom one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+enum FrameState {
+    // Nothing but whitespace has been received at the root. The root stays in this state while a
+    // string or scalar root value is being lexed.
     Ready,
-    // We are in the beginning of a string, likely because we just received an opening quote.
-    StringQuoteOpen,
-    // We just finished a string, likely because we just received a closing quote.
-    StringQuoteClose,
-    // We are in the middle of a scalar value, likely because we just received a digit.
-    Scalar {
-        value_so_far: Vec<char>,
-    },
-    ScalarNumber {
-        value_so_far: Vec<char>,
-    },
-    // We just started an array, after receiving an opening bracket.
-    StartArray,
-    // We are in the beginning of an array string value.
-    ArrayValueQuoteOpen {
-        index: usize,
-    },
-    // We just closed an array string value.
-    ArrayValueQuoteClose,
-    // We are taking an array scalar value (numbers, booleans, etc.).
-    ArrayValueScalar {
-        index: usize,
-        value_so_far: Vec<char>,
-    },
-    // A nested object/array being parsed inside an array
-    ArrayValueNested,
-    // We just started a property, likely because we just received an opening brace or a comma in case of an existing object.
-    StartProperty,
-    // We are in the beginning of a key, likely because we just received a quote. We need to store the key_so_far because
-    // unlike the value, we cannot add the key to the object until it is complete.
-    KeyQuoteOpen {
-        key_so_far: Vec<char>,
-    },
-    // We just finished a key, likely because we just received a closing quote.
-    KeyQuoteClose {
-        key: Vec<char>,
-    },
-    // We just finished a key, likely because we just received a colon.
-    Colon {
-        key: Vec<char>,
-    },
-    // We are in the beginning of a value, likely because we just received a quote.
-    ValueQuoteOpen {
-        key: Vec<char>,
-        // We don't need to store the valueSoFar because we can add the value to the object immediately.
+    // After `[`, or after a `,` in an array or a `:` in an object. The container stays in this
+    // state while a string or scalar value is being lexed.
+    ExpectValue,
+    // After `{` or after a `,` in an object. The object stays in this state while a key is being
+    // lexed.
+    ExpectKey,
+    // After a key, waiting for `:`.
+    ExpectColon,
+    // After a value in a container, waiting for `,` or the closing bracket.
+    AfterValue,
+    // A nested container is being parsed in the frame above.
+    Nested,
+    // The root value has been completely parsed.
+    Done,
+}
+
+// Where we are inside a string or key after the last byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+enum Escape {
+    None,
+    // just after a backslash
+    Backslash,
+    // in a `\u` escape, after `digits` hex digits. `high` is the first half of a surrogate pair
+    // when this escape is its second half.
+    Unicode {
+        high: Option<u16>,
+        code: u16,
+        digits: u8,
     },
-    ValueQuoteClose,
-
-    // We are taking any value that is not a string. For these case we just store
-    // each character until we reach a comma or a closing brace and then we pare
-    // and add the value to the object.
-    ValueScalar {
-        key: Vec<char>,
-        value_so_far: Vec<char>,
+    // after the first half of a surrogate pair, waiting for the `\u` of the second half
+    Surrogate {
+        high: u16,
+        backslash: bool,
     },
-    // A nested object/array being parsed as a property value
-    ValueNested {
-        key: Vec<char>,
+}
+
+// The text of `true`, `false` or `null`. Being an alias, it is not taken for borrowed text when
+// a checkpoint is deserialized.
+type LiteralText = &'static [u8];
+
+// The token the innermost frame is in the middle of.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+enum Token {
+    #[default]
+    None,
+    Key(Escape),
+    String(Escape),
+    // the text so far is kept in `JsonStreamParser::number`
+    Number,
+    Literal {
+        #[cfg_attr(feature = "checkpoint", serde(with = "checkpoint::literal"))]
+        text: LiteralText,
+        matched: usize,
     },
+}
 
-    // We just finished the object, likely because we just received a closing brace.
-    Closed,
-}
-
-// this function takes and existing object that we are building along with a single character as we as an address
-// to the current position in the object that we are in and returns the object with that character added along with
-// the new address.
-fn process_char(
-    object: &mut Value,
-    current_status: &mut ObjectStatus,
-    current_char: char,
-) -> Result<(), String> {
-    match (object, current_status, current_char) {
-        (val @ Value::Null, sts @ ObjectStatus::Ready, '"') => {
-            *val = json!("");
-            *sts = ObjectStatus::StringQuoteOpen;
-        }
-        (val @ Value::Null, sts @ ObjectStatus::Ready, '{') => {
-            *val = json!({});
-            *sts = ObjectStatus::StartProperty;
-        }
-        (val @ Value::Null, sts @ ObjectStatus::Ready, '[') => {
-            *val = json!([]);
-            *sts = ObjectStatus::StartArray;
-        }
-        // ------ true ------
-        (val @ Value::Null, sts @ ObjectStatus::Ready, 't') => {
-            *val = json!(true);
-            *sts = ObjectStatus::Scalar {
-                value_so_far: vec!['t'],
-            };
-        }
-        (
-            Value::Bool(true),
-            ObjectStatus::Scalar {
-                ref mut value_so_far,
-            },
-            'r',
-        ) if *value_so_far == vec!['t'] => {
-            value_so_far.push('r');
-        }
-        (
-            Value::Bool(true),
-            ObjectStatus::Scalar {
-                ref mut value_so_far,
-            },
-            'u',
-        ) if *value_so_far == vec!['t', 'r'] => {
-            value_so_far.push('u');
-        }
-        (Value::Bool(true), sts @ ObjectStatus::Scalar { .. }, 'e') => {
-            *sts = ObjectStatus::Closed;
-        }
-        // ------ false ------
-        (val @ Value::Null, sts @ ObjectStatus::Ready, 'f') => {
-            *val = json!(false);
-            *sts = ObjectStatus::Scalar {
-                value_so_far: vec!['f'],
-            };
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+struct Frame {
+    state: FrameState,
+    // whether the container is an object rather than an array
+    object: bool,
+    // the number of elements or members so far
+    members: usize,
+    // The key of the object member being parsed. It is kept from one member to the next so that
+    // its buffer is reused.
+    key: String,
+    // the keys of the members so far, and whether each has been collected into an array, unless
+    // the last value of a duplicate key wins
+    keys: HashMap<String, bool>,
+}
+
+impl Frame {
+    fn new(state: FrameState, object: bool) -> Frame {
+        Frame {
+            state,
+            object,
+            members: 0,
+            key: String::new(),
+            keys: HashMap::new(),
         }
-        (
-            Value::Bool(false),
-            ObjectStatus::Scalar {
-                ref mut value_so_far,
-            },
-            'a',
-        ) if *value_so_far == vec!['f'] => {
-            value_so_far.push('a');
-        }
-        (
-            Value::Bool(false),
-            ObjectStatus::Scalar {
-                ref mut value_so_far,
-            },
-            'l',
-        ) if *value_so_far == vec!['f', 'a'] => {
-            value_so_far.push('l');
-        }
-        (
-            Value::Bool(false),
-            ObjectStatus::Scalar {
-                ref mut value_so_far,
-            },
-            's',
-        ) if *value_so_far == vec!['f', 'a', 'l'] => {
-            value_so_far.push('s');
-        }
-        (Value::Bool(false), sts @ ObjectStatus::Scalar { .. }, 'e') => {
-            *sts = ObjectStatus::Closed;
-        }
-        // ------ null ------
-        (val @ Value::Null, sts @ ObjectStatus::Ready, 'n') => {
-            *val = json!(null);
-            *sts = ObjectStatus::Scalar {
-                value_so_far: vec!['n'],
-            };
+    }
+}
+
+// A number no other parser state has had, for `JsonStreamParser::generation`.
+pub(crate) fn next_generation() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+pub(crate) fn continues_number(byte: u8) -> bool {
+    matches!(byte, b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-')
+}
+
+// The length of the longest prefix of `text` that is a whole JSON number, or 0.
+fn number_prefix(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let digits = |from: usize| {
+        from + bytes[from.min(bytes.len())..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count()
+    };
+    let mut end = usize::from(bytes.first() == Some(&b'-'));
+    end = match bytes.get(end) {
+        Some(b'0') => end + 1,
+        Some(b'1'..=b'9') => digits(end),
+        _ => return 0,
+    };
+    if bytes.get(end) == Some(&b'.') {
+        match digits(end + 1) {
+            fraction if fraction > end + 1 => end = fraction,
+            _ => return end,
         }
-        (
-            Value::Null,
-            ObjectStatus::Scalar {
-                ref mut value_so_far,
-            },
-            'u',
-        ) if *value_so_far == vec!['n'] => {
-            value_so_far.push('u');
-        }
-        (
-            Value::Null,
-            ObjectStatus::Scalar {
-                ref mut value_so_far,
-            },
-            'l',
-        ) if *value_so_far == vec!['n', 'u'] => {
-            value_so_far.push('l');
-        }
-        (Value::Null, sts @ ObjectStatus::Scalar { .. }, 'l') => {
-            *sts = ObjectStatus::Closed;
-        }
-        // ------ number ------
-        (val @ Value::Null, sts @ ObjectStatus::Ready, c @ '0'..='9') => {
-            let digit = c.to_digit(10).ok_or_else(|| "invalid digit".to_string())?;
-            *val = Value::Number(digit.into());
-            *sts = ObjectStatus::ScalarNumber {
-                value_so_far: vec![c],
-            };
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let sign = end + 1 + usize::from(matches!(bytes.get(end + 1), Some(b'+' | b'-')));
+        let exponent = digits(sign);
+        if exponent > sign {
+            end = exponent;
         }
-        (val @ Value::Null, sts @ ObjectStatus::Ready, '-') => {
-            *val = Value::Number(0.into());
-            *sts = ObjectStatus::ScalarNumber {
-                value_so_far: vec!['-'],
-            };
+    }
+    end
+}
+
+// The end of the plain string content starting at `position`: the next quote, backslash or control
+// character, or the end of the input.
+fn string_run_end(bytes: &[u8], position: usize) -> usize {
+    let rest = &bytes[position..];
+    let end = memchr::memchr2(b'"', b'\\', rest).unwrap_or(rest.len());
+    // control characters are invalid in strings, so they are rare enough to look for separately
+    let end = rest[..end]
+        .iter()
+        .position(|&byte| byte < 0x20)
+        .unwrap_or(end);
+    position + end
+}
+
+fn literal_value(text: &[u8]) -> Scalar {
+    match text {
+        b"true" => Scalar::Bool(true),
+        b"false" => Scalar::Bool(false),
+        _ => Scalar::Null,
+    }
+}
+
+// `context` says where in the grammar the character was found, such as "after a value".
+fn invalid_character(input: &str, position: usize, context: impl fmt::Display) -> ParseError {
+    let c = input[position..].chars().next().unwrap_or_default();
+    syntax_error(format!("Invalid character {c} {context}"), position)
+}
+
+impl fmt::Display for FrameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FrameState::Ready => "before a value",
+            FrameState::ExpectValue => "where a value was expected",
+            FrameState::ExpectKey => "where a key was expected",
+            FrameState::ExpectColon => "after a key, where `:` was expected",
+            FrameState::AfterValue => "after a value",
+            FrameState::Nested => "in a nested value",
+            FrameState::Done => "after the end of the document",
+        })
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::None => f.write_str("between tokens"),
+            Token::Key(Escape::None) => f.write_str("in a key"),
+            Token::String(Escape::None) => f.write_str("in a string"),
+            Token::Key(escape) | Token::String(escape) => escape.fmt(f),
+            Token::Number => f.write_str("in a number"),
+            Token::Literal { text, .. } => {
+                write!(f, "in literal `{}`", String::from_utf8_lossy(text))
+            }
         }
-        (
-            Value::Number(ref mut num),
-            ObjectStatus::ScalarNumber {
-                ref mut value_so_far,
-            },
-            c @ '0'..='9',
-        ) => {
-            value_so_far.push(c);
-            // if the number contains a decimal point or an exponent, parse as f64
-            if value_so_far.contains(&'.')
-                || value_so_far.contains(&'e')
-                || value_so_far.contains(&'E')
-            {
-                let parsed_number = value_so_far
-                    .iter()
-                    .collect::<String>()
-                    .parse::<f64>()
-                    .map_err(|e| e.to_string())?;
-
-                if let Some(json_number) = serde_json::Number::from_f64(parsed_number) {
-                    *num = json_number;
+    }
+}
+
+impl fmt::Display for Escape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Escape::None => "in a string",
+            Escape::Backslash => "in a string escape",
+            Escape::Unicode { .. } => "in a `\\u` escape",
+            Escape::Surrogate { .. } => "after the first half of a surrogate pair",
+        })
+    }
+}
+
+fn syntax_error(message: String, position: usize) -> ParseError {
+    ParseError::new(ErrorKind::Syntax(message), position)
+}
+
+fn exceeds(count: usize, limit: Option<usize>) -> bool {
+    limit.map_or(false, |limit| count > limit)
+}
+
+pub fn parse_stream(json_string: &str) -> Result<Value, ParseError> {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(json_string)?;
+    parser.end_root_number()?;
+    Ok(parser.into_result())
+}
+
+pub fn parse_stream_with_limits(
+    json_string: &str,
+    max_depth: Option<usize>,
+    max_length: Option<usize>,
+) -> Result<Value, ParseError> {
+    let mut parser = JsonStreamParser::with_limits(max_depth, max_length);
+    parser.add_str(json_string)?;
+    parser.end_root_number()?;
+    Ok(parser.into_result())
+}
+
+// What to do with a key that appears twice in the same object.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub enum DuplicateKeys {
+    // The last value replaces the earlier ones, in the position of the first, like `serde_json`.
+    #[default]
+    LastWins,
+    // Later values are parsed but left out of the document.
+    FirstWins,
+    // The duplicate key is a parse error.
+    Error,
+    // The member becomes an array of every value given for the key, in order.
+    Collect,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Lexeme {
+    String,
+    Key,
+    Scalar,
+    None,
+}
+
+// Where the lexer was before one of its steps, which lexes a byte outside of any token or a run
+// of bytes in one. Given to the `stop` callback of `add_str_until`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Step {
+    pub(crate) lexical: (usize, Lexeme),
+    pub(crate) complete: bool,
+    // the first byte of the step
+    pub(crate) byte: u8,
+}
+
+// Called after every step of the lexer by `add_str_until`, to end the chunk after the step.
+type StopAt<'a, B> = Option<&'a mut dyn FnMut(&JsonStreamParser<B>, Step) -> bool>;
+
+// Lexes JSON text and drives a `ValueBuilder` with it. The default `SerdeValueBuilder` keeps the
+// partial document as a `serde_json::Value`, which `get_result` returns.
+#[derive(Clone)]
+pub struct JsonStreamParser<B = SerdeValueBuilder> {
+    builder: B,
+    // the root frame followed by one frame per open nested container
+    stack: Vec<Frame>,
+    token: Token,
+    // the text of the number being lexed, reused from one number to the next
+    number: String,
+    duplicate_keys: DuplicateKeys,
+    // the frame of the object whose duplicate member is being parsed without being built, under
+    // `DuplicateKeys::FirstWins`
+    skip: Option<usize>,
+    limits: Limits,
+    // the bytes of input before the current chunk
+    offset: usize,
+    // Taken from `next_generation` when the parser is made or goes back, so that an offset
+    // together with it always stands for the same input.
+    generation: usize,
+    processed_chars: usize,
+    // the decoded length of the string being lexed
+    string_length: usize,
+    nodes: usize,
+    // the estimated size of the document, checked against `Limits::max_memory`
+    memory: usize,
+    // bytes of a UTF-8 sequence split across two `io::Write::write` calls
+    utf8: Utf8Decoder,
+    // the first error raised through `Extend` or `fmt::Write`, which cannot return it
+    deferred_error: Option<ParseError>,
+    // what to do with malformed values, if syntax errors are recovered from
+    recovery: Option<Recovery>,
+    // how the input is being skipped after a syntax error in recovery mode
+    resync: Option<Resync>,
+    // the syntax errors recovered from
+    diagnostics: Vec<ParseError>,
+    // where the values and keys are in the input, if recorded
+    spans: Option<Spans>,
+    // the time chunks are received at, if the parser is timed
+    clock: Option<Clock>,
+    // the conditions under which `feed` says to stop
+    stop_conditions: Vec<StopWhen>,
+    // which values are replaced with a placeholder, if any
+    redaction: Option<Redaction>,
+    // the frame holding the redacted value being parsed, whose events are left out
+    redacted: Option<usize>,
+    // the string being lexed while it is held back for `Redaction::matching`
+    held: Option<String>,
+    // the paths to build, if not all of them
+    projection: Option<Vec<Glob>>,
+    // the frame holding the selected value being parsed, in which everything is built
+    selected: Option<usize>,
+    // the number of open containers, outermost first, that hold a selected value
+    holding: usize,
+    // the builder calls for the current chunk, made once it has been lexed without an error
+    events: Events,
+    // what it takes to put the lexer back as it was before the current chunk
+    undo: Undo,
+    // the input kept for `truncate_to`, if any
+    history: Option<History<B>>,
+}
+
+impl Default for JsonStreamParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonStreamParser {
+    pub fn new() -> JsonStreamParser {
+        Self::with_limits(None, None)
+    }
+
+    pub fn with_limits(max_depth: Option<usize>, max_length: Option<usize>) -> JsonStreamParser {
+        Self::with_builder_and_limits(SerdeValueBuilder::new(), max_depth, max_length)
+    }
+
+    // Keeps a persistent copy of the completed part of the document next to the `serde_json::Value`
+    // result, so that `snapshot` shares completed subtrees instead of converting the whole document.
+    #[cfg(feature = "shared")]
+    pub fn shared_snapshots(mut self) -> JsonStreamParser {
+        self.builder.share();
+        self
+    }
+
+    // A snapshot of the partial document, equal to `get_result`. With `shared_snapshots` enabled
+    // it is built in O(depth), plus the length of a string that is still being written, and shares
+    // every completed subtree with the previous snapshots.
+    #[cfg(feature = "shared")]
+    pub fn snapshot(&self) -> SharedValue {
+        self.builder.snapshot()
+    }
+
+    pub fn get_result(&self) -> &Value {
+        self.builder.value()
+    }
+
+    pub fn into_result(self) -> Value {
+        self.builder.into_output()
+    }
+
+    // Returns the value at `pointer` (a JSON Pointer such as "/type" or "/args/0") once it has been
+    // fully parsed. Values that are still streaming in, or that have not started yet, return None.
+    pub fn completed_value(&self, pointer: &str) -> Option<&Value> {
+        let mut value = self.get_result();
+        // the index of the stack frame `value` belongs to, as long as we are walking through open containers
+        let mut frame = Some(0);
+        for token in pointer_tokens(pointer)? {
+            if let Some(index) = frame {
+                frame = None;
+                if self.open_child_matches(index, value, &token) {
+                    if self.stack[index].state != FrameState::Nested {
+                        // the child is a string or scalar that is still being written
+                        return None;
+                    }
+                    frame = Some(index + 1);
                 }
-            } else {
-                let parsed_number = value_so_far
-                    .iter()
-                    .collect::<String>()
-                    .parse::<i64>()
-                    .map_err(|e| e.to_string())?;
-                *num = parsed_number.into();
-            }
-        }
-        (
-            Value::Number(_),
-            ObjectStatus::ScalarNumber {
-                ref mut value_so_far,
-            },
-            'e' | 'E',
-        ) => {
-            value_so_far.push(current_char);
-        }
-        (
-            Value::Number(_),
-            ObjectStatus::ScalarNumber {
-                ref mut value_so_far,
-            },
-            '+' | '-',
-        ) if matches!(value_so_far.last(), Some('e') | Some('E')) => {
-            value_so_far.push(current_char);
-        }
-        (
-            Value::Number(_),
-            ObjectStatus::ScalarNumber {
-                ref mut value_so_far,
-            },
-            '.',
-        ) => {
-            value_so_far.push('.');
-        }
-        // ------ array ------
-        (Value::Array(_), sts @ ObjectStatus::StartArray, ']') => {
-            *sts = ObjectStatus::Closed;
-        }
-        (Value::Array(_), ObjectStatus::StartArray, ' ' | '\n') => {}
-        (Value::Array(ref mut arr), sts @ ObjectStatus::StartArray, '"') => {
-            arr.push(json!(""));
-            *sts = ObjectStatus::ArrayValueQuoteOpen {
-                index: arr.len() - 1,
-            };
+            }
+            value = child_value(value, &token)?;
         }
-        (Value::Array(ref mut arr), sts @ ObjectStatus::StartArray, char) => {
-            arr.push(Value::Null);
-            *sts = ObjectStatus::ArrayValueScalar {
-                index: arr.len() - 1,
-                value_so_far: vec![char],
-            };
+        match frame {
+            Some(0) if self.is_complete() => Some(value),
+            Some(_) => None,
+            None => Some(value),
         }
-        (Value::Array(ref mut arr), sts @ ObjectStatus::ArrayValueQuoteOpen { .. }, '"') => {
-            let index = match *sts {
-                ObjectStatus::ArrayValueQuoteOpen { index } => index,
-                _ => unreachable!(),
-            };
-            if let Some(Value::String(s)) = arr.get_mut(index) {
-                if ends_with_odd_backslashes(s) {
-                    s.push('"');
-                    return Ok(());
-                }
-                *s = decode_json_string(s)?;
+    }
+
+    // whether `token` addresses the child of the open `container` of frame `index` that is
+    // currently being parsed
+    fn open_child_matches(&self, index: usize, container: &Value, token: &str) -> bool {
+        let frame = &self.stack[index];
+        if self.skip == Some(index) {
+            // the member being parsed is a duplicate that is left out
+            return false;
+        }
+        let writing = index + 1 == self.stack.len() && self.token != Token::None;
+        match container {
+            Value::Object(_) => {
+                matches!(
+                    frame.state,
+                    FrameState::ExpectColon | FrameState::ExpectValue | FrameState::Nested
+                ) && frame.key == token
             }
-            *sts = ObjectStatus::ArrayValueQuoteClose;
-        }
-        (Value::Array(ref mut arr), ObjectStatus::ArrayValueQuoteOpen { index }, char) => {
-            if let Some(Value::String(s)) = arr.get_mut(*index) {
-                s.push(char);
-            } else {
-                return Err("Invalid string value in array".to_string());
+            // the open child is always the last element
+            Value::Array(arr) => {
+                (frame.state == FrameState::Nested
+                    || (frame.state == FrameState::ExpectValue && writing))
+                    && token.parse::<usize>().ok() == arr.len().checked_sub(1)
             }
+            _ => false,
         }
-        (Value::Array(_), ObjectStatus::ArrayValueQuoteClose, ' ' | '\n') => {}
-        (Value::Array(_), sts @ ObjectStatus::ArrayValueQuoteClose, ',') => {
-            *sts = ObjectStatus::StartArray;
-        }
-        (Value::Array(_), sts @ ObjectStatus::ArrayValueQuoteClose, ']') => {
-            *sts = ObjectStatus::Closed;
+    }
+}
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // Drives `builder` instead of building a `serde_json::Value`.
+    pub fn with_builder(builder: B) -> JsonStreamParser<B> {
+        Self::with_builder_and_limits(builder, None, None)
+    }
+
+    pub fn with_builder_and_limits(
+        builder: B,
+        max_depth: Option<usize>,
+        max_length: Option<usize>,
+    ) -> JsonStreamParser<B> {
+        JsonStreamParser {
+            builder,
+            stack: vec![Frame::new(FrameState::Ready, false)],
+            token: Token::None,
+            number: String::new(),
+            duplicate_keys: DuplicateKeys::default(),
+            skip: None,
+            limits: Limits {
+                max_depth,
+                max_length,
+                ..Limits::default()
+            },
+            offset: 0,
+            generation: next_generation(),
+            processed_chars: 0,
+            string_length: 0,
+            nodes: 0,
+            memory: 0,
+            utf8: Utf8Decoder::default(),
+            deferred_error: None,
+            recovery: None,
+            resync: None,
+            diagnostics: Vec::new(),
+            spans: None,
+            clock: None,
+            stop_conditions: Vec::new(),
+            redaction: None,
+            redacted: None,
+            held: None,
+            projection: None,
+            selected: None,
+            holding: 0,
+            events: Events::default(),
+            undo: Undo::default(),
+            history: None,
         }
-        (Value::Array(ref mut arr), sts @ ObjectStatus::ArrayValueScalar { .. }, ',') => {
-            if let ObjectStatus::ArrayValueScalar {
-                index,
-                ref mut value_so_far,
-            } = sts
-            {
-                let value_string = value_so_far.iter().collect::<String>();
-                let idx = *index;
-                let value: Value = match value_string.parse::<Value>() {
-                    Ok(v) => v,
-                    Err(e) => return Err(format!("Invalid array value: {e}")),
-                };
-                arr[idx] = value;
+    }
+
+    // Sets what happens to keys that appear twice in the same object. Defaults to
+    // `DuplicateKeys::LastWins`.
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> JsonStreamParser<B> {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    // Replaces every limit, including those given to `with_limits`.
+    pub fn limits(mut self, limits: Limits) -> JsonStreamParser<B> {
+        self.limits = limits;
+        self
+    }
+
+    // Recovers from syntax errors instead of failing: the malformed value is dropped or replaced
+    // as `recovery` says, and parsing goes on after the next `,`, `]` or `}` at the same depth, or
+    // stops for good if the error is at the root. Every error recovered from is kept in
+    // `diagnostics`. Limits, invalid UTF-8 and `DuplicateKeys::Error` still fail.
+    pub fn recovery(mut self, recovery: Recovery) -> JsonStreamParser<B> {
+        self.recovery = Some(recovery);
+        self
+    }
+
+    // The syntax errors recovered from so far, in input order.
+    pub fn diagnostics(&self) -> &[ParseError] {
+        &self.diagnostics
+    }
+
+    // Records where every value and key is in the input, for `spans`. It is to be called before
+    // any input is given. A value replaced under `Recovery::Replace` spans the malformed input up
+    // to the error.
+    pub fn record_spans(mut self) -> JsonStreamParser<B> {
+        self.spans.get_or_insert_with(Spans::default);
+        self
+    }
+
+    // The spans recorded as of the end of the last chunk, if `record_spans` was called.
+    pub fn spans(&self) -> Option<&Spans> {
+        self.spans.as_ref()
+    }
+
+    pub fn add_char(&mut self, current_char: char) -> Result<(), ParseError> {
+        self.add_str(current_char.encode_utf8(&mut [0; 4]))
+    }
+
+    // Parses a chunk of input. A chunk is either parsed completely or, if it has an error, not at
+    // all: the parser and its output are left as they were before the chunk, so that parsing can
+    // go on with other input as if the chunk had never been sent.
+    pub fn add_str(&mut self, chunk: &str) -> Result<(), ParseError> {
+        self.add_str_until(chunk, None).map(|_| ())
+    }
+
+    // Like `add_str`, but if `stop` is given, it is called after every step of the lexer, and
+    // the chunk ends after the first step it returns true for. Returns how much of `chunk` was
+    // parsed.
+    pub(crate) fn add_str_until(
+        &mut self,
+        chunk: &str,
+        stop: StopAt<'_, B>,
+    ) -> Result<usize, ParseError> {
+        let mut end = chunk.len();
+        let mut chars = None;
+        if let Some(limit) = self.limits.max_length {
+            let available = limit.saturating_sub(self.processed_chars);
+            let count = chunk.chars().count();
+            if count > available {
+                end = chunk.char_indices().nth(available).unwrap().0;
             }
-            *sts = ObjectStatus::StartArray;
+            chars = Some(count);
         }
-        (Value::Array(ref mut arr), sts @ ObjectStatus::ArrayValueScalar { .. }, ']') => {
-            if let ObjectStatus::ArrayValueScalar {
-                index,
-                ref mut value_so_far,
-            } = sts
-            {
-                let value_string = value_so_far.iter().collect::<String>();
-                let idx = *index;
-                let value: Value = match value_string.parse::<Value>() {
-                    Ok(v) => v,
-                    Err(e) => return Err(format!("Invalid array value: {e}")),
+        self.begin_chunk();
+        let result = self.lex(&chunk[..end], stop).and_then(|parsed| {
+            if parsed == end && end < chunk.len() {
+                Err(ParseError::new(ErrorKind::MaxLength, end))
+            } else {
+                Ok(parsed)
+            }
+        });
+        match result {
+            Ok(parsed) => {
+                let chars = match chars {
+                    Some(count) if parsed == chunk.len() => count,
+                    _ => chunk[..parsed].chars().count(),
                 };
-                arr[idx] = value;
+                let chunk = &chunk[..parsed];
+                self.stamp();
+                self.events
+                    .apply(chunk, &mut self.builder, self.spans.as_mut());
+                self.builder.end_chunk();
+                self.undo.end_chunk();
+                self.offset += chunk.len();
+                self.processed_chars += chars;
+                self.record(chunk);
+                Ok(parsed)
             }
-            *sts = ObjectStatus::Closed;
-        }
-        (
-            Value::Array(_),
-            ObjectStatus::ArrayValueScalar {
-                ref mut value_so_far,
-                ..
-            },
-            char,
-        ) => {
-            value_so_far.push(char);
-        }
-        // ------ string ------
-        (Value::String(ref mut s), sts @ ObjectStatus::StringQuoteOpen, '"') => {
-            if ends_with_odd_backslashes(s) {
-                s.push('"');
-            } else {
-                *s = decode_json_string(s)?;
-                *sts = ObjectStatus::StringQuoteClose;
+            Err(e) => {
+                self.undo_chunk();
+                Err(e.shifted(self.offset))
             }
         }
-        (Value::String(str), sts @ ObjectStatus::StringQuoteOpen, char) => {
-            str.push(char);
-            *sts = ObjectStatus::StringQuoteOpen;
+    }
+
+    pub fn builder(&self) -> &B {
+        &self.builder
+    }
+
+    pub fn into_output(self) -> B::Output {
+        self.builder.into_output()
+    }
+
+    // Returns the error that stopped `Extend::extend` or `fmt::Write` from feeding the parser.
+    // While an error is pending, `extend` ignores its input.
+    pub fn take_error(&mut self) -> Option<ParseError> {
+        self.deferred_error.take()
+    }
+
+    // Whether the root value has been completely parsed. A number at the root is only complete once
+    // whitespace follows it, since another digit could always follow.
+    pub fn is_complete(&self) -> bool {
+        self.stack[0].state == FrameState::Done
+    }
+
+    // whether anything other than whitespace has been received
+    #[cfg(feature = "codec")]
+    pub(crate) fn is_started(&self) -> bool {
+        self.stack[0].state != FrameState::Ready || self.token != Token::None
+    }
+
+    pub(crate) fn is_root_number(&self) -> bool {
+        self.token == Token::Number && self.stack[0].state == FrameState::Ready
+    }
+
+    // Checks the number at the root, if one is being lexed, which only the next value or the end
+    // of the input can end, once either has been received.
+    pub(crate) fn end_root_number(&self) -> Result<(), ParseError> {
+        if !self.is_root_number() {
+            return Ok(());
+        }
+        match self.number.parse::<Number>() {
+            Ok(_) => Ok(()),
+            Err(_) => Err(syntax_error(
+                format!("Invalid number {}", self.number),
+                self.offset,
+            )),
         }
-        (Value::Object(_obj), sts @ ObjectStatus::StartProperty, '"') => {
-            *sts = ObjectStatus::KeyQuoteOpen { key_so_far: vec![] };
+    }
+
+    // the nesting depth and the kind of token the innermost frame is in the middle of
+    pub(crate) fn lexical_position(&self) -> (usize, Lexeme) {
+        let lexeme = match self.token {
+            Token::String(_) => Lexeme::String,
+            Token::Key(_) => Lexeme::Key,
+            Token::Number | Token::Literal { .. } => Lexeme::Scalar,
+            Token::None => Lexeme::None,
+        };
+        (self.stack.len(), lexeme)
+    }
+
+    // Records a builder call, unless the value being parsed is left out, redacted or held back.
+    fn emit(&mut self, event: Event) {
+        if self.skip.is_none() && self.redacted.is_none() && self.held.is_none() {
+            self.events.push(event);
         }
-        (Value::Object(_obj), sts @ ObjectStatus::StartProperty, '}') => {
-            *sts = ObjectStatus::Closed;
+    }
+
+    // Records where a value or key starts or ends, if spans are recorded. A redacted value has a
+    // span, but what is in it does not.
+    pub(crate) fn emit_span(&mut self, event: Event) {
+        let inside = self
+            .redacted
+            .map_or(false, |index| index + 1 < self.stack.len());
+        if self.spans.is_some() && (self.skip.is_none() || self.placeholder()) && !inside {
+            self.events.push(event);
         }
-        (Value::Object(ref mut obj), sts @ ObjectStatus::KeyQuoteOpen { .. }, '"') => {
-            if let ObjectStatus::KeyQuoteOpen { key_so_far } = sts.clone() {
-                *sts = ObjectStatus::KeyQuoteClose {
-                    key: key_so_far.clone(),
-                };
-                obj.insert(key_so_far.iter().collect::<String>(), Value::Null);
+    }
+
+    // lexes `input` until its end or until `stop` returns true, and returns the position reached
+    fn lex(&mut self, input: &str, mut stop: StopAt<'_, B>) -> Result<usize, ParseError> {
+        let mut position = 0;
+        while position < input.len() {
+            let step = stop.is_some().then(|| Step {
+                lexical: self.lexical_position(),
+                complete: self.is_complete(),
+                byte: input.as_bytes()[position],
+            });
+            position = if self.resync.is_some() {
+                self.resync(input, position)?
+            } else {
+                match self.step(input, position) {
+                    Ok(position) => position,
+                    Err(error) => self.recover(error)?,
+                }
+            };
+            if let (Some(stop), Some(step)) = (stop.as_mut(), step) {
+                if stop(self, step) {
+                    break;
+                }
             }
         }
-        (Value::Object(_obj), ObjectStatus::KeyQuoteOpen { ref mut key_so_far }, char) => {
-            key_so_far.push(char);
-        }
-        (Value::Object(_obj), sts @ ObjectStatus::KeyQuoteClose { .. }, ':') => {
-            if let ObjectStatus::KeyQuoteClose { key } = sts.clone() {
-                *sts = ObjectStatus::Colon { key: key.clone() };
+        Ok(position)
+    }
+
+    // lexes from `position` in the current token and returns the position after what was lexed
+    fn step(&mut self, input: &str, position: usize) -> Result<usize, ParseError> {
+        match self.token {
+            Token::None => self.structural(input, position),
+            Token::Key(escape) => self.lex_string(input, position, escape, true),
+            Token::String(escape) => self.lex_string(input, position, escape, false),
+            Token::Number => self.lex_number(input, position),
+            Token::Literal { text, matched } => {
+                if input.as_bytes()[position] != text[matched] {
+                    return Err(invalid_character(input, position, self.token));
+                }
+                if matched + 1 == text.len() {
+                    self.finish_leaf(literal_value(text), position + 1);
+                } else {
+                    self.token = Token::Literal {
+                        text,
+                        matched: matched + 1,
+                    };
+                }
+                Ok(position + 1)
             }
         }
-        (Value::Object(_obj), ObjectStatus::Colon { .. }, ' ' | '\n' | '\t' | '\r') => {}
-        (Value::Object(ref mut obj), sts @ ObjectStatus::Colon { .. }, '"') => {
-            if let ObjectStatus::Colon { key } = sts.clone() {
-                *sts = ObjectStatus::ValueQuoteOpen { key: key.clone() };
-                // create an empty string for the value
-                obj.insert(key.iter().collect::<String>().clone(), json!(""));
+    }
+
+    // handles the byte at `position` outside of any token and returns the position after it
+    fn structural(&mut self, input: &str, position: usize) -> Result<usize, ParseError> {
+        let byte = input.as_bytes()[position];
+        if is_whitespace(byte) {
+            return Ok(position + 1);
+        }
+        let frame = self.stack.last_mut().unwrap();
+        let state = frame.state;
+        match (state, byte) {
+            (FrameState::Ready | FrameState::ExpectValue, b'{' | b'[') => {
+                self.start_value(position, true)?;
+                self.open_container(byte == b'{', position)?
             }
-        }
-        // ------ Add String Value ------
-        (Value::Object(ref mut obj), sts @ ObjectStatus::ValueQuoteOpen { .. }, '"') => {
-            let key_vec = match sts {
-                ObjectStatus::ValueQuoteOpen { key } => key.clone(),
-                _ => unreachable!(),
-            };
-            let key_string = key_vec.iter().collect::<String>();
-            if let Some(Value::String(value)) = obj.get_mut(&key_string) {
-                if ends_with_odd_backslashes(value) {
-                    value.push('"');
-                    return Ok(());
+            (FrameState::Ready | FrameState::ExpectValue, b'"') => {
+                self.start_value(position, false)?;
+                self.string_length = 0;
+                self.hold_string();
+                self.emit(Event::StartString);
+                self.token = Token::String(Escape::None);
+            }
+            (FrameState::Ready | FrameState::ExpectValue, b'-' | b'0'..=b'9') => {
+                if exceeds(1, self.limits.max_number_length) {
+                    return Err(ParseError::new(ErrorKind::MaxNumberLength, position));
                 }
-                *value = decode_json_string(value)?;
-            }
-            *sts = ObjectStatus::ValueQuoteClose;
-        }
-        (Value::Object(ref mut obj), ObjectStatus::ValueQuoteOpen { key }, char) => {
-            let key_string = key.iter().collect::<String>();
-            let value = obj
-                .get_mut(&key_string)
-                .ok_or_else(|| format!("missing key {key_string}"))?;
-            match value {
-                Value::String(value) => {
-                    value.push(char);
+                self.start_value(position, false)?;
+                // a root number is shown as it grows, others once they are complete
+                if state == FrameState::Ready {
+                    self.emit(Event::SetScalar(Scalar::Number(0.into())));
                 }
-                _ => {
-                    return Err(format!("Invalid value type for key {key_string}"));
+                self.number.clear();
+                self.push_number(&input[position..position + 1]);
+                self.token = Token::Number;
+                self.update_root_number();
+            }
+            (FrameState::Ready | FrameState::ExpectValue, b't' | b'f' | b'n') => {
+                let text: &'static [u8] = match byte {
+                    b't' => b"true",
+                    b'f' => b"false",
+                    _ => b"null",
+                };
+                self.start_value(position, false)?;
+                // a root literal is shown as soon as its first letter arrives
+                if state == FrameState::Ready {
+                    self.emit(Event::SetScalar(literal_value(text)));
                 }
+                self.token = Token::Literal { text, matched: 1 };
             }
-        }
-
-        // ------ Add Scalar Value ------
-        (Value::Object(_obj), sts @ ObjectStatus::Colon { .. }, char) => {
-            if let ObjectStatus::Colon { key } = sts.clone() {
-                *sts = ObjectStatus::ValueScalar {
-                    key: key.clone(),
-                    value_so_far: vec![char],
+            (FrameState::ExpectValue | FrameState::AfterValue, b']') if !frame.object => {
+                self.close_container(position + 1)
+            }
+            (FrameState::ExpectKey | FrameState::AfterValue, b'}') if frame.object => {
+                self.close_container(position + 1)
+            }
+            (FrameState::ExpectKey, b'"') => {
+                frame.members += 1;
+                if exceeds(frame.members, self.limits.max_object_members) {
+                    return Err(ParseError::new(ErrorKind::MaxObjectMembers, position));
+                }
+                self.save_key();
+                self.stack.last_mut().unwrap().key.clear();
+                self.add_memory(MEMBER_SIZE, position)?;
+                self.emit_span(Event::KeyStart(position));
+                self.token = Token::Key(Escape::None);
+            }
+            (FrameState::ExpectColon, b':') => frame.state = FrameState::ExpectValue,
+            (FrameState::AfterValue, b',') => {
+                frame.state = if frame.object {
+                    FrameState::ExpectKey
+                } else {
+                    FrameState::ExpectValue
                 };
             }
+            (state, _) => return Err(invalid_character(input, position, state)),
         }
-        (Value::Object(ref mut obj), sts @ ObjectStatus::ValueScalar { .. }, ',') => {
-            if let ObjectStatus::ValueScalar { key, value_so_far } = sts.clone() {
-                let key_string = key.iter().collect::<String>();
-                let value_string = value_so_far.iter().collect::<String>();
-                let value = match value_string.parse::<Value>() {
-                    Ok(value) => value,
-                    Err(e) => {
-                        return Err(format!("Invalid value for key {key_string}: {e}"));
-                    }
-                };
-                obj.insert(key_string, value);
-                *sts = ObjectStatus::StartProperty;
-            }
-        }
-        (Value::Object(ref mut obj), sts @ ObjectStatus::ValueScalar { .. }, '}') => {
-            if let ObjectStatus::ValueScalar { key, value_so_far } = sts.clone() {
-                let key_string = key.iter().collect::<String>();
-                let value_string = value_so_far.iter().collect::<String>();
-                let value = match value_string.parse::<Value>() {
-                    Ok(value) => value,
-                    Err(e) => {
-                        return Err(format!("Invalid value for key {key_string}: {e}"));
-                    }
-                };
-                obj.insert(key_string, value);
-                *sts = ObjectStatus::Closed;
+        Ok(position + 1)
+    }
+
+    // Counts a value that starts at `position` and makes room for it if it is in an array. Object
+    // members get their slot when their key ends, and the root always has one.
+    fn start_value(&mut self, position: usize, container: bool) -> Result<(), ParseError> {
+        self.nodes += 1;
+        if exceeds(self.nodes, self.limits.max_nodes) {
+            return Err(ParseError::new(ErrorKind::MaxNodes, position));
+        }
+        self.add_memory(NODE_SIZE, position)?;
+        let frame = self.stack.last_mut().unwrap();
+        let item = frame.state == FrameState::ExpectValue && !frame.object;
+        if item {
+            frame.members += 1;
+            if exceeds(frame.members, self.limits.max_array_length) {
+                return Err(ParseError::new(ErrorKind::MaxArrayLength, position));
             }
         }
-        (Value::Object(_obj), ObjectStatus::ValueScalar { .. }, ' ' | '\n' | '\t' | '\r') => {}
-        (
-            Value::Object(_obj),
-            ObjectStatus::ValueScalar {
-                key: _key,
-                ref mut value_so_far,
-            },
-            char,
-        ) => {
-            // push the character into the value so far
-            value_so_far.push(char);
+        self.project_value(item, container);
+        if item {
+            self.emit(Event::PushItem);
         }
+        self.emit_span(Event::ValueStart(position));
+        self.redact_value();
+        Ok(())
+    }
 
-        // ------ Finished taking value ------
-        (Value::Object(_obj), sts @ ObjectStatus::ValueQuoteClose, ',') => {
-            *sts = ObjectStatus::StartProperty;
+    fn add_memory(&mut self, size: usize, position: usize) -> Result<(), ParseError> {
+        self.memory += size;
+        if exceeds(self.memory, self.limits.max_memory) {
+            return Err(ParseError::new(ErrorKind::MaxMemory, position));
         }
-        (Value::Object(_obj), sts @ ObjectStatus::ValueQuoteClose, '}') => {
-            *sts = ObjectStatus::Closed;
+        Ok(())
+    }
+
+    fn open_container(&mut self, object: bool, position: usize) -> Result<(), ParseError> {
+        self.emit(if object {
+            Event::StartObject
+        } else {
+            Event::StartArray
+        });
+        let depth = self.stack.len();
+        let frame = self.stack.last_mut().unwrap();
+        let state = if object {
+            FrameState::ExpectKey
+        } else {
+            FrameState::ExpectValue
+        };
+        if frame.state == FrameState::Ready {
+            *frame = Frame::new(state, object);
+            if exceeds(1, self.limits.max_depth) {
+                return Err(ParseError::new(ErrorKind::MaxDepth, position));
+            }
+            return Ok(());
         }
-        // ------ white spaces ------
-        (_, _, ' ' | '\n' | '\t' | '\r') => {}
-        (_val, st, c) => {
-            return Err(format!("Invalid character {c} status: {st:?}"));
+        if exceeds(depth + 1, self.limits.max_depth) {
+            return Err(ParseError::new(ErrorKind::MaxDepth, position));
         }
+        frame.state = FrameState::Nested;
+        self.stack.push(Frame::new(state, object));
+        Ok(())
     }
 
-    Ok(())
-}
+    // Closes the innermost container, whose closing bracket ends before `end`.
+    fn close_container(&mut self, end: usize) {
+        self.emit(Event::EndContainer);
+        if self.stack.len() == 1 {
+            self.emit_span(Event::ValueEnd(end));
+            self.stack[0].state = FrameState::Done;
+            return;
+        }
+        self.pop_frame();
+        self.emit_span(Event::ValueEnd(end));
+        self.end_projected_container();
+        self.end_value();
+    }
 
-fn add_char_into_object(
-    stack: &mut Vec<(Value, ObjectStatus)>,
-    current_char: char,
-) -> Result<(), String> {
-    if stack.is_empty() {
-        return Err("empty stack".to_string());
+    // Gives the scalar being lexed its final value and ends the token before `end`.
+    fn finish_leaf(&mut self, scalar: Scalar, end: usize) {
+        self.emit(Event::SetScalar(scalar));
+        self.end_leaf(end);
     }
 
-    // check if we need to start a nested context without cloning status
-    {
-        let (value, status) = stack.last_mut().unwrap();
-        match (value, current_char) {
-            (Value::Array(_), '{') if matches!(status, ObjectStatus::StartArray) => {
-                *status = ObjectStatus::ArrayValueNested;
-                stack.push((Value::Null, ObjectStatus::Ready));
-                return add_char_into_object(stack, '{');
-            }
-            (Value::Array(_), '[') if matches!(status, ObjectStatus::StartArray) => {
-                *status = ObjectStatus::ArrayValueNested;
-                stack.push((Value::Null, ObjectStatus::Ready));
-                return add_char_into_object(stack, '[');
+    // Ends the token of a string or scalar that has its final value before `end`.
+    fn end_leaf(&mut self, end: usize) {
+        self.emit(Event::EndValue);
+        self.emit_span(Event::ValueEnd(end));
+        self.token = Token::None;
+        self.end_value();
+    }
+
+    // Moves the innermost frame past the value that has just ended.
+    fn end_value(&mut self) {
+        let index = self.stack.len() - 1;
+        let frame = &mut self.stack[index];
+        frame.state = if frame.state == FrameState::Ready {
+            FrameState::Done
+        } else {
+            FrameState::AfterValue
+        };
+        if self.skip == Some(index) {
+            self.skip = None;
+        }
+        if self.redacted == Some(index) {
+            self.redacted = None;
+        }
+        if self.selected == Some(index) {
+            self.selected = None;
+        }
+    }
+
+    // Adds the member whose key has just been lexed, following the duplicate key policy.
+    fn insert_key(&mut self, position: usize) -> Result<(), ParseError> {
+        let index = self.stack.len() - 1;
+        self.stack[index].state = FrameState::ExpectColon;
+        if self.skip.is_some() {
+            return Ok(());
+        }
+        // a member that cannot hold a selected value is parsed without being built
+        if !self.projects_member() {
+            self.skip = Some(index);
+            return Ok(());
+        }
+        // the keys in a redacted value are masked, so they are neither built nor looked up
+        if self.redacted.is_some() {
+            return Ok(());
+        }
+        let frame = &mut self.stack[index];
+        if self.duplicate_keys == DuplicateKeys::LastWins {
+            self.events.push_key(&frame.key, None);
+            return Ok(());
+        }
+        match frame.keys.get_mut(&frame.key) {
+            None => {
+                frame.keys.insert(frame.key.clone(), false);
+                self.events.push_key(&frame.key, None);
+                self.log_key(false);
             }
-            (Value::Object(_), '{') => {
-                if let ObjectStatus::Colon { key } = status {
-                    let key_clone = key.clone();
-                    *status = ObjectStatus::ValueNested {
-                        key: key_clone.clone(),
-                    };
-                    stack.push((Value::Null, ObjectStatus::Ready));
-                    return add_char_into_object(stack, '{');
+            Some(collected) => match self.duplicate_keys {
+                DuplicateKeys::LastWins => self.events.push_key(&frame.key, None),
+                DuplicateKeys::FirstWins => self.skip = Some(index),
+                DuplicateKeys::Error => {
+                    let key = frame.key.clone();
+                    return Err(ParseError::new(ErrorKind::DuplicateKey(key), position));
                 }
-            }
-            (Value::Object(_), '[') => {
-                if let ObjectStatus::Colon { key } = status {
-                    let key_clone = key.clone();
-                    *status = ObjectStatus::ValueNested {
-                        key: key_clone.clone(),
-                    };
-                    stack.push((Value::Null, ObjectStatus::Ready));
-                    return add_char_into_object(stack, '[');
+                DuplicateKeys::Collect => {
+                    self.events.push_key(&frame.key, Some(!*collected));
+                    if !*collected {
+                        *collected = true;
+                        self.log_key(true);
+                    }
                 }
+            },
+        }
+        Ok(())
+    }
+
+    // Shows a root number as its longest prefix that is a whole number, so that "12." is 12
+    // however it is split into chunks. A prefix such as "-" keeps the previous value.
+    fn update_root_number(&mut self) {
+        if self.stack.len() == 1 && self.stack[0].state == FrameState::Ready {
+            let end = number_prefix(&self.number);
+            if let Ok(number) = self.number[..end].parse::<Number>() {
+                self.emit(Event::SetScalar(Scalar::Number(number)));
             }
-            _ => {}
         }
     }
 
-    {
-        let (ref mut val, ref mut status) = stack.last_mut().unwrap();
-        process_char(val, status, current_char)?;
+    fn lex_number(&mut self, input: &str, position: usize) -> Result<usize, ParseError> {
+        let bytes = input.as_bytes();
+        let end = bytes[position..]
+            .iter()
+            .position(|&byte| !continues_number(byte))
+            .map_or(bytes.len(), |offset| position + offset);
+        if end > position {
+            let digits = &input[position..end];
+            if let Some(index) = overflow(self.number.len(), digits, self.limits.max_number_length)
+            {
+                return Err(ParseError::new(
+                    ErrorKind::MaxNumberLength,
+                    position + index,
+                ));
+            }
+            self.push_number(digits);
+            self.update_root_number();
+            return Ok(end);
+        }
+        let number = self
+            .number
+            .parse::<Number>()
+            .map_err(|_| syntax_error(format!("Invalid number {}", self.number), position))?;
+        self.finish_leaf(Scalar::Number(number), position);
+        self.structural(input, position)
+    }
+
+    // Appends decoded text, which starts at `position`, to the key or string being lexed.
+    fn push_str(&mut self, key: bool, s: &str, position: usize) -> Result<(), ParseError> {
+        let (used, limit, kind) = if key {
+            let frame = self.stack.last().unwrap();
+            (
+                frame.key.len(),
+                self.limits.max_key_length,
+                ErrorKind::MaxKeyLength,
+            )
+        } else {
+            (
+                self.string_length,
+                self.limits.max_string_length,
+                ErrorKind::MaxStringLength,
+            )
+        };
+        if let Some(index) = overflow(used, s, limit) {
+            return Err(ParseError::new(kind, position + index));
+        }
+        if let Some(index) = overflow(self.memory, s, self.limits.max_memory) {
+            return Err(ParseError::new(ErrorKind::MaxMemory, position + index));
+        }
+        self.memory += s.len();
+        if key {
+            self.save_key();
+            let frame = self.stack.last_mut().unwrap();
+            // a key in a redacted value only keeps its length, for `max_key_length`
+            if self.redacted.is_some() {
+                frame.key.extend(std::iter::repeat('*').take(s.len()));
+            } else {
+                frame.key.push_str(s);
+            }
+        } else {
+            self.string_length += s.len();
+            if let Some(held) = &mut self.held {
+                held.push_str(s);
+            }
+        }
+        Ok(())
     }
 
-    // handle closed contexts
-    while stack.len() > 1 {
-        let should_pop = matches!(stack.last(), Some((_, ObjectStatus::Closed)));
-        if !should_pop {
-            break;
+    // Appends a char decoded from an escape, which starts at `position`.
+    fn push_char(&mut self, key: bool, c: char, position: usize) -> Result<(), ParseError> {
+        self.push_str(key, c.encode_utf8(&mut [0; 4]), position)?;
+        if !key {
+            self.emit(Event::AppendChar(c));
         }
-        let (completed_value, _) = stack.pop().unwrap();
-        let (parent_value, parent_status) = stack.last_mut().unwrap();
-        match parent_status {
-            ObjectStatus::ArrayValueNested => {
-                if let Value::Array(arr) = parent_value {
-                    arr.push(completed_value);
-                    *parent_status = ObjectStatus::ArrayValueQuoteClose;
+        Ok(())
+    }
+
+    fn lex_string(
+        &mut self,
+        input: &str,
+        position: usize,
+        escape: Escape,
+        key: bool,
+    ) -> Result<usize, ParseError> {
+        let bytes = input.as_bytes();
+        let byte = bytes[position];
+        let escape = match (escape, byte) {
+            (Escape::None, b'"') => {
+                if key {
+                    // the member is shown as null until its value starts
+                    self.token = Token::None;
+                    self.insert_key(position)?;
+                    self.emit_span(Event::KeyEnd(position + 1));
+                } else if self.held.is_some() {
+                    self.release_string(position + 1);
                 } else {
-                    return Err("Parent is not array".to_string());
+                    self.end_leaf(position + 1);
                 }
+                return Ok(position + 1);
             }
-            ObjectStatus::ValueNested { key } => {
-                if let Value::Object(map) = parent_value {
-                    map.insert(key.iter().collect::<String>(), completed_value);
-                    *parent_status = ObjectStatus::ValueQuoteClose;
+            (Escape::None, b'\\') => Escape::Backslash,
+            (Escape::None, 0x20..) => {
+                // copy everything up to the next quote, backslash or control character at once
+                let end = string_run_end(bytes, position);
+                self.push_str(key, &input[position..end], position)?;
+                if !key {
+                    self.emit(Event::AppendInput(position..end));
+                }
+                return Ok(end);
+            }
+            (Escape::Backslash, b'u') => Escape::Unicode {
+                high: None,
+                code: 0,
+                digits: 0,
+            },
+            (Escape::Backslash, _) => {
+                let c = match byte {
+                    b'"' => '"',
+                    b'\\' => '\\',
+                    b'/' => '/',
+                    b'b' => '\u{8}',
+                    b'f' => '\u{c}',
+                    b'n' => '\n',
+                    b'r' => '\r',
+                    b't' => '\t',
+                    _ => return Err(invalid_character(input, position, escape)),
+                };
+                self.push_char(key, c, position)?;
+                Escape::None
+            }
+            (Escape::Unicode { high, code, digits }, _) => {
+                let digit = (byte as char)
+                    .to_digit(16)
+                    .ok_or_else(|| invalid_character(input, position, escape))?;
+                let code = code * 16 + digit as u16;
+                if digits < 3 {
+                    Escape::Unicode {
+                        high,
+                        code,
+                        digits: digits + 1,
+                    }
                 } else {
-                    return Err("Parent is not object".to_string());
+                    let c = match (high, code) {
+                        (None, 0xD800..=0xDBFF) => {
+                            self.token = if key {
+                                Token::Key(Escape::Surrogate {
+                                    high: code,
+                                    backslash: false,
+                                })
+                            } else {
+                                Token::String(Escape::Surrogate {
+                                    high: code,
+                                    backslash: false,
+                                })
+                            };
+                            return Ok(position + 1);
+                        }
+                        (Some(high), 0xDC00..=0xDFFF) => char::from_u32(
+                            0x10000 + ((high as u32 - 0xD800) << 10) + (code as u32 - 0xDC00),
+                        ),
+                        (None, _) => char::from_u32(code as u32),
+                        (Some(_), _) => None,
+                    }
+                    .ok_or_else(|| {
+                        syntax_error(format!("Invalid unicode escape \\u{code:04X}"), position)
+                    })?;
+                    self.push_char(key, c, position)?;
+                    Escape::None
                 }
             }
-            _ => {
-                return Err("Invalid parent status".to_string());
+            (
+                Escape::Surrogate {
+                    high,
+                    backslash: false,
+                },
+                b'\\',
+            ) => Escape::Surrogate {
+                high,
+                backslash: true,
+            },
+            (
+                Escape::Surrogate {
+                    high,
+                    backslash: true,
+                },
+                b'u',
+            ) => Escape::Unicode {
+                high: Some(high),
+                code: 0,
+                digits: 0,
+            },
+            (Escape::Surrogate { high, .. }, _) => {
+                return Err(syntax_error(
+                    format!("Unpaired surrogate \\u{high:04X}"),
+                    position,
+                ));
             }
-        }
+            (Escape::None, _) => return Err(invalid_character(input, position, self.token)),
+        };
+        self.token = if key {
+            Token::Key(escape)
+        } else {
+            Token::String(escape)
+        };
+        Ok(position + 1)
     }
-
-    Ok(())
 }
 
-#[cfg(debug_assertions)]
-pub fn parse_stream(json_string: &str) -> Result<Value, String> {
-    let mut stack: Vec<(Value, ObjectStatus)> = vec![(Value::Null, ObjectStatus::Ready)];
-    for current_char in json_string.chars() {
-        let (ref val, ref st) = stack.last().unwrap();
-        println!(
-            "variables: {:?} {:?} {:?}",
-            val,
-            st.clone(),
-            current_char.to_string()
-        );
-        add_char_into_object(&mut stack, current_char)?;
+fn pointer_tokens(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(vec![]);
     }
-    Ok(stack.pop().unwrap().0)
+    let rest = pointer.strip_prefix('/')?;
+    Some(
+        rest.split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
 }
 
-#[cfg(not(debug_assertions))]
-pub fn parse_stream(json_string: &str) -> Result<Value, String> {
-    let mut stack: Vec<(Value, ObjectStatus)> = vec![(Value::Null, ObjectStatus::Ready)];
-    for current_char in json_string.chars() {
-        add_char_into_object(&mut stack, current_char)?;
-    }
-    Ok(stack.pop().unwrap().0)
+// Appends `token` to a JSON Pointer, escaped.
+fn push_pointer_token(pointer: &mut String, token: &str) {
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
 }
 
-pub fn parse_stream_with_limits(
-    json_string: &str,
-    max_depth: Option<usize>,
-    max_length: Option<usize>,
-) -> Result<Value, String> {
-    let mut parser = JsonStreamParser::with_limits(max_depth, max_length);
-    for c in json_string.chars() {
-        parser.add_char(c)?;
-    }
-    Ok(parser.stack.pop().unwrap().0)
-}
-
-pub struct JsonStreamParser {
-    stack: Vec<(Value, ObjectStatus)>,
-    processed_chars: usize,
-    max_depth: Option<usize>,
-    max_length: Option<usize>,
-}
-
-impl Default for JsonStreamParser {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl JsonStreamParser {
-    pub fn new() -> JsonStreamParser {
-        Self::with_limits(None, None)
-    }
-
-    pub fn with_limits(max_depth: Option<usize>, max_length: Option<usize>) -> JsonStreamParser {
-        JsonStreamParser {
-            stack: vec![(Value::Null, ObjectStatus::Ready)],
-            processed_chars: 0,
-            max_depth,
-            max_length,
-        }
-    }
-
-    pub fn add_char(&mut self, current_char: char) -> Result<(), String> {
-        if let Some(limit) = self.max_length {
-            self.processed_chars += 1;
-            if self.processed_chars > limit {
-                return Err("input length limit exceeded".to_string());
-            }
-        }
-
-        add_char_into_object(&mut self.stack, current_char)?;
-
-        if let Some(max_depth) = self.max_depth {
-            if self.stack.len() > max_depth {
-                return Err("max depth exceeded".to_string());
-            }
-        }
-
-        Ok(())
-    }
-
-    pub fn get_result(&self) -> &Value {
-        &self.stack[0].0
+fn child_value<'a>(value: &'a Value, token: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) => map.get(token),
+        Value::Array(arr) => arr.get(token.parse::<usize>().ok()?),
+        _ => None,
     }
 }
 
@@ -861,6 +1433,10 @@ param_test! {
     string_with_space_at_end: r#""a b c ""#, Value::String("a b c ".to_string())
     string_with_space_at_start: r#"" a b c""#, Value::String(" a b c".to_string())
     string_with_space_at_start_and_end: r#"" a b c ""#, Value::String(" a b c ".to_string())
+    string_with_escapes: r#""a\\b\/c\n\t""#, Value::String("a\\b/c\n\t".to_string())
+    string_with_unicode_escape: r#""caf\u00e9""#, Value::String("café".to_string())
+    string_with_surrogate_pair: r#""\ud83e\udd80""#, Value::String("🦀".to_string())
+    string_with_multibyte_chars: r#""zoë 🦀""#, Value::String("zoë 🦀".to_string())
     number: r#"1234567890"#, Value::Number(1234567890.into())
     single_digit_number: r#"1"#, Value::Number(1.into())
     number_with_spaces_at_start: r#" 1234567890"#, Value::Number(1234567890.into())
@@ -884,6 +1460,62 @@ param_test! {
     deep_nested_object_value: r#"{"a":{"b":{"c":1}}}"#, json!({"a": {"b": {"c": 1 }}})
 }
 
+#[cfg(test)]
+mod lexer_tests {
+    use super::{parse_stream, JsonStreamParser};
+    use serde_json::json;
+
+    #[test]
+    fn escaped_keys_are_decoded() {
+        let raw_json = r#"{"a\"b": 1, "\u00e9": 2}"#;
+        let expected = json!({"a\"b": 1, "é": 2});
+        assert_eq!(parse_stream(raw_json).unwrap(), expected);
+    }
+
+    #[test]
+    fn chunks_split_inside_tokens() {
+        let raw_json = r#"{"key": ["va\u00e9lue", -12.5e3, true], "n": null}"#;
+        let expected: serde_json::Value = serde_json::from_str(raw_json).unwrap();
+        for split in 1..raw_json.len() {
+            let mut parser = JsonStreamParser::new();
+            parser.add_str(&raw_json[..split]).unwrap();
+            parser.add_str(&raw_json[split..]).unwrap();
+            assert_eq!(parser.get_result(), &expected, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn root_number_grows_with_each_digit() {
+        let mut parser = JsonStreamParser::new();
+        parser.add_str("-").unwrap();
+        assert_eq!(parser.get_result(), &json!(0));
+        parser.add_str("12").unwrap();
+        assert_eq!(parser.get_result(), &json!(-12));
+        parser.add_str(".5").unwrap();
+        assert_eq!(parser.get_result(), &json!(-12.5));
+        assert!(!parser.is_complete());
+        parser.add_str(" ").unwrap();
+        assert!(parser.is_complete());
+    }
+
+    #[test]
+    fn invalid_input_is_rejected() {
+        for raw_json in [
+            r#"{]"#,
+            r#"[1 2]"#,
+            r#"[tru]"#,
+            r#"["a\x"]"#,
+            r#""\ud83e\u0041""#,
+            "\"a\nb\"",
+            r#"[1.]"#,
+            r#"{"a" 1}"#,
+            r#"{} x"#,
+        ] {
+            assert!(parse_stream(raw_json).is_err(), "{raw_json}");
+        }
+    }
+}
+
 #[cfg(test)]
 mod array_tests {
     use super::{parse_stream, JsonStreamParser};
@@ -0,0 +1,44 @@
+use std::mem::size_of;
+
+use serde_json::Value;
+
+// Bounds on the input and on the document built from it, for untrusted input. Every limit is off
+// by default. Lengths are in bytes of decoded text, except `max_length`, which counts input chars.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub struct Limits {
+    // the number of nested containers, counting the root one
+    pub max_depth: Option<usize>,
+    pub max_length: Option<usize>,
+    pub max_string_length: Option<usize>,
+    pub max_key_length: Option<usize>,
+    // the length of a number literal, such as 5 for `-1e10`
+    pub max_number_length: Option<usize>,
+    // the number of elements in one array
+    pub max_array_length: Option<usize>,
+    // the number of members in one object, counting duplicate keys
+    pub max_object_members: Option<usize>,
+    // the number of values in the document, containers included
+    pub max_nodes: Option<usize>,
+    // An estimate of the bytes the document takes up: one `Value` per value, plus the text of
+    // strings and keys. Values left out under `DuplicateKeys::FirstWins` count too.
+    pub max_memory: Option<usize>,
+}
+
+// the estimated size of a value, not counting its text
+pub(crate) const NODE_SIZE: usize = size_of::<Value>();
+// the estimated size of an object member, not counting its value or the text of its key
+pub(crate) const MEMBER_SIZE: usize = size_of::<String>();
+
+// The index in `added` of the first char that does not fit within `limit` once `used` bytes are
+// taken, if any.
+pub(crate) fn overflow(used: usize, added: &str, limit: Option<usize>) -> Option<usize> {
+    let limit = limit?;
+    if used + added.len() <= limit {
+        return None;
+    }
+    let fits = limit.saturating_sub(used);
+    (0..=fits)
+        .rev()
+        .find(|&index| added.is_char_boundary(index))
+}
@@ -0,0 +1,322 @@
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Number, Value};
+
+use crate::{FrameState, JsonStreamParser, Token, ValueBuilder};
+
+// A node of the partial document along with whether it is complete: a string, number or literal
+// whose last char has been received, or a container whose closing bracket has. Members whose key
+// is complete but whose value has not started yet are incomplete nulls.
+//
+// It serializes as `{"value": ..., "complete": ...}` for every node, where the value of a string
+// or scalar is its JSON value, that of an array is an array of nodes, and that of an object is an
+// object of nodes:
+//
+//     {"value": {"a": {"value": "hel", "complete": false}}, "complete": false}
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartialValue {
+    pub node: PartialNode,
+    pub complete: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PartialNode {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<PartialValue>),
+    // the members in the order of the `serde_json::Map` they come from
+    Object(Vec<(String, PartialValue)>),
+}
+
+impl PartialValue {
+    pub fn to_value(&self) -> Value {
+        Value::from(self)
+    }
+
+    fn slot_mut(&mut self, slot: Slot) -> Option<&mut PartialValue> {
+        match (&mut self.node, slot) {
+            (PartialNode::Array(items), Slot::Last) => items.last_mut(),
+            (PartialNode::Object(members), Slot::Key(key)) => members
+                .iter_mut()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+// A complete value. Like the rest of this module, it walks the document with an explicit stack
+// rather than recursion, so that it works on documents of any depth.
+impl From<&Value> for PartialValue {
+    fn from(value: &Value) -> Self {
+        let mut stack = vec![];
+        let mut done = match open_value(value, None, &mut stack) {
+            Some((_, done)) => return done,
+            None => None,
+        };
+        loop {
+            if let Some((key, value)) = done.take() {
+                match stack.last_mut() {
+                    Some(parent) => parent.node.push(key, value),
+                    None => return value,
+                }
+            }
+            let top = stack.last_mut().unwrap();
+            let child = match &mut top.children {
+                ValueChildren::Items(items) => items.next().map(|item| (None, item)),
+                ValueChildren::Members(members) => members
+                    .next()
+                    .map(|(key, value)| (Some(key.clone()), value)),
+            };
+            done = match child {
+                Some((key, value)) => open_value(value, key, &mut stack),
+                None => stack.pop().map(|open| {
+                    let value = PartialValue {
+                        node: open.node,
+                        complete: true,
+                    };
+                    (open.key, value)
+                }),
+            };
+        }
+    }
+}
+
+// A container being converted, with its children that are left to convert.
+struct Open<N, C> {
+    node: N,
+    // its key in its parent, if that is an object
+    key: Option<String>,
+    children: C,
+}
+
+enum ValueChildren<'a> {
+    Items(std::slice::Iter<'a, Value>),
+    Members(serde_json::map::Iter<'a>),
+}
+
+// Converts `value` if it is a string or scalar. A container is opened on `stack` instead.
+fn open_value<'a>(
+    value: &'a Value,
+    key: Option<String>,
+    stack: &mut Vec<Open<PartialNode, ValueChildren<'a>>>,
+) -> Option<(Option<String>, PartialValue)> {
+    let node = match value {
+        Value::Null => PartialNode::Null,
+        Value::Bool(b) => PartialNode::Bool(*b),
+        Value::Number(n) => PartialNode::Number(n.clone()),
+        Value::String(s) => PartialNode::String(s.clone()),
+        Value::Array(items) => {
+            stack.push(Open {
+                node: PartialNode::Array(Vec::with_capacity(items.len())),
+                key,
+                children: ValueChildren::Items(items.iter()),
+            });
+            return None;
+        }
+        Value::Object(map) => {
+            stack.push(Open {
+                node: PartialNode::Object(Vec::with_capacity(map.len())),
+                key,
+                children: ValueChildren::Members(map.iter()),
+            });
+            return None;
+        }
+    };
+    let value = PartialValue {
+        node,
+        complete: true,
+    };
+    Some((key, value))
+}
+
+impl PartialNode {
+    fn push(&mut self, key: Option<String>, value: PartialValue) {
+        match self {
+            PartialNode::Array(items) => items.push(value),
+            PartialNode::Object(members) => members.push((key.unwrap_or_default(), value)),
+            _ => {}
+        }
+    }
+}
+
+impl From<&PartialValue> for Value {
+    fn from(value: &PartialValue) -> Self {
+        let mut stack = vec![];
+        let mut done: Option<(Option<String>, Value)> = match open_node(value, None, &mut stack) {
+            Some((_, done)) => return done,
+            None => None,
+        };
+        loop {
+            if let Some((key, value)) = done.take() {
+                match stack.last_mut() {
+                    Some(parent) => match &mut parent.node {
+                        Value::Array(items) => items.push(value),
+                        Value::Object(map) => {
+                            map.insert(key.unwrap_or_default(), value);
+                        }
+                        _ => {}
+                    },
+                    None => return value,
+                }
+            }
+            let top = stack.last_mut().unwrap();
+            let child = match &mut top.children {
+                NodeChildren::Items(items) => items.next().map(|item| (None, item)),
+                NodeChildren::Members(members) => members
+                    .next()
+                    .map(|(key, value)| (Some(key.clone()), value)),
+            };
+            done = match child {
+                Some((key, value)) => open_node(value, key, &mut stack),
+                None => stack.pop().map(|open| (open.key, open.node)),
+            };
+        }
+    }
+}
+
+enum NodeChildren<'a> {
+    Items(std::slice::Iter<'a, PartialValue>),
+    Members(std::slice::Iter<'a, (String, PartialValue)>),
+}
+
+// Converts `value` if it is a string or scalar. A container is opened on `stack` instead.
+fn open_node<'a>(
+    value: &'a PartialValue,
+    key: Option<String>,
+    stack: &mut Vec<Open<Value, NodeChildren<'a>>>,
+) -> Option<(Option<String>, Value)> {
+    let value = match &value.node {
+        PartialNode::Null => Value::Null,
+        PartialNode::Bool(b) => Value::Bool(*b),
+        PartialNode::Number(n) => Value::Number(n.clone()),
+        PartialNode::String(s) => Value::String(s.clone()),
+        PartialNode::Array(items) => {
+            stack.push(Open {
+                node: Value::Array(Vec::with_capacity(items.len())),
+                key,
+                children: NodeChildren::Items(items.iter()),
+            });
+            return None;
+        }
+        PartialNode::Object(members) => {
+            stack.push(Open {
+                node: Value::Object(Map::new()),
+                key,
+                children: NodeChildren::Members(members.iter()),
+            });
+            return None;
+        }
+    };
+    Some((key, value))
+}
+
+impl Serialize for PartialValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("value", &self.node)?;
+        map.serialize_entry("complete", &self.complete)?;
+        map.end()
+    }
+}
+
+impl Serialize for PartialNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            PartialNode::Null => serializer.serialize_unit(),
+            PartialNode::Bool(b) => serializer.serialize_bool(*b),
+            PartialNode::Number(n) => n.serialize(serializer),
+            PartialNode::String(s) => serializer.serialize_str(s),
+            PartialNode::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            PartialNode::Object(members) => {
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+                for (key, value) in members {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+// Only the default builder keeps a `serde_json::Value` to mark, so this is not available with
+// `with_builder`.
+impl JsonStreamParser {
+    // The partial document with every node marked as complete or not, as of the end of the last
+    // chunk. It converts back to `get_result`.
+    pub fn partial_value(&self) -> PartialValue {
+        let mut value = PartialValue::from(self.get_result());
+        if self.stack[0].state == FrameState::Done {
+            return value;
+        }
+        value.complete = false;
+        if self.stack[0].state == FrameState::Ready {
+            return value;
+        }
+        let (slots, _) = self.open_slots();
+        let mut node = &mut value;
+        for slot in slots {
+            node = match node.slot_mut(slot) {
+                Some(node) => node,
+                None => break,
+            };
+            node.complete = false;
+        }
+        value
+    }
+}
+
+// A child of a container: the last element of an array, or the member of an object with a key.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Slot<'a> {
+    Last,
+    Key(&'a str),
+}
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // The slots that lead from the root container to the incomplete nodes: the open containers
+    // of the frames above the bottom one, then the string or scalar being written in the
+    // innermost one or the member whose value has not started, if any, which the returned flag
+    // says. A member collected under `DuplicateKeys::Collect` is followed by the last slot of its
+    // array.
+    pub(crate) fn open_slots(&self) -> (Vec<Slot<'_>>, bool) {
+        let innermost = self.stack.len() - 1;
+        let mut slots = Vec::with_capacity(innermost);
+        for (index, frame) in self.stack.iter().enumerate() {
+            let leaf = index == innermost
+                && (matches!(
+                    self.token,
+                    Token::String(_) | Token::Number | Token::Literal { .. }
+                ) || (frame.object
+                    && matches!(
+                        frame.state,
+                        FrameState::ExpectColon | FrameState::ExpectValue
+                    )));
+            // a member left out under `DuplicateKeys::FirstWins` is not in the document
+            if (index < innermost || leaf) && self.skip != Some(index) {
+                if frame.object {
+                    slots.push(Slot::Key(&frame.key));
+                    if frame.keys.get(&frame.key) == Some(&true) {
+                        slots.push(Slot::Last);
+                    }
+                } else {
+                    slots.push(Slot::Last);
+                }
+            } else {
+                return (slots, false);
+            }
+            if leaf {
+                return (slots, true);
+            }
+        }
+        (slots, false)
+    }
+}
@@ -0,0 +1,93 @@
+use crate::glob::Glob;
+use crate::transaction::Event;
+use crate::{FrameState, JsonStreamParser, ValueBuilder};
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // Only builds the values whose path matches one of `globs`, JSON Pointers whose tokens can be
+    // `*` for any one token or `**` for any number of them, such as `/items/*/id`, and the
+    // containers they are in. Everything else is lexed and checked against the limits, but left
+    // out of the document without being built: a member is removed, and an array element is null,
+    // so that every value keeps the index it has in the input. A container that could hold a
+    // selected value is built while it is open, and left out once it ends if it holds none. The
+    // root value is always kept. Panics if a glob is not a JSON Pointer.
+    pub fn project<'a>(mut self, globs: impl IntoIterator<Item = &'a str>) -> JsonStreamParser<B> {
+        let globs = globs.into_iter().map(|glob| {
+            Glob::new(glob).unwrap_or_else(|| panic!("projection {glob:?} is not a JSON Pointer"))
+        });
+        self.projection.get_or_insert_with(Vec::new).extend(globs);
+        self
+    }
+
+    // The globs to sort the value whose slot is in the innermost frame with, unless it is in a
+    // value that is already kept or left out as a whole, or it is the root.
+    fn projecting(&self) -> Option<&[Glob]> {
+        let globs = self.projection.as_deref()?;
+        if self.skip.is_some()
+            || self.selected.is_some()
+            || self.redacted.is_some()
+            || self.stack[0].state == FrameState::Ready
+        {
+            return None;
+        }
+        Some(globs)
+    }
+
+    // Whether the member whose key has just been lexed can hold a selected value. If not, it is
+    // not even added.
+    pub(crate) fn projects_member(&self) -> bool {
+        match self.projecting() {
+            Some(globs) => {
+                let path = self.slot_path();
+                globs.iter().any(|glob| glob.reaches(&path))
+            }
+            None => true,
+        }
+    }
+
+    // Sorts the value that has just started into the projection: everything in a selected value
+    // is kept, a container on the way to one is kept as far as it is selected, and anything else
+    // is left out, along with the member it was added to, or in place of the array element.
+    pub(crate) fn project_value(&mut self, item: bool, container: bool) {
+        let globs = match self.projecting() {
+            Some(globs) => globs,
+            None => return,
+        };
+        let path = self.slot_path();
+        let index = self.stack.len() - 1;
+        if globs.iter().any(|glob| glob.matches(&path)) {
+            self.selected = Some(index);
+            self.holding = self.holding.max(index + 1);
+        } else if !container || !globs.iter().any(|glob| glob.reaches(&path)) {
+            if item {
+                self.events.push(Event::PushItem);
+                self.events.push(Event::EndValue);
+            } else if self.stack[index].object {
+                self.events.push(Event::Discard);
+            }
+            self.skip = Some(index);
+        }
+    }
+
+    // Whether the value being parsed is an array element left out by the projection, which is
+    // null in the document but has its span.
+    pub(crate) fn placeholder(&self) -> bool {
+        let index = self.stack.len() - 1;
+        self.skip == Some(index) && !self.stack[index].object
+    }
+
+    // Leaves out the container that has just ended if it was on the way to a selected value but
+    // holds none.
+    pub(crate) fn end_projected_container(&mut self) {
+        let index = self.stack.len();
+        let holds = index < self.holding;
+        self.holding = self.holding.min(index);
+        if holds || self.projecting().is_none() {
+            return;
+        }
+        self.events.push(if self.stack[index - 1].object {
+            Event::Discard
+        } else {
+            Event::Clear
+        });
+    }
+}
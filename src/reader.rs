@@ -0,0 +1,224 @@
+use std::io::{self, Read};
+
+use serde_json::Value;
+
+use crate::utf8::Utf8Decoder;
+use crate::{JsonStreamParser, Lexeme, ParseError, Step};
+
+const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+fn invalid_data(e: ParseError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// How often `ReaderSnapshots` yields a snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Granularity {
+    // After every completed token: punctuation, keys, strings, numbers and literals.
+    Token,
+    // After every completed value, at any depth.
+    Value,
+    // After at least this many bytes have been parsed since the previous snapshot.
+    Bytes(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    // The partial document at this point of the input.
+    pub value: Value,
+    // The number of input bytes parsed so far.
+    pub offset: usize,
+}
+
+// Parses everything `reader` produces, like `parse_stream` does for a string. The input is read
+// through a bounded buffer, so it can follow a pipe or a file that is still being written.
+pub fn parse_reader<R: Read>(mut reader: R) -> io::Result<Value> {
+    let mut parser = JsonStreamParser::new();
+    let mut decoder = Utf8Decoder::default();
+    let mut buffer = vec![0; DEFAULT_BUFFER_SIZE];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        decoder
+            .decode(&buffer[..read], |s| parser.add_str(s))
+            .map_err(invalid_data)?;
+    }
+    decoder.finish().map_err(invalid_data)?;
+    parser.end_root_number().map_err(invalid_data)?;
+    Ok(parser.into_result())
+}
+
+// An iterator of snapshots of the document read from a `std::io::Read`, created with
+// `JsonStreamParser::from_reader`. Reads block, and each snapshot is yielded as soon as the input
+// for it has arrived. A final snapshot is yielded at the end of the input if anything was parsed
+// after the previous one. Parse errors and invalid UTF-8 are reported as
+// `io::ErrorKind::InvalidData` and end the iteration.
+pub struct ReaderSnapshots<R> {
+    reader: R,
+    parser: JsonStreamParser,
+    granularity: Granularity,
+    decoder: Utf8Decoder,
+    buffer: Box<[u8]>,
+    // decoded text of the last read, fed to the parser in one chunk per snapshot interval
+    chunk: String,
+    position: usize,
+    offset: usize,
+    bytes_since_snapshot: usize,
+    done: bool,
+}
+
+impl JsonStreamParser {
+    pub fn from_reader<R: Read>(reader: R) -> ReaderSnapshots<R> {
+        ReaderSnapshots::with_parser(reader, JsonStreamParser::new())
+    }
+}
+
+impl<R: Read> ReaderSnapshots<R> {
+    // Uses a preconfigured parser, for example one created with `JsonStreamParser::with_limits`.
+    pub fn with_parser(reader: R, parser: JsonStreamParser) -> ReaderSnapshots<R> {
+        ReaderSnapshots {
+            reader,
+            parser,
+            granularity: Granularity::Value,
+            decoder: Utf8Decoder::default(),
+            buffer: vec![0; DEFAULT_BUFFER_SIZE].into_boxed_slice(),
+            chunk: String::new(),
+            position: 0,
+            offset: 0,
+            bytes_since_snapshot: 0,
+            done: false,
+        }
+    }
+
+    // Defaults to `Granularity::Value`.
+    pub fn granularity(mut self, granularity: Granularity) -> ReaderSnapshots<R> {
+        self.granularity = granularity;
+        self
+    }
+
+    // The size of the read buffer, which bounds how much input is read ahead of the parser.
+    // Defaults to 8 KiB.
+    pub fn buffer_size(mut self, size: usize) -> ReaderSnapshots<R> {
+        self.buffer = vec![0; size.max(1)].into_boxed_slice();
+        self
+    }
+
+    pub fn parser(&self) -> &JsonStreamParser {
+        &self.parser
+    }
+
+    pub fn into_parser(self) -> JsonStreamParser {
+        self.parser
+    }
+
+    fn snapshot(&mut self) -> Snapshot {
+        self.bytes_since_snapshot = 0;
+        Snapshot {
+            value: self.parser.get_result().clone(),
+            offset: self.offset,
+        }
+    }
+
+    // Feeds the decoded text from `position` up to the end of the snapshot interval, or of the
+    // text, as one chunk, and reports whether it ended the interval.
+    fn feed(&mut self) -> Result<bool, ParseError> {
+        let rest = &self.chunk[self.position..];
+        let (parsed, ended) = match self.granularity {
+            Granularity::Bytes(bytes) => {
+                let wanted = bytes.max(1) - self.bytes_since_snapshot;
+                let mut end = wanted.min(rest.len());
+                while !rest.is_char_boundary(end) {
+                    end += 1;
+                }
+                self.parser.add_str(&rest[..end])?;
+                (end, end >= wanted)
+            }
+            granularity => {
+                let mut ended = false;
+                let mut stop = |parser: &JsonStreamParser, step: Step| {
+                    ended = ends_interval(granularity, parser, step);
+                    ended
+                };
+                let parsed = self.parser.add_str_until(rest, Some(&mut stop))?;
+                (parsed, ended)
+            }
+        };
+        self.position += parsed;
+        self.offset += parsed;
+        self.bytes_since_snapshot += parsed;
+        Ok(ended)
+    }
+}
+
+// Whether the step of the lexer that has just been taken ends a snapshot interval of
+// `granularity`.
+fn ends_interval(granularity: Granularity, parser: &JsonStreamParser, step: Step) -> bool {
+    let before = step.lexical;
+    let after = parser.lexical_position();
+    let finished_token = before.1 != Lexeme::None && before != after;
+    match granularity {
+        Granularity::Token => {
+            finished_token || matches!(step.byte, b'{' | b'}' | b'[' | b']' | b',' | b':')
+        }
+        Granularity::Value => {
+            (finished_token && before.1 != Lexeme::Key)
+                || after.0 < before.0
+                || (!step.complete && parser.is_complete())
+        }
+        Granularity::Bytes(_) => false,
+    }
+}
+
+impl<R: Read> Iterator for ReaderSnapshots<R> {
+    type Item = io::Result<Snapshot>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.position < self.chunk.len() {
+                match self.feed() {
+                    Ok(true) => return Some(Ok(self.snapshot())),
+                    Ok(false) => continue,
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(invalid_data(e)));
+                    }
+                }
+            }
+
+            let read = match self.reader.read(&mut self.buffer) {
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            };
+            if read == 0 {
+                self.done = true;
+                if let Err(e) = self.decoder.finish() {
+                    return Some(Err(invalid_data(e)));
+                }
+                if self.bytes_since_snapshot > 0 {
+                    return Some(Ok(self.snapshot()));
+                }
+                return None;
+            }
+            self.chunk.clear();
+            self.position = 0;
+            let chunk = &mut self.chunk;
+            let decoded = self.decoder.decode(&self.buffer[..read], |s| {
+                chunk.push_str(s);
+                Ok(())
+            });
+            if let Err(e) = decoded {
+                self.done = true;
+                return Some(Err(invalid_data(e)));
+            }
+        }
+        None
+    }
+}
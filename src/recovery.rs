@@ -0,0 +1,180 @@
+use serde_json::Value;
+
+use crate::transaction::{Event, Events};
+use crate::{ErrorKind, FrameState, JsonStreamParser, ParseError, Scalar, Token, ValueBuilder};
+
+// What recovery mode puts in place of a malformed value.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub enum Recovery {
+    // The value is left out: an object member is removed, and an array element is not added.
+    Drop,
+    // The value is replaced with this one.
+    Replace(Value),
+}
+
+// How input is skipped after a syntax error in recovery mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Resync {
+    // The rest of a malformed value, up to the next `,`, `]` or `}` outside of it. `depth` counts
+    // the brackets it has opened.
+    Value {
+        depth: usize,
+        string: bool,
+        escape: bool,
+    },
+    // Everything after a malformed or complete value at the root.
+    Rest,
+}
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // Handles an error raised at `error.offset()` in the current chunk. In recovery mode, a syntax
+    // error is recorded, the malformed value is dropped or replaced, and the input is skipped from
+    // there until it can be resynchronised. Returns where to go on from.
+    pub(crate) fn recover(&mut self, error: ParseError) -> Result<usize, ParseError> {
+        if self.recovery.is_none() || !matches!(error.kind(), ErrorKind::Syntax(_)) {
+            return Err(error);
+        }
+        let position = error.offset();
+        let frame = self.stack.last().unwrap();
+        // whether the error is where a value goes, and whether the value has a slot yet
+        let (value, slot) = match (frame.state, self.token) {
+            (_, Token::Key(_)) => (false, false),
+            (FrameState::ExpectValue, Token::None) => (true, frame.object),
+            (FrameState::Ready | FrameState::ExpectValue | FrameState::ExpectColon, _) => {
+                (true, true)
+            }
+            _ => (false, false),
+        };
+        let root =
+            self.stack.len() == 1 && matches!(frame.state, FrameState::Ready | FrameState::Done);
+        // an array element left out by the projection keeps its span, which ends at the error
+        if value && self.placeholder() && self.token != Token::None {
+            self.emit_span(Event::ValueEnd(position));
+        }
+        if value && self.skip.is_none() {
+            // a malformed string or scalar spans up to the error, and a marker that replaces a
+            // value that had not started is given an empty span at the error
+            let started = self.token != Token::None;
+            if started {
+                self.emit_span(Event::ValueEnd(position));
+            }
+            match self.recovery.as_ref().unwrap() {
+                // a redacted value keeps its placeholder, whatever is malformed in it
+                _ if self.redacted.is_some() => {}
+                Recovery::Drop if slot => self.events.push(Event::Discard),
+                Recovery::Drop => {}
+                Recovery::Replace(marker) => {
+                    if !slot {
+                        self.events.push(Event::PushItem);
+                    }
+                    if !started && self.spans.is_some() {
+                        self.events.push(Event::ValueStart(position));
+                        self.events.push(Event::ValueEnd(position));
+                    }
+                    push_value(&mut self.events, marker);
+                }
+            }
+        }
+        let string = matches!(self.token, Token::String(_) | Token::Key(_));
+        self.token = Token::None;
+        if let Some(text) = self.held.take() {
+            self.undo.keep_held(text);
+        }
+        if root {
+            self.stack[0].state = FrameState::Done;
+            self.resync = Some(Resync::Rest);
+        } else {
+            self.end_value();
+            self.resync = Some(Resync::Value {
+                depth: 0,
+                string,
+                escape: false,
+            });
+        }
+        self.diagnostics.push(error.shifted(self.offset));
+        Ok(position)
+    }
+
+    // Skips input from `position` as `self.resync` says, and handles the `,`, `]` or `}` it stops
+    // at as if it followed a value. Returns the position after the skipped input.
+    pub(crate) fn resync(&mut self, input: &str, mut position: usize) -> Result<usize, ParseError> {
+        let bytes = input.as_bytes();
+        let (mut depth, mut string, mut escape) = match self.resync {
+            Some(Resync::Value {
+                depth,
+                string,
+                escape,
+            }) => (depth, string, escape),
+            _ => return Ok(bytes.len()),
+        };
+        while position < bytes.len() {
+            let byte = bytes[position];
+            if string {
+                match byte {
+                    _ if escape => escape = false,
+                    b'\\' => escape = true,
+                    b'"' => string = false,
+                    _ => {}
+                }
+            } else {
+                match byte {
+                    b'"' => string = true,
+                    b'[' | b'{' => depth += 1,
+                    b']' | b'}' if depth > 0 => depth -= 1,
+                    b',' | b']' | b'}' if depth == 0 => {
+                        self.resync = None;
+                        return match self.structural(input, position) {
+                            Ok(position) => Ok(position),
+                            // a bracket that does not close the container, which is skipped too
+                            Err(error) => self.recover(error).map(|position| position + 1),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+            position += 1;
+        }
+        self.resync = Some(Resync::Value {
+            depth,
+            string,
+            escape,
+        });
+        Ok(position)
+    }
+}
+
+// Records the events that build `value` in the current slot.
+pub(crate) fn push_value(events: &mut Events, value: &Value) {
+    match value {
+        Value::Null => events.push(Event::SetScalar(Scalar::Null)),
+        Value::Bool(b) => events.push(Event::SetScalar(Scalar::Bool(*b))),
+        Value::Number(n) => events.push(Event::SetScalar(Scalar::Number(n.clone()))),
+        Value::String(s) => {
+            events.push(Event::StartString);
+            for c in s.chars() {
+                events.push(Event::AppendChar(c));
+            }
+        }
+        Value::Array(arr) => {
+            events.push(Event::StartArray);
+            for item in arr {
+                events.push(Event::PushItem);
+                push_value(events, item);
+            }
+            events.push(Event::EndContainer);
+            return;
+        }
+        Value::Object(map) => {
+            events.push(Event::StartObject);
+            for (key, item) in map {
+                events.push_key(key, None);
+                push_value(events, item);
+            }
+            events.push(Event::EndContainer);
+            return;
+        }
+    }
+    events.push(Event::EndValue);
+}
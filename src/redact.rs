@@ -0,0 +1,195 @@
+#[cfg(feature = "checkpoint")]
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "checkpoint")]
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::glob::Glob;
+use crate::recovery::push_value;
+use crate::transaction::Event;
+use crate::{FrameState, JsonStreamParser, Token, ValueBuilder};
+
+type Matcher = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+// Which values `JsonStreamParser::redact` replaces, and with what.
+#[derive(Clone)]
+pub struct Redaction {
+    paths: Vec<Glob>,
+    matcher: Option<Matcher>,
+    placeholder: Value,
+}
+
+impl Redaction {
+    pub fn new() -> Redaction {
+        Redaction {
+            paths: Vec::new(),
+            matcher: None,
+            placeholder: Value::from("[REDACTED]"),
+        }
+    }
+
+    // Redacts the values whose path matches `glob`, a JSON Pointer whose tokens can be `*` for any
+    // one token or `**` for any number of them, such as `/**/api_key`. Panics if it is not a JSON
+    // Pointer.
+    pub fn path(mut self, glob: &str) -> Redaction {
+        let glob = Glob::new(glob)
+            .unwrap_or_else(|| panic!("redaction path {glob:?} is not a JSON Pointer"));
+        self.paths.push(glob);
+        self
+    }
+
+    // Redacts the strings `matcher` returns true for, such as email addresses. Since it can only
+    // tell once a string is complete, strings are held back until their closing quote, and their
+    // slot stays null until then.
+    pub fn matching(mut self, matcher: impl Fn(&str) -> bool + Send + Sync + 'static) -> Redaction {
+        self.matcher = Some(Arc::new(matcher));
+        self
+    }
+
+    // The value put in place of a redacted one. Defaults to the string `"[REDACTED]"`.
+    pub fn placeholder(mut self, placeholder: Value) -> Redaction {
+        self.placeholder = placeholder;
+        self
+    }
+}
+
+// A `Redaction` in a checkpoint, borrowed when saved and owned when restored. A matcher cannot be
+// saved, so only whether there is one is.
+#[cfg(feature = "checkpoint")]
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SavedRedaction<'a> {
+    paths: Cow<'a, [Glob]>,
+    placeholder: Cow<'a, Value>,
+    matching: bool,
+}
+
+#[cfg(feature = "checkpoint")]
+impl<'a> From<&'a Redaction> for SavedRedaction<'a> {
+    fn from(redaction: &'a Redaction) -> Self {
+        SavedRedaction {
+            paths: Cow::Borrowed(&redaction.paths),
+            placeholder: Cow::Borrowed(&redaction.placeholder),
+            matching: redaction.matcher.is_some(),
+        }
+    }
+}
+
+#[cfg(feature = "checkpoint")]
+impl SavedRedaction<'_> {
+    // The redaction to restore: `given` if there is one, which must have a matcher if the saved
+    // one did, or else the saved one, which must not have had a matcher.
+    pub(crate) fn restore(self, given: Option<Redaction>) -> Result<Redaction, &'static str> {
+        match given {
+            Some(given) if self.matching && given.matcher.is_none() => {
+                Err("checkpoint redacts strings with a matcher, which the redaction has not got")
+            }
+            Some(given) => Ok(given),
+            None if self.matching => Err(
+                "checkpoint redacts strings with a matcher, which has to be given to `restore_with`",
+            ),
+            None => Ok(Redaction {
+                paths: self.paths.into_owned(),
+                matcher: None,
+                placeholder: self.placeholder.into_owned(),
+            }),
+        }
+    }
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Redaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Redaction")
+            .field("paths", &self.paths)
+            .field("matcher", &self.matcher.as_ref().map(|_| ".."))
+            .field("placeholder", &self.placeholder)
+            .finish()
+    }
+}
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // Replaces the values `redaction` selects with its placeholder as soon as they start, so that
+    // the builder, and every snapshot taken from it, never sees them. What is in them is still
+    // lexed and checked against the limits, but neither kept nor recorded in the spans, and the
+    // keys in them are not looked up for duplicates. The root value itself is never redacted, and
+    // the keys of the members it is in are kept as they are.
+    pub fn redact(mut self, redaction: Redaction) -> JsonStreamParser<B> {
+        self.redaction = Some(redaction);
+        self
+    }
+
+    // Puts the placeholder in the slot of the value that has just started, if its path matches.
+    pub(crate) fn redact_value(&mut self) {
+        let redaction = match &self.redaction {
+            Some(redaction) if !redaction.paths.is_empty() => redaction,
+            _ => return,
+        };
+        if self.skip.is_some()
+            || self.redacted.is_some()
+            || self.stack[0].state == FrameState::Ready
+        {
+            return;
+        }
+        let path = self.slot_path();
+        if redaction.paths.iter().any(|glob| glob.matches(&path)) {
+            push_value(&mut self.events, &redaction.placeholder);
+            self.redacted = Some(self.stack.len() - 1);
+        }
+    }
+
+    // Holds the string that has just started back from the builder until the matcher can tell
+    // whether to redact it.
+    pub(crate) fn hold_string(&mut self) {
+        let matching = self
+            .redaction
+            .as_ref()
+            .map_or(false, |redaction| redaction.matcher.is_some());
+        if matching
+            && self.skip.is_none()
+            && self.redacted.is_none()
+            && self.stack[0].state != FrameState::Ready
+        {
+            self.held = Some(String::new());
+        }
+    }
+
+    // Ends the held string before `end`, giving it to the builder as it is or as the placeholder.
+    pub(crate) fn release_string(&mut self, end: usize) {
+        let text = self.held.take().unwrap();
+        let redaction = self.redaction.as_ref().unwrap();
+        if (redaction.matcher.as_ref().unwrap())(&text) {
+            push_value(&mut self.events, &redaction.placeholder);
+        } else {
+            self.events.push(Event::StartString);
+            for c in text.chars() {
+                self.events.push(Event::AppendChar(c));
+            }
+            self.events.push(Event::EndValue);
+        }
+        self.undo.keep_held(text);
+        self.emit_span(Event::ValueEnd(end));
+        self.token = Token::None;
+        self.end_value();
+    }
+
+    // Appends the text of the number being lexed. A redacted number only keeps its shape, since
+    // whether a digit is zero is all the grammar of numbers cares about.
+    pub(crate) fn push_number(&mut self, text: &str) {
+        if self.redacted.is_none() {
+            self.number.push_str(text);
+            return;
+        }
+        self.number.extend(text.chars().map(|c| match c {
+            '1'..='9' => '1',
+            c => c,
+        }));
+    }
+}
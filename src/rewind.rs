@@ -0,0 +1,367 @@
+use std::any::Any;
+use std::fmt;
+use std::time::Duration;
+
+use crate::builder::BuilderMark;
+use crate::spans::{Spans, SpansMark};
+use crate::transaction::Undo;
+use crate::utf8::Utf8Decoder;
+use crate::{next_generation, JsonStreamParser, SerdeValueBuilder, ValueBuilder};
+
+// A state saved by `JsonStreamParser::checkpoint`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CheckpointId {
+    id: u64,
+    offset: usize,
+}
+
+impl CheckpointId {
+    // The number of chars received before the checkpoint.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+// Why the parser could not be rewound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewindError {
+    // The offset is past the input received so far.
+    Ahead,
+    // The offset is before the input the parser keeps, or the checkpoint was rewound past.
+    Forgotten,
+}
+
+impl fmt::Display for RewindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewindError::Ahead => f.write_str("offset is past the input received so far"),
+            RewindError::Forgotten => f.write_str("offset is no longer kept"),
+        }
+    }
+}
+
+impl std::error::Error for RewindError {}
+
+// A chunk of the input kept, with the token id and time it was received with.
+#[derive(Clone)]
+struct Chunk {
+    // its end in `History::text`
+    end: usize,
+    token: Option<usize>,
+    now: Option<Duration>,
+}
+
+// The builder at a mark: a log of its changes if it is a `SerdeValueBuilder`, or else a copy.
+#[derive(Clone)]
+enum Saved<B> {
+    Copy(B),
+    Log(BuilderMark),
+}
+
+// The parser as it was between two chunks, and the changes made from there to the next mark, or
+// for the last mark, the changes to the lexer so far. The document is not copied, so a mark costs
+// as much as the changes after it rather than as much as the document.
+#[derive(Clone)]
+struct Mark<B> {
+    // the chars received before it, the bytes, and its byte offset in `History::text`
+    offset: usize,
+    bytes: usize,
+    position: usize,
+    // the number of chunks in `History::chunks` before it
+    chunk: usize,
+    // set for marks made by `checkpoint`
+    id: Option<u64>,
+    // whether it was made in a string held back for redaction that has ended since, so that it
+    // can no longer be gone back to
+    dead: bool,
+    lexer: Undo,
+    builder: Saved<B>,
+    spans: Option<SpansMark>,
+}
+
+// The input received since the first mark, by chunk, and marks along it, from which any earlier
+// offset is reached by undoing the changes since the last mark before it and replaying the chunks
+// after that mark. A parser that redacts keeps no input, so it can only go back to its marks.
+#[derive(Clone)]
+pub(crate) struct History<B> {
+    text: String,
+    chunks: Vec<Chunk>,
+    // by offset, the first one at the start of `text`
+    marks: Vec<Mark<B>>,
+    // whether a mark was made while a string was held back for redaction
+    held: bool,
+    // the chars between two marks made as the input is received
+    interval: Option<usize>,
+    next_id: u64,
+    // the time of the chunk being replayed, which `stamp` gives instead of reading the clock
+    pub(crate) replaying: Option<Option<Duration>>,
+    // `Clone::clone` and a downcast, kept here so that `add_str` can make marks without requiring
+    // `B: Clone + 'static`
+    copy: fn(&B) -> B,
+    serde: fn(&mut B) -> Option<&mut SerdeValueBuilder>,
+}
+
+impl<B: ValueBuilder + Clone + 'static> JsonStreamParser<B> {
+    // Keeps the input from here on and a mark every `interval` chars, so that `truncate_to` can go
+    // back to any later offset by replaying at most `interval` chars. A mark logs the changes made
+    // after it rather than copying the document, unless the builder is not a `SerdeValueBuilder`.
+    // A parser that redacts does not keep its input, and only goes back to the offsets of its
+    // marks.
+    pub fn rewindable(mut self, interval: usize) -> JsonStreamParser<B> {
+        self.start_history();
+        if let Some(history) = &mut self.history {
+            history.interval = Some(interval.max(1));
+        }
+        self
+    }
+
+    // Saves the current state, which `rollback` goes back to without replaying any input. The
+    // input is kept from the first checkpoint on. A checkpoint saved while a string is held back
+    // for redaction is forgotten once the string ends, along with the text of it.
+    pub fn checkpoint(&mut self) -> CheckpointId {
+        self.start_history();
+        let history = self.history.as_mut().unwrap();
+        let id = history.next_id;
+        history.next_id += 1;
+        self.mark(Some(id));
+        CheckpointId {
+            id,
+            offset: self.processed_chars,
+        }
+    }
+
+    // Goes back to the state saved by `checkpoint`, forgetting the input received since.
+    pub fn rollback(&mut self, checkpoint: CheckpointId) -> Result<(), RewindError> {
+        let history = self.history.as_ref().ok_or(RewindError::Forgotten)?;
+        if !history
+            .marks
+            .iter()
+            .any(|mark| mark.id == Some(checkpoint.id) && !mark.dead)
+        {
+            return Err(RewindError::Forgotten);
+        }
+        self.truncate_to(checkpoint.offset)
+    }
+
+    fn start_history(&mut self) {
+        if self.history.is_none() {
+            self.history = Some(History {
+                text: String::new(),
+                chunks: Vec::new(),
+                marks: Vec::new(),
+                held: false,
+                interval: None,
+                next_id: 0,
+                replaying: None,
+                copy: B::clone,
+                serde: as_serde::<B>,
+            });
+            self.mark(None);
+        }
+    }
+}
+
+fn as_serde<B: 'static>(builder: &mut B) -> Option<&mut SerdeValueBuilder> {
+    (builder as &mut dyn Any).downcast_mut()
+}
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // Goes back to the state after the first `offset` chars of input, as if the chars after them
+    // had never been sent. The input must have been kept, by `rewindable` or `checkpoint`, since
+    // before `offset`. The chunks after the last mark before `offset` are parsed again as they
+    // were received, with their token ids and times. Bytes of an incomplete UTF-8 sequence
+    // written through `io::Write` are dropped too.
+    pub fn truncate_to(&mut self, offset: usize) -> Result<(), RewindError> {
+        if offset > self.processed_chars {
+            return Err(RewindError::Ahead);
+        }
+        let history = self.history.as_ref().ok_or(RewindError::Forgotten)?;
+        let index = history
+            .marks
+            .iter()
+            .rposition(|mark| mark.offset <= offset && !mark.dead)
+            .ok_or(RewindError::Forgotten)?;
+        if self.redaction.is_some() && history.marks[index].offset != offset {
+            return Err(RewindError::Forgotten);
+        }
+        let mut history = self.history.take().unwrap();
+        self.rewind_to(&mut history, index);
+        let mark = &history.marks[index];
+        let start = mark.position;
+        let text = history.text.split_off(start);
+        let chunks = history.chunks.split_off(mark.chunk);
+        let end = text
+            .char_indices()
+            .nth(offset - mark.offset)
+            .map_or(text.len(), |(index, _)| index);
+        self.history = Some(history);
+        let mut position = 0;
+        for chunk in chunks {
+            if position >= end {
+                break;
+            }
+            let next = (chunk.end - start).min(end);
+            self.replay(&text[position..next], chunk.token, chunk.now);
+            position = next;
+        }
+        self.utf8 = Utf8Decoder::at(self.offset);
+        self.generation = next_generation();
+        Ok(())
+    }
+
+    // The number of chars received so far.
+    pub fn char_offset(&self) -> usize {
+        self.processed_chars
+    }
+
+    // The chars between two marks made as the input is received, if the parser is rewindable.
+    pub(crate) fn interval(&self) -> Option<usize> {
+        self.history.as_ref()?.interval
+    }
+
+    // Keeps a chunk that has been parsed along with the changes it made to the lexer, and makes a
+    // mark if one is due. A redacted value can only be told from the input while it is parsed, so
+    // a parser that redacts keeps no input, and no mark holding a string that has been held back
+    // for redaction once the string has ended.
+    pub(crate) fn record(&mut self, chunk: &str) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        if self.redaction.is_none() {
+            history.text.push_str(chunk);
+            let (token, now) = self
+                .spans
+                .as_ref()
+                .map_or((None, None), |spans| (spans.token, spans.now));
+            history.chunks.push(Chunk {
+                end: history.text.len(),
+                token,
+                now,
+            });
+        }
+        let last = history.marks.last_mut().unwrap();
+        last.lexer.absorb(&mut self.undo);
+        let last = last.offset;
+        if history.held {
+            let held = self.held.as_deref();
+            for mark in &mut history.marks {
+                let Some(old) = mark.lexer.held() else {
+                    continue;
+                };
+                if !held.map_or(false, |new| new.starts_with(old)) {
+                    mark.dead = true;
+                    mark.lexer.forget_held();
+                }
+            }
+            history.held = history.marks.iter().any(|mark| mark.lexer.held().is_some());
+        }
+        if let Some(interval) = history.interval {
+            // a mark in a held string would be forgotten anyway
+            if self.processed_chars - last >= interval && self.held.is_none() {
+                self.mark(None);
+            }
+        }
+    }
+
+    fn mark(&mut self, id: Option<u64>) {
+        let mut history = self.history.take().unwrap();
+        history.held |= self.held.is_some();
+        self.take_changes(&mut history);
+        let mut lexer = Undo::default();
+        self.save_lexer(&mut lexer);
+        lexer.hold(self.held.as_deref());
+        let builder = match (history.serde)(&mut self.builder) {
+            Some(builder) => Saved::Log(builder.mark()),
+            None => Saved::Copy((history.copy)(&self.builder)),
+        };
+        history.marks.push(Mark {
+            offset: self.processed_chars,
+            bytes: self.offset,
+            position: history.text.len(),
+            chunk: history.chunks.len(),
+            id,
+            dead: false,
+            lexer,
+            builder,
+            spans: self.spans.as_mut().map(Spans::mark),
+        });
+        self.history = Some(history);
+    }
+
+    // Moves the changes logged by the builder and the spans since the last mark into it.
+    fn take_changes(&mut self, history: &mut History<B>) {
+        let Some(mark) = history.marks.last_mut() else {
+            return;
+        };
+        if let Saved::Log(saved) = &mut mark.builder {
+            saved.changes = (history.serde)(&mut self.builder).unwrap().take_changes();
+        }
+        if let (Some(spans), Some(saved)) = (&mut self.spans, &mut mark.spans) {
+            saved.changes = spans.take_changes();
+        }
+    }
+
+    // Goes back to the mark at `index`, undoing the changes since, newest first, and forgets the
+    // marks after it. The input is left to the caller.
+    fn rewind_to(&mut self, history: &mut History<B>, index: usize) {
+        self.take_changes(history);
+        let serde = history.serde;
+        for mut mark in history.marks.drain(index + 1..).rev() {
+            self.undo_mark(&mut mark, serde);
+        }
+        let mark = &mut history.marks[index];
+        self.undo_mark(mark, serde);
+        match &mark.builder {
+            Saved::Copy(builder) => self.builder = (history.copy)(builder),
+            Saved::Log(saved) => serde(&mut self.builder).unwrap().restore(saved),
+        }
+        match (&mut self.spans, &mut mark.spans) {
+            (Some(spans), Some(saved)) => *saved = spans.mark(),
+            // spans recorded from the start, but after the mark was made
+            (Some(spans), None) => {
+                *spans = Spans::default();
+                mark.spans = Some(spans.mark());
+            }
+            _ => {}
+        }
+        // the mark logs the changes from here on again
+        self.save_lexer(&mut mark.lexer);
+        mark.lexer.hold(self.held.as_deref());
+        self.offset = mark.bytes;
+        self.processed_chars = mark.offset;
+    }
+
+    fn undo_mark(
+        &mut self,
+        mark: &mut Mark<B>,
+        serde: fn(&mut B) -> Option<&mut SerdeValueBuilder>,
+    ) {
+        self.restore_lexer(&mut mark.lexer);
+        if let Saved::Log(saved) = &mut mark.builder {
+            serde(&mut self.builder).unwrap().rewind(&mut saved.changes);
+        }
+        if let (Some(spans), Some(saved)) = (&mut self.spans, &mut mark.spans) {
+            spans.rewind(saved);
+        }
+    }
+
+    // Parses a chunk again as it was first received, with its token id and time.
+    fn replay(&mut self, chunk: &str, token: Option<usize>, now: Option<Duration>) {
+        if let Some(history) = &mut self.history {
+            history.replaying = Some(now);
+        }
+        if let Some(spans) = &mut self.spans {
+            spans.token = token;
+        }
+        self.add_str(chunk).expect("the input was accepted before");
+        if let Some(history) = &mut self.history {
+            history.replaying = None;
+        }
+        if let Some(spans) = &mut self.spans {
+            spans.token = None;
+            if token.is_some() {
+                spans.latest = token;
+            }
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use std::sync::Arc;
+
+#[cfg(feature = "preserve_order")]
+use im::HashMap;
+use im::{OrdMap, Vector};
+use serde_json::{Number, Value};
+
+use crate::{Scalar, ValueBuilder};
+
+// An immutable JSON document whose containers share structure with the snapshots taken before
+// and after it. Cloning is O(1), and it is `Send + Sync`, so a snapshot can be handed to another
+// thread while the parser keeps going.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum SharedValue {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(Arc<str>),
+    Array(Vector<SharedValue>),
+    Object(SharedMap),
+}
+
+impl SharedValue {
+    pub fn to_value(&self) -> Value {
+        Value::from(self)
+    }
+}
+
+impl From<&Value> for SharedValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => SharedValue::Null,
+            Value::Bool(b) => SharedValue::Bool(*b),
+            Value::Number(n) => SharedValue::Number(n.clone()),
+            Value::String(s) => SharedValue::String(Arc::from(s.as_str())),
+            Value::Array(arr) => SharedValue::Array(arr.iter().map(SharedValue::from).collect()),
+            Value::Object(map) => SharedValue::Object(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), SharedValue::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<&SharedValue> for Value {
+    fn from(value: &SharedValue) -> Self {
+        match value {
+            SharedValue::Null => Value::Null,
+            SharedValue::Bool(b) => Value::Bool(*b),
+            SharedValue::Number(n) => Value::Number(n.clone()),
+            SharedValue::String(s) => Value::String(s.to_string()),
+            SharedValue::Array(arr) => Value::Array(arr.iter().map(Value::from).collect()),
+            SharedValue::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// The members of a `SharedValue::Object`. Like those of a `serde_json::Map`, they are sorted by
+// key, or kept in the order they were inserted in with the `preserve_order` feature, and maps with
+// the same members are equal whatever their order.
+#[derive(Clone, Debug, Default)]
+pub struct SharedMap {
+    #[cfg(not(feature = "preserve_order"))]
+    members: OrdMap<String, SharedValue>,
+    // the members by when they were inserted, and when each key was
+    #[cfg(feature = "preserve_order")]
+    members: OrdMap<u64, (String, SharedValue)>,
+    #[cfg(feature = "preserve_order")]
+    inserted: HashMap<String, u64>,
+    #[cfg(feature = "preserve_order")]
+    next: u64,
+}
+
+#[cfg(not(feature = "preserve_order"))]
+impl SharedMap {
+    pub fn new() -> SharedMap {
+        SharedMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SharedValue> {
+        self.members.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut SharedValue> {
+        self.members.get_mut(key)
+    }
+
+    // Sets the value of `key`, and returns the one it had.
+    pub fn insert(&mut self, key: String, value: SharedValue) -> Option<SharedValue> {
+        self.members.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<SharedValue> {
+        self.members.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &SharedValue)> + '_ {
+        self.members.iter()
+    }
+}
+
+#[cfg(feature = "preserve_order")]
+impl SharedMap {
+    pub fn new() -> SharedMap {
+        SharedMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SharedValue> {
+        let inserted = self.inserted.get(key)?;
+        self.members.get(inserted).map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut SharedValue> {
+        let inserted = self.inserted.get(key)?;
+        self.members.get_mut(inserted).map(|(_, value)| value)
+    }
+
+    // Sets the value of `key`, and returns the one it had. A key that is already there keeps its
+    // place.
+    pub fn insert(&mut self, key: String, value: SharedValue) -> Option<SharedValue> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(std::mem::replace(old, value));
+        }
+        self.inserted.insert(key.clone(), self.next);
+        self.members.insert(self.next, (key, value));
+        self.next += 1;
+        None
+    }
+
+    // Removes `key`, keeping the other members in order.
+    pub fn remove(&mut self, key: &str) -> Option<SharedValue> {
+        let inserted = self.inserted.remove(key)?;
+        self.members.remove(&inserted).map(|(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &SharedValue)> + '_ {
+        self.members.values().map(|(key, value)| (key, value))
+    }
+}
+
+impl SharedMap {
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &SharedValue> + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl PartialEq for SharedMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl Eq for SharedMap {}
+
+impl FromIterator<(String, SharedValue)> for SharedMap {
+    fn from_iter<I: IntoIterator<Item = (String, SharedValue)>>(members: I) -> Self {
+        let mut map = SharedMap::new();
+        for (key, value) in members {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+// The completed part of the root or of an open container.
+#[derive(Clone, Debug)]
+struct Level {
+    value: SharedValue,
+    // the key of the current member of an object
+    key: String,
+    // whether the current member collects the values of a duplicate key into an array
+    collected: bool,
+}
+
+impl Level {
+    fn new(value: SharedValue) -> Level {
+        Level {
+            value,
+            key: String::new(),
+            collected: false,
+        }
+    }
+
+    // The level with `child` in its current slot.
+    fn with_child(&self, child: Option<SharedValue>) -> SharedValue {
+        match (&self.value, child) {
+            (SharedValue::Array(arr), Some(child)) => {
+                let mut arr = arr.clone();
+                arr.push_back(child);
+                SharedValue::Array(arr)
+            }
+            (SharedValue::Object(map), Some(child)) if self.collected => {
+                let mut map = map.clone();
+                if let Some(SharedValue::Array(values)) = map.get_mut(&self.key) {
+                    values.push_back(child);
+                }
+                SharedValue::Object(map)
+            }
+            (SharedValue::Object(map), Some(child)) => {
+                let mut map = map.clone();
+                map.insert(self.key.clone(), child);
+                SharedValue::Object(map)
+            }
+            // a root string or scalar
+            (_, Some(child)) => child,
+            (container, None) => container.clone(),
+        }
+    }
+}
+
+// The string or scalar being written into the current slot of the innermost level.
+#[derive(Clone, Debug)]
+enum Open {
+    String(String),
+    Value(SharedValue),
+}
+
+impl Open {
+    fn to_shared(&self) -> SharedValue {
+        match self {
+            Open::String(s) => SharedValue::String(Arc::from(s.as_str())),
+            Open::Value(value) => value.clone(),
+        }
+    }
+}
+
+// Builds a `SharedValue`. Only the completed part of each open container is kept, and completed
+// subtrees are only ever shared, never copied, so `snapshot` costs one clone and one insert per
+// open container plus the length of a string that is still being written.
+#[derive(Clone, Debug)]
+pub struct SharedValueBuilder {
+    // the root first, then one level per open container
+    levels: Vec<Level>,
+    open: Option<Open>,
+}
+
+impl Default for SharedValueBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SharedValueBuilder {
+    pub fn new() -> SharedValueBuilder {
+        SharedValueBuilder {
+            levels: vec![Level::new(SharedValue::Null)],
+            open: None,
+        }
+    }
+
+    // The partial document, equal to what a `SerdeValueBuilder` fed the same events holds.
+    pub fn snapshot(&self) -> SharedValue {
+        let mut child = self.open.as_ref().map(Open::to_shared);
+        for level in self.levels.iter().rev() {
+            child = Some(level.with_child(child));
+        }
+        child.unwrap_or_default()
+    }
+
+    // Splits an existing partial document into the completed part of each open container, given
+    // the key of the current member of each of them and whether it collects duplicate values,
+    // outermost first. `open` is whether the innermost container, or the root if none is open, is
+    // in the middle of a value.
+    pub(crate) fn split(document: &Value, keys: &[(&str, bool)], open: bool) -> SharedValueBuilder {
+        let mut builder = SharedValueBuilder::new();
+        let mut child = Some(SharedValue::from(document));
+        if keys.is_empty() && !open {
+            builder.levels[0].value = child.take().unwrap_or_default();
+        }
+        for (index, &(key, collected)) in keys.iter().enumerate() {
+            let mut container = child.take().unwrap_or_default();
+            if index + 1 < keys.len() || open {
+                child = match &mut container {
+                    SharedValue::Array(arr) => arr.pop_back(),
+                    SharedValue::Object(map) if collected => match map.get_mut(key) {
+                        Some(SharedValue::Array(values)) => values.pop_back(),
+                        _ => None,
+                    },
+                    SharedValue::Object(map) => map.remove(key),
+                    _ => None,
+                };
+            }
+            builder.levels.push(Level {
+                value: container,
+                key: key.to_string(),
+                collected,
+            });
+        }
+        builder.open = child.map(|child| match child {
+            SharedValue::String(s) => Open::String(s.to_string()),
+            value => Open::Value(value),
+        });
+        builder
+    }
+
+    fn start_container(&mut self, value: SharedValue) {
+        self.levels.push(Level::new(value));
+        self.open = None;
+    }
+
+    // Adds a completed value to the current slot of the innermost level.
+    fn complete(&mut self, value: SharedValue) {
+        let level = self.levels.last_mut().unwrap();
+        match &mut level.value {
+            SharedValue::Array(arr) => arr.push_back(value),
+            SharedValue::Object(map) if level.collected => {
+                if let Some(SharedValue::Array(values)) = map.get_mut(&level.key) {
+                    values.push_back(value);
+                }
+            }
+            SharedValue::Object(map) => {
+                map.insert(level.key.clone(), value);
+            }
+            root => *root = value,
+        }
+    }
+}
+
+impl ValueBuilder for SharedValueBuilder {
+    type Output = SharedValue;
+
+    fn start_object(&mut self) {
+        self.start_container(SharedValue::Object(SharedMap::new()));
+    }
+
+    fn start_array(&mut self) {
+        self.start_container(SharedValue::Array(Vector::new()));
+    }
+
+    fn end_container(&mut self) {
+        let child = self.levels.pop().expect("a container is open");
+        self.complete(child.value);
+        self.open = None;
+    }
+
+    fn insert_key(&mut self, key: &str) {
+        let level = self.levels.last_mut().unwrap();
+        level.key.clear();
+        level.key.push_str(key);
+        level.collected = false;
+        self.open = Some(Open::Value(SharedValue::Null));
+    }
+
+    fn collect_key(&mut self, key: &str, wrap: bool) {
+        let level = self.levels.last_mut().unwrap();
+        level.key.clear();
+        level.key.push_str(key);
+        level.collected = true;
+        if let SharedValue::Object(map) = &mut level.value {
+            if let Some(slot) = map.get_mut(key).filter(|_| wrap) {
+                *slot = SharedValue::Array(Vector::unit(std::mem::take(slot)));
+            }
+        }
+        self.open = Some(Open::Value(SharedValue::Null));
+    }
+
+    fn push_item(&mut self) {
+        self.open = Some(Open::Value(SharedValue::Null));
+    }
+
+    fn start_string(&mut self) {
+        self.open = Some(Open::String(String::new()));
+    }
+
+    fn append_string(&mut self, s: &str) {
+        if let Some(Open::String(string)) = &mut self.open {
+            string.push_str(s);
+        }
+    }
+
+    fn set_scalar(&mut self, scalar: Scalar) {
+        let value = match scalar {
+            Scalar::Null => SharedValue::Null,
+            Scalar::Bool(b) => SharedValue::Bool(b),
+            Scalar::Number(n) => SharedValue::Number(n),
+        };
+        self.open = Some(Open::Value(value));
+    }
+
+    fn end_value(&mut self) {
+        if let Some(open) = self.open.take() {
+            let value = match open {
+                Open::String(s) => SharedValue::String(Arc::from(s)),
+                Open::Value(value) => value,
+            };
+            self.complete(value);
+        }
+    }
+
+    fn discard(&mut self) {
+        // a value that has ended is already in its container
+        let ended = self.open.take().is_none();
+        let root = self.levels.len() == 1;
+        let level = self.levels.last_mut().unwrap();
+        if root {
+            level.value = SharedValue::Null;
+            return;
+        }
+        match &mut level.value {
+            SharedValue::Array(arr) if ended => {
+                arr.pop_back();
+            }
+            SharedValue::Object(map) if level.collected => {
+                if let Some(SharedValue::Array(values)) = map.get_mut(&level.key).filter(|_| ended)
+                {
+                    values.pop_back();
+                }
+            }
+            SharedValue::Object(map) => {
+                map.remove(&level.key);
+            }
+            _ => {}
+        }
+    }
+
+    fn into_output(self) -> SharedValue {
+        self.snapshot()
+    }
+}
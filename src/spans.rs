@@ -0,0 +1,599 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use crate::{pointer_tokens, push_pointer_token};
+
+// A position in the input. Lines and columns start at 1, and columns count chars.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+    pub byte: usize,
+    pub char: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Location {
+            byte: 0,
+            char: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+// Where a value or key is in the input. `end` is just past its last char, and is not known yet
+// while the value is still open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: Location,
+    pub end: Option<Location>,
+}
+
+// When a value started and finished streaming, as given by the clock of
+// `JsonStreamParser::timed`, and its char offsets. A value is stamped with the time the chunk
+// holding its first or last char was received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Timing {
+    pub started: Duration,
+    pub finished: Option<Duration>,
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+struct Node {
+    span: Span,
+    // set while the parser is timed
+    started: Option<Duration>,
+    finished: Option<Duration>,
+    // the first token that wrote into it or its key, and the last one once it has ended, under
+    // `JsonStreamParser::add_token`
+    first_token: Option<usize>,
+    last_token: Option<usize>,
+    // for object members
+    key: Option<Span>,
+    name: Option<String>,
+    // whether the member adds a value to a duplicate key under `DuplicateKeys::Collect`
+    collected: bool,
+    children: Vec<usize>,
+    // the container it is in, and its index in the children of that container
+    parent: Option<usize>,
+    position: usize,
+    // the children of an object by key, with every member given for the key in order
+    members: HashMap<String, Vec<usize>>,
+}
+
+// The key of the member whose value comes next.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+struct Pending {
+    span: Span,
+    name: Option<String>,
+    collected: bool,
+    // the tokens that wrote into it
+    tokens: Vec<usize>,
+}
+
+// A change to the spans that `Spans::rewind` undoes, logged for the nodes that were there at the
+// last mark of `JsonStreamParser::rewindable`. The nodes added since are dropped instead.
+#[derive(Clone, Debug)]
+pub(crate) enum Change {
+    // a child was added to the node
+    Added(usize),
+    // the child was removed from the node
+    Removed(usize, usize),
+    // the node, which was open, ended
+    Ended(usize),
+    // the token touched one more value
+    Touched(usize),
+}
+
+// The changes made since the last mark, if the parser is rewindable. It is no part of what the
+// spans say, so it is neither compared nor saved.
+#[derive(Clone, Debug, Default)]
+struct Journal {
+    changes: Option<Vec<Change>>,
+    // the number of nodes at the last mark
+    base: usize,
+}
+
+impl PartialEq for Journal {
+    fn eq(&self, _: &Journal) -> bool {
+        true
+    }
+}
+
+// The spans as they were at a mark of `JsonStreamParser::rewindable`, along with the changes made
+// from there to the next mark.
+#[derive(Clone, Debug)]
+pub(crate) struct SpansMark {
+    nodes: usize,
+    root: Option<usize>,
+    pending: Option<Pending>,
+    cursor: Location,
+    now: Option<Duration>,
+    latest: Option<usize>,
+    pub(crate) changes: Vec<Change>,
+}
+
+// The spans of the values and keys of the document, looked up by JSON Pointer like
+// `JsonStreamParser::completed_value`. Values that are still open have a span without an end.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spans {
+    nodes: Vec<Node>,
+    // the first node, unless it has been dropped under `Recovery::Drop`
+    root: Option<usize>,
+    // the open values, outermost first
+    open: Vec<usize>,
+    pending: Option<Pending>,
+    // how far the current chunk has been located, and where that is
+    consumed: usize,
+    cursor: Location,
+    // when the current chunk was received, if the parser is timed
+    pub(crate) now: Option<Duration>,
+    // the id of the current chunk, if it was given to `add_token`, and of the last one that was
+    pub(crate) token: Option<usize>,
+    pub(crate) latest: Option<usize>,
+    // the values each token wrote into directly, or into the key of
+    touched: HashMap<usize, Vec<usize>>,
+    #[cfg_attr(feature = "checkpoint", serde(skip))]
+    journal: Journal,
+}
+
+impl Spans {
+    // The span of the value at `pointer`.
+    pub fn get(&self, pointer: &str) -> Option<&Span> {
+        self.find(pointer).map(|node| &node.span)
+    }
+
+    // The span of the key of the object member at `pointer`.
+    pub fn key(&self, pointer: &str) -> Option<&Span> {
+        self.find(pointer)?.key.as_ref()
+    }
+
+    // The nodes of the values as `Document` walks them, by index: the root, the span of a value,
+    // the children of a container in document order, and the name of a member.
+    pub(crate) fn root_node(&self) -> Option<usize> {
+        self.root
+    }
+
+    pub(crate) fn node_span(&self, index: usize) -> &Span {
+        &self.nodes[index].span
+    }
+
+    pub(crate) fn children(&self, index: usize) -> &[usize] {
+        &self.nodes[index].children
+    }
+
+    pub(crate) fn name(&self, index: usize) -> Option<&str> {
+        self.nodes[index].name.as_deref()
+    }
+
+    // The key whose value comes next in the object at `index`, which has replaced the member
+    // `member` gives for it in the document.
+    pub(crate) fn pending_key(&self, index: usize) -> Option<&str> {
+        if self.open.last() != Some(&index) {
+            return None;
+        }
+        self.pending.as_ref()?.name.as_deref()
+    }
+
+    // The member of the object at `index` that `find` looks `key` up as, unless the key collects
+    // its values into an array.
+    pub(crate) fn member(&self, index: usize, key: &str) -> Option<usize> {
+        let members = self.nodes[index].members.get(key)?;
+        let last = *members.last()?;
+        if members.len() > 1 && self.nodes[last].collected {
+            None
+        } else {
+            Some(last)
+        }
+    }
+
+    pub(crate) fn timing(&self, pointer: &str) -> Option<Timing> {
+        self.find(pointer).and_then(timing)
+    }
+
+    // The timing of every value that has one, by JSON Pointer in document order.
+    pub(crate) fn timings(&self) -> Vec<(String, Timing)> {
+        self.paths()
+            .into_iter()
+            .filter_map(|(path, index)| Some((path, timing(&self.nodes[index])?)))
+            .collect()
+    }
+
+    pub(crate) fn token_range(&self, pointer: &str) -> Option<RangeInclusive<usize>> {
+        let node = self.find(pointer)?;
+        let last = node.last_token.or(self.latest)?;
+        Some(node.first_token?..=last)
+    }
+
+    pub(crate) fn token_paths(&self, token: usize) -> Vec<String> {
+        let mut touched = match self.touched.get(&token) {
+            Some(touched) => touched.clone(),
+            None => return Vec::new(),
+        };
+        // nodes are added in document order
+        touched.sort_unstable();
+        touched.dedup();
+        touched
+            .into_iter()
+            .filter_map(|index| self.path_of(index))
+            .collect()
+    }
+
+    // The JSON Pointer `find` looks a node up by, unless it has been dropped or is a member
+    // hidden by a later one with the same key.
+    fn path_of(&self, mut index: usize) -> Option<String> {
+        let mut tokens = Vec::new();
+        let mut node = &self.nodes[index];
+        while let Some(parent_index) = node.parent {
+            let parent = &self.nodes[parent_index];
+            if parent.children.get(node.position) != Some(&index) {
+                return None;
+            }
+            match &node.name {
+                None => tokens.push(node.position.to_string()),
+                Some(name) => {
+                    let members = &parent.members[name];
+                    let last = *members.last().unwrap();
+                    if members.len() > 1 && self.nodes[last].collected {
+                        let position = members.iter().position(|&member| member == index)?;
+                        tokens.push(position.to_string());
+                    } else if last != index {
+                        return None;
+                    }
+                    tokens.push(name.clone());
+                }
+            }
+            index = parent_index;
+            node = parent;
+        }
+        if self.root != Some(index) {
+            return None;
+        }
+        let mut path = String::new();
+        for token in tokens.iter().rev() {
+            push_pointer_token(&mut path, token);
+        }
+        Some(path)
+    }
+
+    // The JSON Pointer of every value in the document, in document order. The values of a key
+    // are listed once, at its last member, as `find` looks them up.
+    fn paths(&self) -> Vec<(String, usize)> {
+        let mut paths = Vec::new();
+        // the values left to list, the next one last, so that deep documents need no recursion
+        let mut pending: Vec<(String, usize)> = self
+            .root
+            .map(|root| (String::new(), root))
+            .into_iter()
+            .collect();
+        while let Some((path, index)) = pending.pop() {
+            let node = &self.nodes[index];
+            let first = pending.len();
+            if self.is_array(node) {
+                for (position, &child) in node.children.iter().enumerate() {
+                    pending.push((child_path(&path, &position.to_string()), child));
+                }
+            } else {
+                for &child in &node.children {
+                    let name = self.nodes[child].name.as_deref().unwrap_or_default();
+                    let members = &node.members[name];
+                    let last = *members.last().unwrap();
+                    if child != last {
+                        continue;
+                    }
+                    let path = child_path(&path, name);
+                    if members.len() > 1 && self.nodes[last].collected {
+                        for (position, &member) in members.iter().enumerate() {
+                            pending.push((child_path(&path, &position.to_string()), member));
+                        }
+                    } else {
+                        pending.push((path, child));
+                    }
+                }
+            }
+            pending[first..].reverse();
+            paths.push((path, index));
+        }
+        paths
+    }
+
+    fn find(&self, pointer: &str) -> Option<&Node> {
+        let mut node = &self.nodes[self.root?];
+        let mut tokens = pointer_tokens(pointer)?.into_iter();
+        while let Some(token) = tokens.next() {
+            let child = if self.is_array(node) {
+                node.children.get(token.parse::<usize>().ok()?).copied()
+            } else {
+                let members = node.members.get(&token)?;
+                // the values of a collected key are looked up by their index in its array
+                match members.last() {
+                    Some(&last) if members.len() > 1 && self.nodes[last].collected => {
+                        members.get(tokens.next()?.parse::<usize>().ok()?).copied()
+                    }
+                    last => last.copied(),
+                }
+            };
+            node = &self.nodes[child?];
+        }
+        Some(node)
+    }
+
+    // whether the children of `node` are array elements rather than object members
+    fn is_array(&self, node: &Node) -> bool {
+        node.children
+            .first()
+            .map_or(false, |&child| self.nodes[child].name.is_none())
+    }
+
+    // Moves the cursor to `position` in the chunk being applied, and returns where that is.
+    fn locate(&mut self, chunk: &str, position: usize) -> Location {
+        if position > self.consumed {
+            self.touch();
+        }
+        for c in chunk[self.consumed..position].chars() {
+            self.cursor.byte += c.len_utf8();
+            self.cursor.char += 1;
+            if c == '\n' {
+                self.cursor.line += 1;
+                self.cursor.column = 1;
+            } else {
+                self.cursor.column += 1;
+            }
+        }
+        self.consumed = position;
+        self.cursor
+    }
+
+    // Attributes the input since the last event to the current token.
+    fn touch(&mut self) {
+        let token = match self.token {
+            Some(token) => token,
+            None => return,
+        };
+        match (&mut self.pending, self.open.last()) {
+            (Some(pending), _) => {
+                if pending.tokens.last() != Some(&token) {
+                    pending.tokens.push(token);
+                }
+            }
+            (None, Some(&index)) => {
+                let touched = self.touched.entry(token).or_default();
+                if touched.last() != Some(&index) {
+                    touched.push(index);
+                    self.log(Change::Touched(token));
+                }
+            }
+            (None, None) => {}
+        }
+    }
+
+    pub(crate) fn start_value(&mut self, chunk: &str, position: usize) {
+        let start = self.locate(chunk, position);
+        let (key, name, collected, tokens) = match self.pending.take() {
+            Some(Pending {
+                span,
+                name: Some(name),
+                collected,
+                tokens,
+            }) => (Some(span), Some(name), collected, tokens),
+            _ => (None, None, false, Vec::new()),
+        };
+        let index = self.nodes.len();
+        for &token in &tokens {
+            self.touched.entry(token).or_default().push(index);
+            self.log(Change::Touched(token));
+        }
+        self.nodes.push(Node {
+            span: Span { start, end: None },
+            started: self.now,
+            finished: None,
+            first_token: tokens.first().copied().or(self.token),
+            last_token: None,
+            key,
+            name,
+            collected,
+            children: Vec::new(),
+            parent: self.open.last().copied(),
+            position: self
+                .open
+                .last()
+                .map_or(0, |&parent| self.nodes[parent].children.len()),
+            members: HashMap::new(),
+        });
+        match self.open.last() {
+            Some(&parent) => {
+                if parent < self.journal.base {
+                    self.log(Change::Added(parent));
+                }
+                let name = self.nodes[index].name.clone();
+                let parent = &mut self.nodes[parent];
+                parent.children.push(index);
+                if let Some(name) = name {
+                    parent.members.entry(name).or_default().push(index);
+                }
+            }
+            None => self.root = Some(index),
+        }
+        self.open.push(index);
+    }
+
+    pub(crate) fn end_value(&mut self, chunk: &str, position: usize) {
+        let end = self.locate(chunk, position);
+        if let Some(index) = self.open.pop() {
+            if index < self.journal.base {
+                self.log(Change::Ended(index));
+            }
+            self.nodes[index].span.end = Some(end);
+            self.nodes[index].finished = self.now;
+            self.nodes[index].last_token = self.token;
+        }
+        self.pending = None;
+    }
+
+    pub(crate) fn start_key(&mut self, chunk: &str, position: usize) {
+        let start = self.locate(chunk, position);
+        self.pending = Some(Pending {
+            span: Span { start, end: None },
+            name: None,
+            collected: false,
+            tokens: Vec::new(),
+        });
+    }
+
+    pub(crate) fn end_key(&mut self, chunk: &str, position: usize) {
+        let end = self.locate(chunk, position);
+        if let Some(pending) = &mut self.pending {
+            pending.span.end = Some(end);
+        }
+    }
+
+    // Names the key being read, which the events of a marker value under `Recovery::Replace` do
+    // without one.
+    pub(crate) fn name_key(&mut self, key: &str, collected: bool) {
+        if let Some(pending) = &mut self.pending {
+            pending.name = Some(key.to_string());
+            pending.collected = collected;
+        }
+    }
+
+    // Removes the member or element whose value was malformed, under `Recovery::Drop`.
+    pub(crate) fn discard(&mut self) {
+        if self.pending.take().is_some() {
+            return;
+        }
+        match self.open.last() {
+            Some(&parent) => {
+                let child = self.nodes[parent].children.pop();
+                if let (Some(child), true) = (child, parent < self.journal.base) {
+                    self.log(Change::Removed(parent, child));
+                }
+                let name = child.and_then(|child| self.nodes[child].name.clone());
+                if let Some(name) = name {
+                    let members = &mut self.nodes[parent].members;
+                    let values = members.get_mut(&name).unwrap();
+                    values.pop();
+                    if values.is_empty() {
+                        members.remove(&name);
+                    }
+                }
+            }
+            None => self.root = None,
+        }
+    }
+
+    pub(crate) fn end_chunk(&mut self, chunk: &str) {
+        self.locate(chunk, chunk.len());
+        self.consumed = 0;
+    }
+
+    // Saves the spans for a mark, and logs the changes from here on.
+    pub(crate) fn mark(&mut self) -> SpansMark {
+        self.journal.changes = Some(Vec::new());
+        self.journal.base = self.nodes.len();
+        SpansMark {
+            nodes: self.nodes.len(),
+            root: self.root,
+            pending: self.pending.clone(),
+            cursor: self.cursor,
+            now: self.now,
+            // a mark made for a chunk given to `add_token` comes after it
+            latest: self.token.or(self.latest),
+            changes: Vec::new(),
+        }
+    }
+
+    // The changes logged since the last mark.
+    pub(crate) fn take_changes(&mut self) -> Vec<Change> {
+        self.journal
+            .changes
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    // Puts the spans back as they were at `mark`, once they have been put back to the next mark,
+    // or if there is none, have had the changes since `mark` taken into it.
+    pub(crate) fn rewind(&mut self, mark: &mut SpansMark) {
+        let base = mark.nodes;
+        // the values opened since the mark are above the ones it had
+        while self.open.last().map_or(false, |&index| index >= base) {
+            self.open.pop();
+        }
+        for change in mark.changes.drain(..).rev() {
+            match change {
+                Change::Added(parent) => {
+                    let child = self.nodes[parent].children.pop().unwrap();
+                    if let Some(name) = self.nodes[child].name.clone() {
+                        let members = &mut self.nodes[parent].members;
+                        let values = members.get_mut(&name).unwrap();
+                        values.pop();
+                        if values.is_empty() {
+                            members.remove(&name);
+                        }
+                    }
+                }
+                Change::Removed(parent, child) => {
+                    let name = self.nodes[child].name.clone();
+                    let parent = &mut self.nodes[parent];
+                    parent.children.push(child);
+                    if let Some(name) = name {
+                        parent.members.entry(name).or_default().push(child);
+                    }
+                }
+                Change::Ended(index) => {
+                    let node = &mut self.nodes[index];
+                    node.span.end = None;
+                    node.finished = None;
+                    node.last_token = None;
+                    self.open.push(index);
+                }
+                Change::Touched(token) => {
+                    let touched = self.touched.get_mut(&token).unwrap();
+                    touched.pop();
+                    if touched.is_empty() {
+                        self.touched.remove(&token);
+                    }
+                }
+            }
+        }
+        self.nodes.truncate(base);
+        self.root = mark.root;
+        self.pending = mark.pending.clone();
+        self.consumed = 0;
+        self.cursor = mark.cursor;
+        self.now = mark.now;
+        self.token = None;
+        self.latest = mark.latest;
+    }
+
+    fn log(&mut self, change: Change) {
+        if let Some(changes) = &mut self.journal.changes {
+            changes.push(change);
+        }
+    }
+}
+
+fn child_path(path: &str, token: &str) -> String {
+    let mut path = path.to_string();
+    push_pointer_token(&mut path, token);
+    path
+}
+
+fn timing(node: &Node) -> Option<Timing> {
+    Some(Timing {
+        started: node.started?,
+        finished: node.finished,
+        start: node.span.start.char,
+        end: node.span.end.map(|end| end.char),
+    })
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::partial::Slot;
+use crate::{FrameState, JsonStreamParser, ParseError};
+
+// A condition under which enough of the document has arrived, for `JsonStreamParser::stop_when`.
+#[derive(Clone)]
+pub enum StopWhen {
+    // Every value at these JSON Pointers is complete.
+    Complete(Vec<String>),
+    // The root is an array with at least this many complete elements. `Items(0)` holds as soon
+    // as the root array opens.
+    Items(usize),
+    // The predicate holds for the partial document, as `get_result` has it.
+    Predicate(Arc<dyn Fn(&Value) -> bool + Send + Sync>),
+}
+
+impl StopWhen {
+    pub fn complete<S: Into<String>>(pointers: impl IntoIterator<Item = S>) -> StopWhen {
+        StopWhen::Complete(pointers.into_iter().map(Into::into).collect())
+    }
+
+    pub fn predicate(predicate: impl Fn(&Value) -> bool + Send + Sync + 'static) -> StopWhen {
+        StopWhen::Predicate(Arc::new(predicate))
+    }
+
+    fn holds(&self, parser: &JsonStreamParser) -> bool {
+        match self {
+            StopWhen::Complete(pointers) => pointers
+                .iter()
+                .all(|pointer| parser.completed_value(pointer).is_some()),
+            StopWhen::Items(0) => parser.get_result().is_array(),
+            StopWhen::Items(n) => parser.completed_value(&format!("/{}", n - 1)).is_some(),
+            StopWhen::Predicate(predicate) => predicate(parser.get_result()),
+        }
+    }
+}
+
+impl fmt::Debug for StopWhen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopWhen::Complete(pointers) => f.debug_tuple("Complete").field(pointers).finish(),
+            StopWhen::Items(n) => f.debug_tuple("Items").field(n).finish(),
+            StopWhen::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+// What to do after a chunk has been fed.
+#[must_use]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feed {
+    Continue,
+    // A condition given to `stop_when` holds, and the rest of the input can be cancelled.
+    Stop,
+}
+
+impl JsonStreamParser {
+    // Adds a condition under which `feed` says to stop. Any of the conditions is enough.
+    pub fn stop_when(mut self, condition: StopWhen) -> JsonStreamParser {
+        self.stop_conditions.push(condition);
+        self
+    }
+
+    // Parses a chunk like `add_str`, and says whether enough of the document has arrived.
+    pub fn feed(&mut self, chunk: &str) -> Result<Feed, ParseError> {
+        self.add_str(chunk)?;
+        let stop = self.stop_conditions.iter().any(|c| c.holds(self));
+        Ok(if stop { Feed::Stop } else { Feed::Continue })
+    }
+
+    // Ends parsing where it is, with a value that only holds what has completely arrived: open
+    // containers are closed as they are, and the string or scalar being written and a member
+    // whose value has not started are left out. A root that is not complete becomes null.
+    pub fn finish(self) -> Value {
+        match self.stack[0].state {
+            FrameState::Done => return self.into_result(),
+            FrameState::Ready => return Value::Null,
+            _ => {}
+        }
+        // the keys of the slots down to the incomplete leaf, or none for the last element
+        let path: Vec<Option<String>> = match self.open_slots() {
+            (slots, true) => slots
+                .iter()
+                .map(|slot| match slot {
+                    Slot::Last => None,
+                    Slot::Key(key) => Some(key.to_string()),
+                })
+                .collect(),
+            (_, false) => Vec::new(),
+        };
+        let mut value = self.into_result();
+        if let Some((leaf, path)) = path.split_last() {
+            let mut parent = Some(&mut value);
+            for key in path {
+                parent = parent.and_then(|parent| match (parent, key) {
+                    (Value::Array(items), None) => items.last_mut(),
+                    (Value::Object(map), Some(key)) => map.get_mut(key),
+                    _ => None,
+                });
+            }
+            match (parent, leaf) {
+                (Some(Value::Array(items)), None) => {
+                    items.pop();
+                }
+                // keeps the other members in order with `preserve_order`
+                #[cfg(feature = "preserve_order")]
+                (Some(Value::Object(map)), Some(key)) => {
+                    map.shift_remove(key);
+                }
+                #[cfg(not(feature = "preserve_order"))]
+                (Some(Value::Object(map)), Some(key)) => {
+                    map.remove(key);
+                }
+                _ => {}
+            }
+        }
+        value
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::spans::{Spans, Timing};
+use crate::{JsonStreamParser, ValueBuilder};
+
+// Gives the current time, as a duration since any fixed point.
+pub(crate) type Clock = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // Stamps every value with the time its first and last chars were received, measured from now,
+    // for `timing` and `timings`. Like `record_spans`, which it implies, it is to be called
+    // before any input is given.
+    pub fn timed(self) -> JsonStreamParser<B> {
+        let start = Instant::now();
+        self.timed_with(move || start.elapsed())
+    }
+
+    // Like `timed`, with the time given by `clock`, such as a fake clock in tests.
+    pub fn timed_with(
+        mut self,
+        clock: impl Fn() -> Duration + Send + Sync + 'static,
+    ) -> JsonStreamParser<B> {
+        self.spans.get_or_insert_with(Spans::default);
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    // The timing of the value at `pointer`, if the parser is timed.
+    pub fn timing(&self, pointer: &str) -> Option<Timing> {
+        self.spans.as_ref()?.timing(pointer)
+    }
+
+    // The timing of every value, by JSON Pointer in document order, if the parser is timed.
+    pub fn timings(&self) -> Vec<(String, Timing)> {
+        self.spans.as_ref().map_or_else(Vec::new, Spans::timings)
+    }
+
+    // Reads the clock for a chunk that has been received, or for one that `truncate_to` replays,
+    // gives the time it was first received at.
+    pub(crate) fn stamp(&mut self) {
+        if let Some(spans) = &mut self.spans {
+            let replaying = self.history.as_ref().and_then(|history| history.replaying);
+            spans.now = match replaying {
+                Some(now) => now,
+                None => self.clock.as_ref().map(|clock| clock()),
+            };
+        }
+    }
+}
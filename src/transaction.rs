@@ -0,0 +1,351 @@
+use std::ops::Range;
+
+use crate::recovery::Resync;
+use crate::spans::Spans;
+use crate::{Frame, FrameState, JsonStreamParser, Scalar, Token, ValueBuilder};
+
+// A builder call made while a chunk is lexed. Calls are only replayed on the builder once the
+// whole chunk has been lexed without an error.
+#[derive(Clone, Debug)]
+pub(crate) enum Event {
+    StartObject,
+    StartArray,
+    EndContainer,
+    // the key is in `Events::text`
+    InsertKey(Range<usize>),
+    CollectKey(Range<usize>, bool),
+    PushItem,
+    StartString,
+    // a run of string content in the chunk
+    AppendInput(Range<usize>),
+    // a char decoded from an escape
+    AppendChar(char),
+    SetScalar(Scalar),
+    EndValue,
+    Discard,
+    // replaces the array element that has just ended with null, keeping its span
+    Clear,
+    // where a value or key starts or ends in the chunk, recorded for `JsonStreamParser::spans`
+    ValueStart(usize),
+    ValueEnd(usize),
+    KeyStart(usize),
+    KeyEnd(usize),
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Events {
+    events: Vec<Event>,
+    // the keys of `InsertKey` and `CollectKey`, which are not in the chunk when they are escaped
+    text: String,
+}
+
+impl Events {
+    pub(crate) fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    // Records an `InsertKey`, or a `CollectKey` with `collect` giving whether to wrap.
+    pub(crate) fn push_key(&mut self, key: &str, collect: Option<bool>) {
+        let start = self.text.len();
+        self.text.push_str(key);
+        let range = start..self.text.len();
+        self.events.push(match collect {
+            None => Event::InsertKey(range),
+            Some(wrap) => Event::CollectKey(range, wrap),
+        });
+    }
+
+    // Replays the events of `chunk` on `builder`, and on `spans` if they are recorded.
+    pub(crate) fn apply<B: ValueBuilder>(
+        &mut self,
+        chunk: &str,
+        builder: &mut B,
+        mut spans: Option<&mut Spans>,
+    ) {
+        for event in self.events.drain(..) {
+            if let Some(spans) = &mut spans {
+                match &event {
+                    Event::InsertKey(range) => spans.name_key(&self.text[range.clone()], false),
+                    Event::CollectKey(range, _) => spans.name_key(&self.text[range.clone()], true),
+                    Event::Discard => spans.discard(),
+                    Event::ValueStart(position) => spans.start_value(chunk, *position),
+                    Event::ValueEnd(position) => spans.end_value(chunk, *position),
+                    Event::KeyStart(position) => spans.start_key(chunk, *position),
+                    Event::KeyEnd(position) => spans.end_key(chunk, *position),
+                    _ => {}
+                }
+            }
+            match event {
+                Event::StartObject => builder.start_object(),
+                Event::StartArray => builder.start_array(),
+                Event::EndContainer => builder.end_container(),
+                Event::InsertKey(range) => builder.insert_key(&self.text[range]),
+                Event::CollectKey(range, wrap) => builder.collect_key(&self.text[range], wrap),
+                Event::PushItem => builder.push_item(),
+                Event::StartString => builder.start_string(),
+                Event::AppendInput(range) => builder.append_string(&chunk[range]),
+                Event::AppendChar(c) => builder.append_string(c.encode_utf8(&mut [0; 4])),
+                Event::SetScalar(scalar) => builder.set_scalar(scalar),
+                Event::EndValue => builder.end_value(),
+                Event::Discard => builder.discard(),
+                Event::Clear => {
+                    builder.discard();
+                    builder.push_item();
+                    builder.end_value();
+                }
+                Event::ValueStart(_)
+                | Event::ValueEnd(_)
+                | Event::KeyStart(_)
+                | Event::KeyEnd(_) => {}
+            }
+        }
+        if let Some(spans) = spans {
+            spans.end_chunk(chunk);
+        }
+        self.text.clear();
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+        self.text.clear();
+    }
+}
+
+// The parts of a frame that change while it is the innermost one.
+#[derive(Clone, Debug)]
+struct Head {
+    state: FrameState,
+    object: bool,
+    members: usize,
+    // saved the first time the key changes
+    key: Option<String>,
+}
+
+impl Head {
+    fn of(frame: &Frame) -> Head {
+        Head {
+            state: frame.state,
+            object: frame.object,
+            members: frame.members,
+            key: None,
+        }
+    }
+}
+
+// The lexer state as it was before the current chunk, so that a chunk that fails can be undone.
+// A frame only changes while it is the innermost one, so only the frames from the lowest point the
+// stack has reached during the chunk need to be kept, and the cost of a chunk stays proportional
+// to its length rather than to the depth of the document. The marks of `rewindable` keep one
+// too, which the logs of the chunks after them are absorbed into.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Undo {
+    // the stack length before the chunk
+    start: usize,
+    // The stack length at the lowest point of the chunk. The frames below it are as they were
+    // before the chunk, except for the innermost of them.
+    low: usize,
+    // the frames from before the chunk that it closed, innermost first
+    popped: Vec<Frame>,
+    // the head of every frame from before the chunk that has been the innermost one
+    heads: Vec<(usize, Head)>,
+    // the keys added to `Frame::keys` of frames from before the chunk, and whether the key was
+    // already there and only marked as collected
+    keys: Vec<(usize, String, bool)>,
+    token: Token,
+    number: String,
+    skip: Option<usize>,
+    redacted: Option<usize>,
+    selected: Option<usize>,
+    holding: usize,
+    // the length of the string held back for redaction, and the string itself once the chunk has
+    // ended it
+    held: Option<usize>,
+    held_text: Option<String>,
+    string_length: usize,
+    nodes: usize,
+    memory: usize,
+    resync: Option<Resync>,
+    diagnostics: usize,
+}
+
+impl Undo {
+    // Keeps a held string that has just ended, in case it was held before the chunk.
+    pub(crate) fn keep_held(&mut self, text: String) {
+        if self.held.is_some() && self.held_text.is_none() {
+            self.held_text = Some(text);
+        }
+    }
+
+    // Forgets what the chunk ended once it has been applied, so that a held string does not
+    // outlive it.
+    pub(crate) fn end_chunk(&mut self) {
+        self.held_text = None;
+    }
+
+    // Keeps the whole of a held string, for an undo log that outlives the chunk.
+    pub(crate) fn hold(&mut self, held: Option<&str>) {
+        self.held_text = held.map(str::to_string);
+    }
+
+    // The string held back for redaction when the state was saved.
+    pub(crate) fn held(&self) -> Option<&str> {
+        self.held_text.as_deref()
+    }
+
+    // Forgets the string held back for redaction, once the state can no longer be gone back to.
+    pub(crate) fn forget_held(&mut self) {
+        self.held = None;
+        self.held_text = None;
+    }
+
+    // Adds the frames logged by `chunk`, which began where this log has got to, so that undoing
+    // the log undoes both. Only what changed in the frames this log began with is kept.
+    pub(crate) fn absorb(&mut self, chunk: &mut Undo) {
+        let low = self.low;
+        for (index, frame) in (0..chunk.start).rev().zip(chunk.popped.drain(..)) {
+            if index < low {
+                self.popped.push(frame);
+            }
+        }
+        for (index, head) in chunk.heads.drain(..) {
+            if index + 1 < low {
+                self.heads.push((index, head));
+            } else if index + 1 == low {
+                // the innermost of the frames this log began with, whose head is already saved
+                let saved = &mut self.heads.last_mut().unwrap().1;
+                if saved.key.is_none() {
+                    saved.key = head.key;
+                }
+            }
+        }
+        self.keys
+            .extend(chunk.keys.drain(..).filter(|(index, _, _)| *index < low));
+        self.low = low.min(chunk.low);
+    }
+}
+
+impl<B: ValueBuilder> JsonStreamParser<B> {
+    // Remembers the lexer state before a chunk.
+    pub(crate) fn begin_chunk(&mut self) {
+        let mut undo = std::mem::take(&mut self.undo);
+        self.save_lexer(&mut undo);
+        self.undo = undo;
+        self.events.clear();
+    }
+
+    // Puts the lexer back as it was before the chunk. The builder has not seen the chunk yet.
+    pub(crate) fn undo_chunk(&mut self) {
+        let mut undo = std::mem::take(&mut self.undo);
+        self.restore_lexer(&mut undo);
+        self.undo = undo;
+        self.events.clear();
+    }
+
+    // Saves the lexer state into `undo`, and starts logging the frames from here on.
+    pub(crate) fn save_lexer(&self, undo: &mut Undo) {
+        undo.start = self.stack.len();
+        undo.low = self.stack.len();
+        undo.popped.clear();
+        undo.heads.clear();
+        undo.keys.clear();
+        undo.token = self.token;
+        if self.token == Token::Number {
+            undo.number.clear();
+            undo.number.push_str(&self.number);
+        }
+        undo.skip = self.skip;
+        undo.redacted = self.redacted;
+        undo.selected = self.selected;
+        undo.holding = self.holding;
+        undo.held = self.held.as_ref().map(String::len);
+        undo.held_text = None;
+        undo.string_length = self.string_length;
+        undo.nodes = self.nodes;
+        undo.memory = self.memory;
+        undo.resync = self.resync;
+        undo.diagnostics = self.diagnostics.len();
+        let index = self.stack.len() - 1;
+        undo.heads.push((index, Head::of(&self.stack[index])));
+    }
+
+    // Puts the lexer back as `undo` saved it, undoing the frames it logged.
+    pub(crate) fn restore_lexer(&mut self, undo: &mut Undo) {
+        self.stack.truncate(undo.low);
+        while let Some(frame) = undo.popped.pop() {
+            self.stack.push(frame);
+        }
+        for (index, head) in undo.heads.drain(..) {
+            let frame = &mut self.stack[index];
+            frame.state = head.state;
+            frame.object = head.object;
+            frame.members = head.members;
+            if let Some(key) = head.key {
+                frame.key = key;
+            }
+        }
+        for (index, key, collected) in undo.keys.drain(..).rev() {
+            let keys = &mut self.stack[index].keys;
+            if collected {
+                keys.insert(key, false);
+            } else {
+                keys.remove(&key);
+            }
+        }
+        self.token = undo.token;
+        if undo.token == Token::Number {
+            std::mem::swap(&mut self.number, &mut undo.number);
+        }
+        self.skip = undo.skip;
+        self.redacted = undo.redacted;
+        self.selected = undo.selected;
+        self.holding = undo.holding;
+        let held = undo.held_text.take().or_else(|| self.held.take());
+        self.held = undo.held.map(|len| {
+            let mut text = held.unwrap_or_default();
+            text.truncate(len);
+            text
+        });
+        self.string_length = undo.string_length;
+        self.nodes = undo.nodes;
+        self.memory = undo.memory;
+        self.resync = undo.resync;
+        self.diagnostics.truncate(undo.diagnostics);
+    }
+
+    // Saves the head of the innermost frame, which is from before the chunk.
+    fn save_head(&mut self) {
+        let index = self.stack.len() - 1;
+        self.undo.heads.push((index, Head::of(&self.stack[index])));
+    }
+
+    // Closes the innermost frame.
+    pub(crate) fn pop_frame(&mut self) {
+        let frame = self.stack.pop().unwrap();
+        if self.stack.len() < self.undo.low {
+            self.undo.low = self.stack.len();
+            self.undo.popped.push(frame);
+            self.save_head();
+        }
+    }
+
+    // To be called before the key of the innermost frame changes.
+    pub(crate) fn save_key(&mut self) {
+        let index = self.stack.len() - 1;
+        if index < self.undo.low {
+            let head = &mut self.undo.heads.last_mut().unwrap().1;
+            if head.key.is_none() {
+                head.key = Some(self.stack[index].key.clone());
+            }
+        }
+    }
+
+    // To be called after the key of the innermost frame has been added to its `keys`, or marked
+    // as collected there.
+    pub(crate) fn log_key(&mut self, collected: bool) {
+        let index = self.stack.len() - 1;
+        if index < self.undo.low {
+            let key = self.stack[index].key.clone();
+            self.undo.keys.push((index, key, collected));
+        }
+    }
+}
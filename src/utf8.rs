@@ -0,0 +1,101 @@
+use crate::{ErrorKind, ParseError};
+
+// Decodes a byte stream into `&str` chunks, carrying a UTF-8 sequence that is split across two
+// chunks over to the next one.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "checkpoint", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Utf8Decoder {
+    pending: Vec<u8>,
+    // the bytes decoded so far, including `pending`, for the offsets of errors
+    offset: usize,
+}
+
+impl Utf8Decoder {
+    // A decoder with nothing pending, after `offset` bytes.
+    pub(crate) fn at(offset: usize) -> Utf8Decoder {
+        Utf8Decoder {
+            pending: Vec::new(),
+            offset,
+        }
+    }
+
+    // how many more bytes the sequence carried over from the previous chunk needs, if any
+    pub(crate) fn needed(&self) -> usize {
+        match self.pending.first() {
+            Some(&first) => sequence_length(first) - self.pending.len(),
+            None => 0,
+        }
+    }
+
+    // Invalid UTF-8 is reported before anything in the chunk is output.
+    pub(crate) fn decode<F>(&mut self, mut bytes: &[u8], mut output: F) -> Result<(), ParseError>
+    where
+        F: FnMut(&str) -> Result<(), ParseError>,
+    {
+        // the sequence carried over from the previous chunk, once this one completes it
+        let mut carried = None;
+        if !self.pending.is_empty() {
+            let needed = sequence_length(self.pending[0]) - self.pending.len();
+            let taken = needed.min(bytes.len());
+            self.pending.extend_from_slice(&bytes[..taken]);
+            bytes = &bytes[taken..];
+            self.offset += taken;
+            if taken < needed {
+                return Ok(());
+            }
+            let sequence = std::mem::take(&mut self.pending);
+            let start = self.offset - sequence.len();
+            let sequence = String::from_utf8(sequence);
+            carried = Some(sequence.map_err(|e| invalid_utf8(e.utf8_error(), start))?);
+        }
+
+        let start = self.offset;
+        self.offset += bytes.len();
+        let valid = match std::str::from_utf8(bytes) {
+            Ok(s) => s,
+            // the chunk ends in the middle of a sequence
+            Err(e) if e.error_len().is_none() => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                self.pending.extend_from_slice(rest);
+                // `valid` is known to be well-formed
+                std::str::from_utf8(valid).map_err(|e| invalid_utf8(e, start))?
+            }
+            Err(e) => return Err(invalid_utf8(e, start)),
+        };
+        if let Some(carried) = carried {
+            output(&carried)?;
+            if bytes.is_empty() {
+                return Ok(());
+            }
+        }
+        output(valid)
+    }
+
+    pub(crate) fn finish(&self) -> Result<(), ParseError> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseError::new(
+                ErrorKind::InvalidUtf8("incomplete UTF-8 sequence at end of input".to_string()),
+                self.offset - self.pending.len(),
+            ))
+        }
+    }
+}
+
+// `start` is the offset of the bytes `e` was found in.
+fn invalid_utf8(e: std::str::Utf8Error, start: usize) -> ParseError {
+    ParseError::new(
+        ErrorKind::InvalidUtf8(format!("invalid UTF-8: {e}")),
+        start + e.valid_up_to(),
+    )
+}
+
+fn sequence_length(first_byte: u8) -> usize {
+    match first_byte {
+        0xF0..=0xFF => 4,
+        0xE0..=0xEF => 3,
+        0xC0..=0xDF => 2,
+        _ => 1,
+    }
+}
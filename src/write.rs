@@ -0,0 +1,78 @@
+use std::fmt;
+use std::io;
+
+use crate::{JsonStreamParser, ValueBuilder};
+
+// Feeds bytes into the parser, so that `io::copy` can stream a file or a child process' stdout
+// into it. A UTF-8 sequence split across two writes is completed by the next write. Invalid
+// UTF-8 and parse errors are reported as `io::ErrorKind::InvalidData`, with the parser's error
+// message as the inner error. Like `add_str`, a write with an error leaves the parser untouched.
+impl<B: ValueBuilder> io::Write for JsonStreamParser<B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut utf8 = self.utf8.clone();
+        let result = if utf8.needed() == 0 {
+            utf8.decode(buf, |s| self.add_str(s))
+        } else {
+            // the sequence carried over has to be parsed in the same chunk as what follows it
+            let mut text = String::new();
+            utf8.decode(buf, |s| {
+                text.push_str(s);
+                Ok(())
+            })
+            .and_then(|()| self.add_str(&text))
+        };
+        if result.is_ok() {
+            self.utf8 = utf8;
+        }
+        result
+            .map(|()| buf.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Allows `write!` into the parser. `fmt::Error` carries no details, so the parse error is kept
+// and can be retrieved with `take_error`. Like `extend`, it ignores further input until then.
+impl<B: ValueBuilder> fmt::Write for JsonStreamParser<B> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.deferred_error.is_some() {
+            return Err(fmt::Error);
+        }
+        self.add_str(s).map_err(|e| {
+            self.deferred_error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+// `extend` stops at the first parse error, which can be retrieved with `take_error`.
+impl<B: ValueBuilder> Extend<char> for JsonStreamParser<B> {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        if self.deferred_error.is_some() {
+            return;
+        }
+        for c in iter {
+            if let Err(e) = self.add_char(c) {
+                self.deferred_error = Some(e);
+                return;
+            }
+        }
+    }
+}
+
+impl<'a, B: ValueBuilder> Extend<&'a str> for JsonStreamParser<B> {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        if self.deferred_error.is_some() {
+            return;
+        }
+        for s in iter {
+            if let Err(e) = self.add_str(s) {
+                self.deferred_error = Some(e);
+                return;
+            }
+        }
+    }
+}
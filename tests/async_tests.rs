@@ -0,0 +1,138 @@
+#![cfg(feature = "async")]
+
+use bytes::Bytes;
+use futures::StreamExt;
+use json_stream_parser::{AsyncParseError, ReaderSnapshotStream, SnapshotStream};
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+
+#[tokio::test]
+async fn snapshots_from_byte_stream() {
+    let chunks: Vec<Result<Bytes, std::io::Error>> = vec![
+        Ok(Bytes::from_static(br#"{"key": "va"#)),
+        Ok(Bytes::new()),
+        Ok(Bytes::from_static(br#"lue", "n": 1}"#)),
+    ];
+    let snapshots: Vec<_> = SnapshotStream::new(futures::stream::iter(chunks))
+        .map(|snapshot| snapshot.unwrap())
+        .collect()
+        .await;
+    assert_eq!(
+        snapshots,
+        vec![json!({"key": "va"}), json!({"key": "value", "n": 1})]
+    );
+}
+
+#[tokio::test]
+async fn utf8_split_across_chunks() {
+    let text = r#"["héllo"]"#.as_bytes();
+    let split = text.iter().position(|&b| b == 0xC3).unwrap() + 1;
+    let chunks: Vec<Result<Vec<u8>, std::io::Error>> =
+        vec![Ok(text[..split].to_vec()), Ok(text[split..].to_vec())];
+    let mut stream = SnapshotStream::new(futures::stream::iter(chunks));
+    assert_eq!(stream.next().await.unwrap().unwrap(), json!(["h"]));
+    assert_eq!(stream.next().await.unwrap().unwrap(), json!(["héllo"]));
+    assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn string_chunks_and_errors() {
+    let chunks: Vec<Result<String, String>> = vec![
+        Ok("[1, ".to_string()),
+        Err("connection reset".to_string()),
+        Ok("2]".to_string()),
+    ];
+    let mut stream = SnapshotStream::new(futures::stream::iter(chunks));
+    assert!(stream.next().await.unwrap().is_ok());
+    match stream.next().await.unwrap() {
+        Err(AsyncParseError::Source(e)) => assert_eq!(e, "connection reset"),
+        other => panic!("unexpected {other:?}"),
+    }
+    assert!(stream.next().await.is_none());
+
+    let chunks: Vec<Result<&str, String>> = vec![Ok("{]")];
+    let mut stream = SnapshotStream::new(futures::stream::iter(chunks));
+    assert!(matches!(
+        stream.next().await,
+        Some(Err(AsyncParseError::Parse(_)))
+    ));
+}
+
+#[tokio::test]
+async fn reader_over_duplex() {
+    let (mut writer, reader) = tokio::io::duplex(16);
+    let json = r#"{"items": [1, 2, 3], "text": "ünïcode everywhere"}"#;
+    let producer = tokio::spawn(async move {
+        for chunk in json.as_bytes().chunks(5) {
+            writer.write_all(chunk).await.unwrap();
+        }
+    });
+
+    let mut stream = ReaderSnapshotStream::new(reader);
+    let mut last = None;
+    while let Some(snapshot) = stream.next().await {
+        last = Some(snapshot.unwrap());
+    }
+    producer.await.unwrap();
+    assert_eq!(
+        last.unwrap(),
+        json!({"items": [1, 2, 3], "text": "ünïcode everywhere"})
+    );
+}
+
+#[tokio::test]
+async fn reader_reports_parse_errors_as_invalid_data() {
+    let (mut writer, reader) = tokio::io::duplex(16);
+    writer.write_all(b"[1, {]").await.unwrap();
+    drop(writer);
+    let results: Vec<_> = ReaderSnapshotStream::new(reader).collect().await;
+    let error = results.last().unwrap().as_ref().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn cancelled_poll_loses_no_input() {
+    let (mut writer, reader) = tokio::io::duplex(64);
+    let mut stream = ReaderSnapshotStream::new(reader);
+
+    // nothing has been written yet, so this poll is cancelled while pending
+    let cancelled = tokio::time::timeout(std::time::Duration::from_millis(10), stream.next()).await;
+    assert!(cancelled.is_err());
+
+    writer.write_all(br#"{"a": true}"#).await.unwrap();
+    drop(writer);
+    let mut last = None;
+    while let Some(snapshot) = stream.next().await {
+        last = Some(snapshot.unwrap());
+    }
+    assert_eq!(last.unwrap(), json!({"a": true}));
+}
+
+#[tokio::test]
+async fn invalid_utf8_is_reported_before_the_chunk_is_parsed() {
+    // the first chunk ends inside "é", and the second completes it before an invalid byte
+    let chunks: Vec<Result<Vec<u8>, std::io::Error>> =
+        vec![Ok(b"[\"\xC3".to_vec()), Ok(b"\xA9x\xFF".to_vec())];
+    let mut stream = SnapshotStream::new(futures::stream::iter(chunks));
+    assert_eq!(stream.next().await.unwrap().unwrap(), json!([""]));
+    match stream.next().await.unwrap() {
+        Err(AsyncParseError::Parse(e)) => assert_eq!(e.offset(), 5),
+        other => panic!("unexpected {other:?}"),
+    }
+    assert_eq!(stream.parser().get_result(), &json!([""]));
+}
+
+#[tokio::test]
+async fn stream_errors_are_the_source() {
+    use std::error::Error as _;
+    let chunks: Vec<Result<&str, std::io::Error>> = vec![Err(std::io::Error::new(
+        std::io::ErrorKind::ConnectionReset,
+        "connection reset",
+    ))];
+    let error = SnapshotStream::new(futures::stream::iter(chunks))
+        .next()
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(error.source().unwrap().to_string(), "connection reset");
+}
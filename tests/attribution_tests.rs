@@ -0,0 +1,110 @@
+use json_stream_parser::{DuplicateKeys, JsonStreamParser, Recovery};
+
+const TOKENS: [&str; 6] = [
+    r#"{"na"#,
+    r#"me": "A"#,
+    r#"da", "#,
+    r#""tags": ["#,
+    r#""x"]"#,
+    "}",
+];
+
+fn fed(tokens: &[&str]) -> JsonStreamParser {
+    let mut parser = JsonStreamParser::new().record_spans();
+    for (id, token) in tokens.iter().enumerate() {
+        parser.add_token(token, id).unwrap();
+    }
+    parser
+}
+
+#[test]
+fn values_know_the_tokens_that_wrote_them() {
+    let parser = fed(&TOKENS);
+    assert_eq!(parser.get_result()["name"], "Ada");
+    assert_eq!(parser.token_range(""), Some(0..=5));
+    // the key counts
+    assert_eq!(parser.token_range("/name"), Some(0..=2));
+    assert_eq!(parser.token_range("/tags"), Some(3..=4));
+    assert_eq!(parser.token_range("/tags/0"), Some(4..=4));
+    assert_eq!(parser.token_range("/missing"), None);
+}
+
+#[test]
+fn tokens_know_the_values_they_wrote() {
+    let parser = fed(&TOKENS);
+    let paths: Vec<Vec<String>> = (0..7).map(|id| parser.token_paths(id)).collect();
+    assert_eq!(
+        paths,
+        vec![
+            vec!["", "/name"],
+            vec!["/name"],
+            vec!["", "/name"],
+            vec!["/tags"],
+            vec!["/tags", "/tags/0"],
+            vec![""],
+            vec![],
+        ]
+    );
+}
+
+#[test]
+fn open_values_reach_the_latest_token() {
+    let mut parser = fed(&TOKENS[..4]);
+    assert_eq!(parser.token_range(""), Some(0..=3));
+    assert_eq!(parser.token_range("/tags"), Some(3..=3));
+
+    // a failed token leaves no trace
+    assert!(parser.add_token("x", 4).is_err());
+    assert_eq!(parser.token_range(""), Some(0..=3));
+    assert!(parser.token_paths(4).is_empty());
+
+    // input that is not a token is not attributed
+    parser.add_str("1, ").unwrap();
+    assert_eq!(parser.token_range("/tags/0"), None);
+    parser.add_token("2", 9).unwrap();
+    parser.add_token("]", 10).unwrap();
+    assert_eq!(parser.token_range("/tags"), Some(3..=10));
+    assert_eq!(parser.token_range("/tags/1"), Some(9..=10));
+}
+
+#[test]
+fn tokens_are_not_kept_without_spans() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_token("[1]", 0).unwrap();
+    assert_eq!(parser.get_result(), &serde_json::json!([1]));
+    assert_eq!(parser.token_range(""), None);
+    assert!(parser.token_paths(0).is_empty());
+}
+
+#[test]
+fn hidden_and_dropped_values_have_no_path() {
+    let mut parser = JsonStreamParser::new().record_spans();
+    for (id, token) in [r#"{"a": 1, "#, r#""a": [2], "b": "#, "3}"]
+        .iter()
+        .enumerate()
+    {
+        parser.add_token(token, id).unwrap();
+    }
+    // the first `a` is hidden by the second
+    assert_eq!(parser.token_paths(0), vec![""]);
+    assert_eq!(parser.token_paths(1), vec!["", "/a", "/a/0", "/b"]);
+
+    let mut parser = JsonStreamParser::new()
+        .duplicate_keys(DuplicateKeys::Collect)
+        .record_spans();
+    for (id, token) in [r#"{"a": 1, "#, r#""a": [2]}"#].iter().enumerate() {
+        parser.add_token(token, id).unwrap();
+    }
+    assert_eq!(parser.token_paths(0), vec!["", "/a/0"]);
+    assert_eq!(parser.token_paths(1), vec!["", "/a/1", "/a/1/0"]);
+
+    let mut parser = JsonStreamParser::new()
+        .recovery(Recovery::Drop)
+        .record_spans();
+    for (id, token) in [r#"[1, tr"#, r#"x, 2]"#].iter().enumerate() {
+        parser.add_token(token, id).unwrap();
+    }
+    assert_eq!(parser.get_result(), &serde_json::json!([1, 2]));
+    assert_eq!(parser.token_paths(0), vec!["", "/0"]);
+    assert_eq!(parser.token_paths(1), vec!["", "/1"]);
+}
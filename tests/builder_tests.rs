@@ -0,0 +1,129 @@
+use json_stream_parser::{JsonStreamParser, Scalar, ValueBuilder};
+use serde_json::{json, Value};
+
+// Builds a flat list of `(path, value)` pairs for every string and scalar, instead of a tree.
+#[derive(Default)]
+struct Leaves {
+    // the key of the current member, or the index of the current element, of each open container
+    path: Vec<String>,
+    // the next index of each open container, or None for objects
+    indices: Vec<Option<usize>>,
+    string: String,
+    scalar: Option<Scalar>,
+    leaves: Vec<(String, Value)>,
+}
+
+impl ValueBuilder for Leaves {
+    type Output = Vec<(String, Value)>;
+
+    fn start_object(&mut self) {
+        self.indices.push(None);
+        self.path.push(String::new());
+    }
+
+    fn start_array(&mut self) {
+        self.indices.push(Some(0));
+        self.path.push(String::new());
+    }
+
+    fn end_container(&mut self) {
+        self.indices.pop();
+        self.path.pop();
+    }
+
+    fn insert_key(&mut self, key: &str) {
+        *self.path.last_mut().unwrap() = key.to_string();
+    }
+
+    fn push_item(&mut self) {
+        let index = self.indices.last_mut().unwrap().as_mut().unwrap();
+        *self.path.last_mut().unwrap() = index.to_string();
+        *index += 1;
+    }
+
+    fn start_string(&mut self) {
+        self.string.clear();
+        self.scalar = None;
+    }
+
+    fn append_string(&mut self, s: &str) {
+        self.string.push_str(s);
+    }
+
+    fn set_scalar(&mut self, scalar: Scalar) {
+        self.scalar = Some(scalar);
+    }
+
+    fn end_value(&mut self) {
+        let value = match self.scalar.take() {
+            Some(scalar) => scalar.into(),
+            None => Value::String(std::mem::take(&mut self.string)),
+        };
+        let path = self.path.iter().map(|key| format!("/{key}")).collect();
+        self.leaves.push((path, value));
+    }
+
+    fn into_output(self) -> Vec<(String, Value)> {
+        self.leaves
+    }
+}
+
+#[test]
+fn custom_builder_receives_every_leaf() {
+    let json = r#"{"name": "Zoë", "tags": ["a", 1, {"ok": true}], "n": null, "e": [], "x": -2.5}"#;
+    let expected = vec![
+        ("/name".to_string(), json!("Zoë")),
+        ("/tags/0".to_string(), json!("a")),
+        ("/tags/1".to_string(), json!(1)),
+        ("/tags/2/ok".to_string(), json!(true)),
+        ("/n".to_string(), json!(null)),
+        ("/x".to_string(), json!(-2.5)),
+    ];
+
+    let mut parser = JsonStreamParser::with_builder(Leaves::default());
+    parser.add_str(json).unwrap();
+    assert!(parser.is_complete());
+    assert_eq!(parser.into_output(), expected);
+
+    let mut parser = JsonStreamParser::with_builder(Leaves::default());
+    for c in json.chars() {
+        parser.add_char(c).unwrap();
+    }
+    assert_eq!(parser.into_output(), expected);
+}
+
+#[test]
+fn limits_apply_to_custom_builders() {
+    let mut parser = JsonStreamParser::with_builder_and_limits(Leaves::default(), Some(1), None);
+    assert!(parser.add_str("[[1]]").is_err());
+
+    let mut parser = JsonStreamParser::with_builder(Leaves::default());
+    assert!(parser.add_str("[1 2]").is_err());
+    // a chunk with an error is not applied at all
+    assert!(parser.builder().leaves.is_empty());
+    parser.add_str("[1, 2]").unwrap();
+    assert_eq!(
+        parser.builder().leaves,
+        vec![("/0".to_string(), json!(1)), ("/1".to_string(), json!(2))]
+    );
+}
+
+#[cfg(feature = "shared")]
+#[test]
+fn shared_builder_matches_the_default_builder() {
+    use json_stream_parser::{parse_stream, SharedValue, SharedValueBuilder};
+
+    let json = r#"{"title": "Zoë", "items": [{"id": 1, "tags": ["a", "b"]}, {"ok": true}], "x": [[], {}]}"#;
+    let mut parser = JsonStreamParser::with_builder(SharedValueBuilder::new());
+    let mut reference = JsonStreamParser::new();
+    for c in json.chars() {
+        parser.add_char(c).unwrap();
+        reference.add_char(c).unwrap();
+        assert_eq!(
+            &parser.builder().snapshot().to_value(),
+            reference.get_result()
+        );
+    }
+    let output: SharedValue = parser.into_output();
+    assert_eq!(output.to_value(), parse_stream(json).unwrap());
+}
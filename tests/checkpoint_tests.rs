@@ -0,0 +1,279 @@
+#![cfg(feature = "checkpoint")]
+
+use std::io::Write as _;
+
+use json_stream_parser::{
+    DuplicateKeys, JsonStreamParser, Limits, ParseError, Recovery, Redaction, SerdeValueBuilder,
+    Spans, CHECKPOINT_VERSION,
+};
+use serde_json::{json, Value};
+
+fn through_json(parser: &JsonStreamParser) -> JsonStreamParser {
+    serde_json::from_str(&serde_json::to_string(parser).unwrap()).unwrap()
+}
+
+fn through_cbor(parser: &JsonStreamParser) -> JsonStreamParser {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(parser, &mut bytes).unwrap();
+    ciborium::from_reader(bytes.as_slice()).unwrap()
+}
+
+type Step = (
+    Result<(), ParseError>,
+    Value,
+    Vec<ParseError>,
+    bool,
+    Option<Spans>,
+);
+
+// Feeds `json` char by char, saving and restoring the parser with `restore` before char `at`,
+// and returns the outcome of every char.
+fn run(
+    parser: &dyn Fn() -> JsonStreamParser,
+    json: &str,
+    at: Option<usize>,
+    restore: fn(&JsonStreamParser) -> JsonStreamParser,
+) -> Vec<Step> {
+    let mut parser = parser();
+    let mut steps = Vec::new();
+    for (index, c) in json.chars().enumerate() {
+        if at == Some(index) {
+            let restored = restore(&parser);
+            assert_eq!(restored.get_result(), parser.get_result());
+            parser = restored;
+        }
+        let result = parser.add_char(c);
+        steps.push((
+            result,
+            parser.get_result().clone(),
+            parser.diagnostics().to_vec(),
+            parser.is_complete(),
+            parser.spans().cloned(),
+        ));
+    }
+    steps
+}
+
+// Restoring a checkpoint taken before any char of `json` must give the same results as never
+// stopping.
+fn check(parser: &dyn Fn() -> JsonStreamParser, json: &str) {
+    let expected = run(parser, json, None, through_json);
+    for at in 0..=json.chars().count() {
+        assert_eq!(
+            run(parser, json, Some(at), through_json),
+            expected,
+            "{json} at {at}"
+        );
+        assert_eq!(
+            run(parser, json, Some(at), through_cbor),
+            expected,
+            "{json} at {at}"
+        );
+    }
+}
+
+#[test]
+fn restored_parsers_go_on_where_they_stopped() {
+    let documents = [
+        r#"{"title": "Zoë \"quoted\" é🦀", "items": [{"id": 1, "tags": ["a", []]}, {"id": -2.5e3, "ok": true}], "rest": null}"#,
+        "-12.5e+3 ",
+        "fals",
+        r#"[{"a": {"b": [[1], {"c\n": "d"}]}}, 2]"#,
+    ];
+    for json in documents {
+        check(&JsonStreamParser::new, json);
+    }
+}
+
+#[test]
+fn settings_and_counters_are_restored() {
+    check(
+        &|| JsonStreamParser::new().duplicate_keys(DuplicateKeys::Collect),
+        r#"{"a": 1, "b": {"a": 2, "a": 3}, "a": [4], "a": 5}"#,
+    );
+    check(
+        &|| JsonStreamParser::new().duplicate_keys(DuplicateKeys::FirstWins),
+        r#"{"a": 1, "a": {"x": [2]}, "b": 3}"#,
+    );
+    check(
+        &|| {
+            JsonStreamParser::new()
+                .recovery(Recovery::Replace(json!("<invalid>")))
+                .record_spans()
+        },
+        r#"{"a": tru x, "b": "c\q", "d": [1 2], "e": 3} 4"#,
+    );
+    // the limits are hit at the same char after a restore
+    check(
+        &|| {
+            JsonStreamParser::new().limits(Limits {
+                max_length: Some(30),
+                max_nodes: Some(5),
+                max_memory: Some(200),
+                ..Limits::default()
+            })
+        },
+        r#"[1, "two", [3], {"four": 4}, 5, 6]"#,
+    );
+}
+
+#[test]
+fn split_utf8_sequences_are_restored() {
+    let json = r#"["é🦀"]"#.as_bytes();
+    for at in 0..json.len() {
+        let mut parser = JsonStreamParser::new();
+        parser.write_all(&json[..at]).unwrap();
+        let mut parser = through_cbor(&parser);
+        parser.write_all(&json[at..]).unwrap();
+        assert_eq!(parser.get_result(), &json!(["é🦀"]));
+    }
+}
+
+#[cfg(feature = "shared")]
+#[test]
+fn shared_snapshots_are_rebuilt() {
+    let mut parser = JsonStreamParser::new().shared_snapshots();
+    parser.add_str(r#"{"a": [1, {"b": "c"#).unwrap();
+    let mut parser = through_json(&parser);
+    assert_eq!(parser.snapshot().to_value(), json!({"a": [1, {"b": "c"}]}));
+    parser.add_str(r#"d"}], "e": 2}"#).unwrap();
+    assert_eq!(
+        parser.snapshot().to_value(),
+        json!({"a": [1, {"b": "cd"}], "e": 2})
+    );
+}
+
+#[test]
+fn checkpoints_from_other_versions_are_rejected() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str("[1, 2").unwrap();
+    let mut checkpoint = serde_json::to_value(&parser).unwrap();
+    assert_eq!(checkpoint[0], json!(CHECKPOINT_VERSION));
+
+    checkpoint[0] = json!(CHECKPOINT_VERSION + 1);
+    match serde_json::from_value::<JsonStreamParser>(checkpoint) {
+        Err(error) => assert!(error.to_string().contains("unsupported checkpoint version")),
+        Ok(_) => panic!("restored a checkpoint from another version"),
+    }
+}
+
+// A checkpoint of `{"a": [1, "b` in the current format. If this test fails, the format has
+// changed, and `CHECKPOINT_VERSION` must change along with the checkpoint here.
+const PINNED: &str = concat!(
+    r#"[1,{"builder":{"root":{"a":[1,"b"]},"containers":[{"value":null,"key":"a","collected":false},"#,
+    r#"{"value":null,"key":"","collected":false}],"detached":false,"open":true,"shared":false},"#,
+    r#""stack":[{"state":"Nested","object":true,"members":1,"key":"a","keys":{}},"#,
+    r#"{"state":"ExpectValue","object":false,"members":2,"key":"","keys":{}}],"#,
+    r#""token":{"String":"None"},"number":"1","duplicate_keys":"LastWins","skip":null,"#,
+    r#""redaction":null,"redacted":null,"projection":null,"selected":null,"holding":0,"#,
+    r#""limits":{"max_depth":null,"max_length":null,"max_string_length":null,"max_key_length":null,"#,
+    r#""max_number_length":null,"max_array_length":null,"max_object_members":null,"max_nodes":null,"#,
+    r#""max_memory":null},"offset":12,"processed_chars":12,"string_length":1,"nodes":4,"#,
+    r#""memory":154,"utf8":{"pending":[],"offset":0},"deferred_error":null,"recovery":null,"#,
+    r#""resync":null,"diagnostics":[],"spans":null}]"#,
+);
+
+#[test]
+fn the_checkpoint_format_is_pinned_to_its_version() {
+    assert_eq!(CHECKPOINT_VERSION, 1);
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": [1, "b"#).unwrap();
+    let mut checkpoint = serde_json::to_value(&parser).unwrap();
+    let pinned: Value = serde_json::from_str(PINNED).unwrap();
+    // the estimate of the memory used depends on the features enabled
+    checkpoint[1]["memory"] = pinned[1]["memory"].clone();
+    assert_eq!(checkpoint, pinned);
+
+    let mut restored: JsonStreamParser = serde_json::from_str(PINNED).unwrap();
+    restored.add_str(r#"c"]}"#).unwrap();
+    assert_eq!(restored.get_result(), &json!({"a": [1, "bc"]}));
+}
+
+#[test]
+fn checkpoints_whose_frames_do_not_match_are_rejected() {
+    let mut outer = JsonStreamParser::new();
+    outer.add_str("[").unwrap();
+    let mut inner = JsonStreamParser::new();
+    inner.add_str(r#"[1,{"a":["#).unwrap();
+    let outer = serde_json::to_value(&outer).unwrap();
+    let inner = serde_json::to_value(&inner).unwrap();
+
+    // the builder of one parser with the frames of the other
+    let mut checkpoint = outer.clone();
+    checkpoint[1]["stack"] = inner[1]["stack"].clone();
+    match serde_json::from_value::<JsonStreamParser>(checkpoint) {
+        Err(error) => assert!(error.to_string().contains("open containers")),
+        Ok(_) => panic!("restored a builder with the frames of another parser"),
+    }
+
+    for field in ["skip", "redacted", "selected"] {
+        let mut checkpoint = inner.clone();
+        checkpoint[1][field] = json!(3);
+        assert!(serde_json::from_value::<JsonStreamParser>(checkpoint).is_err());
+    }
+    let mut checkpoint = inner.clone();
+    checkpoint[1]["holding"] = json!(4);
+    assert!(serde_json::from_value::<JsonStreamParser>(checkpoint).is_err());
+
+    // in a skipped value, the containers are not built
+    let mut parser = JsonStreamParser::new().duplicate_keys(DuplicateKeys::FirstWins);
+    parser.add_str(r#"{"a": 1, "a": [[{"#).unwrap();
+    through_json(&parser).add_str("}]], \"b\": 2}").unwrap();
+}
+
+#[test]
+fn redacted_text_is_not_saved() {
+    let redaction = Redaction::new().path("/pin").path("/secret");
+    let mut parser = JsonStreamParser::new().redact(redaction);
+    parser
+        .add_str(r#"{"pin": 4096, "secret": {"hunter_key": "hunter"#)
+        .unwrap();
+    let checkpoint = serde_json::to_string(&parser).unwrap();
+    assert!(!checkpoint.contains("hunter") && !checkpoint.contains("4096"));
+    // the redaction is saved with it
+    let mut restored = through_json(&parser);
+    restored.add_str(r#"2"}, "pin": 7}"#).unwrap();
+    assert_eq!(
+        restored.get_result(),
+        &json!({"pin": "[REDACTED]", "secret": "[REDACTED]"})
+    );
+
+    let redaction = Redaction::new().matching(|s| s.contains('@'));
+    let mut parser = JsonStreamParser::new().redact(redaction.clone());
+    parser.add_str(r#"{"to": "ada@"#).unwrap();
+    assert!(serde_json::to_string(&parser).is_err());
+    parser.add_str(r#"example.com", "cc": "#).unwrap();
+    let checkpoint = serde_json::to_string(&parser).unwrap();
+    assert!(!checkpoint.contains("example"));
+
+    // a matcher is not saved, so it has to be given again
+    match serde_json::from_str::<JsonStreamParser>(&checkpoint) {
+        Err(error) => assert!(error.to_string().contains("restore_with"), "{error}"),
+        Ok(_) => panic!("restored a matcher that was not given"),
+    }
+    let restore = |redaction| {
+        let mut deserializer = serde_json::Deserializer::from_str(&checkpoint);
+        JsonStreamParser::<SerdeValueBuilder>::restore_with(&mut deserializer, redaction)
+    };
+    assert!(restore(Redaction::new().path("/cc")).is_err());
+    let mut restored = restore(redaction).unwrap();
+    restored.add_str(r#""bob@example.com"}"#).unwrap();
+    assert_eq!(
+        restored.get_result(),
+        &json!({"to": "[REDACTED]", "cc": "[REDACTED]"})
+    );
+}
+
+#[test]
+fn projections_are_restored() {
+    let mut parser = JsonStreamParser::new().project(["/items/*/id"]);
+    parser
+        .add_str(r#"{"items": [{"id": 1, "tags": ["a"#)
+        .unwrap();
+    let mut restored = through_cbor(&parser);
+    restored.add_str(r#""]}, {"id": 2}], "n": 3}"#).unwrap();
+    assert_eq!(
+        restored.get_result(),
+        &json!({"items": [{"id": 1}, {"id": 2}]})
+    );
+}
@@ -0,0 +1,140 @@
+#![cfg(feature = "codec")]
+
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use json_stream_parser::{CodecError, JsonCodec};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio_util::codec::{Decoder, FramedRead, FramedWrite};
+
+#[test]
+fn frames_back_to_back_values() {
+    let mut codec = JsonCodec::<Value>::new();
+    let mut buf = BytesMut::from(&br#"{"a": 1}[true, null]"str" 12 {"#[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!({"a": 1})));
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!([true, null])));
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!("str")));
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!(12)));
+    // the open brace is consumed and kept as partial state
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert!(buf.is_empty());
+    buf.extend_from_slice(br#""b": 2}"#);
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!({"b": 2})));
+}
+
+#[test]
+fn keeps_partial_state_between_calls() {
+    let mut codec = JsonCodec::<Value>::new();
+    let input = r#"{"text": "héllo", "list": [1, 2]}"#.as_bytes();
+    let mut buf = BytesMut::new();
+    let mut frames = vec![];
+    for byte in input {
+        buf.extend_from_slice(&[*byte]);
+        if let Some(frame) = codec.decode(&mut buf).unwrap() {
+            frames.push(frame);
+        }
+    }
+    assert_eq!(frames, vec![json!({"text": "héllo", "list": [1, 2]})]);
+}
+
+#[test]
+fn trailing_number_and_truncated_value_at_eof() {
+    let mut codec = JsonCodec::<Value>::new();
+    let mut buf = BytesMut::from(&b"42"[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), None);
+    assert_eq!(codec.decode_eof(&mut buf).unwrap(), Some(json!(42)));
+    assert_eq!(codec.decode_eof(&mut buf).unwrap(), None);
+
+    let mut buf = BytesMut::from(&br#"{"a": "#[..]);
+    assert!(matches!(
+        codec.decode_eof(&mut buf),
+        Err(CodecError::Parse(_))
+    ));
+}
+
+#[test]
+fn root_numbers_are_checked_when_they_end() {
+    for input in ["- ", "1. ", "1e ", "-[", "1e+ "] {
+        let mut codec = JsonCodec::<Value>::new();
+        let mut buf = BytesMut::from(input.as_bytes());
+        assert!(
+            matches!(codec.decode(&mut buf), Err(CodecError::Parse(_))),
+            "{input:?}"
+        );
+        // the codec starts over with the next value
+        let mut buf = BytesMut::from(&b"[1]"[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!([1])));
+    }
+    for input in ["-", "1.", "1e"] {
+        let mut codec = JsonCodec::<Value>::new();
+        let mut buf = BytesMut::from(input.as_bytes());
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        let error = codec.decode_eof(&mut buf).unwrap_err();
+        assert!(error.to_string().contains("Invalid number"), "{error}");
+    }
+    let mut codec = JsonCodec::<Value>::new();
+    let mut buf = BytesMut::from(&b"-1.5e3 "[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!(-1500.0)));
+}
+
+#[test]
+fn limits_apply_to_each_frame() {
+    let mut codec = JsonCodec::<Value>::with_limits(Some(2), Some(8));
+    let mut buf = BytesMut::from(&b"[[1]] [1, 2] [1, 2, 3, 4]"[..]);
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!([[1]])));
+    assert_eq!(codec.decode(&mut buf).unwrap(), Some(json!([1, 2])));
+    assert!(codec.decode(&mut buf).is_err());
+
+    let mut codec = JsonCodec::<Value>::with_limits(Some(2), None);
+    let mut buf = BytesMut::from(&b"[[[1]]]"[..]);
+    assert!(codec.decode(&mut buf).is_err());
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+struct Message {
+    id: u32,
+    body: String,
+}
+
+#[tokio::test]
+async fn typed_frames_over_duplex() {
+    let (writer, reader) = tokio::io::duplex(8);
+    let messages = vec![
+        Message {
+            id: 1,
+            body: "first".to_string(),
+        },
+        Message {
+            id: 2,
+            body: "second".to_string(),
+        },
+    ];
+
+    let mut sink = FramedWrite::new(writer, JsonCodec::<Message>::new());
+    let sent = messages.clone();
+    let producer = tokio::spawn(async move {
+        for message in sent {
+            sink.send(message).await.unwrap();
+        }
+    });
+
+    let frames: Vec<Message> = FramedRead::new(reader, JsonCodec::<Message>::new())
+        .map(|frame| frame.unwrap())
+        .collect()
+        .await;
+    producer.await.unwrap();
+    assert_eq!(frames, messages);
+}
+
+#[tokio::test]
+async fn invalid_input_is_reported() {
+    let (mut writer, reader) = tokio::io::duplex(64);
+    writer.write_all(br#"{"a": 1} {]"#).await.unwrap();
+    drop(writer);
+    let frames: Vec<_> = FramedRead::new(reader, JsonCodec::<Value>::new())
+        .collect()
+        .await;
+    assert_eq!(frames[0].as_ref().unwrap(), &json!({"a": 1}));
+    assert!(matches!(frames[1], Err(CodecError::Parse(_))));
+}
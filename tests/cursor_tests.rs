@@ -0,0 +1,92 @@
+use json_stream_parser::{JsonStreamParser, ParseState, Recovery};
+use serde_json::json;
+
+use ParseState::*;
+
+// The state and path after every char of `json`.
+fn trace(mut parser: JsonStreamParser, json: &str) -> Vec<(ParseState, String)> {
+    json.chars()
+        .map(|c| {
+            parser.add_char(c).unwrap();
+            (parser.state(), parser.current_path())
+        })
+        .collect()
+}
+
+#[test]
+fn the_cursor_follows_the_document() {
+    let json = r#"{"k": [10, {"s": "v"}], "t": true}"#;
+    let expected = [
+        (ExpectingKey, ""),
+        (InKey, "/"),
+        (InKey, "/k"),
+        (ExpectingValue, "/k"),
+        (ExpectingValue, "/k"),
+        (ExpectingValue, "/k"),
+        (ExpectingValue, "/k"),
+        (InNumber, "/k/0"),
+        (InNumber, "/k/0"),
+        (ExpectingValue, "/k"),
+        (ExpectingValue, "/k"),
+        (ExpectingKey, "/k/1"),
+        (InKey, "/k/1/"),
+        (InKey, "/k/1/s"),
+        (ExpectingValue, "/k/1/s"),
+        (ExpectingValue, "/k/1/s"),
+        (ExpectingValue, "/k/1/s"),
+        (InString, "/k/1/s"),
+        (InString, "/k/1/s"),
+        (AfterValue, "/k/1"),
+        (AfterValue, "/k"),
+        (AfterValue, ""),
+        (ExpectingKey, ""),
+        (ExpectingKey, ""),
+        (InKey, "/"),
+        (InKey, "/t"),
+        (ExpectingValue, "/t"),
+        (ExpectingValue, "/t"),
+        (ExpectingValue, "/t"),
+        (InLiteral, "/t"),
+        (InLiteral, "/t"),
+        (InLiteral, "/t"),
+        (AfterValue, ""),
+        (Closed, ""),
+    ];
+    let expected: Vec<(ParseState, String)> = expected
+        .iter()
+        .map(|(state, path)| (*state, path.to_string()))
+        .collect();
+    assert_eq!(trace(JsonStreamParser::new(), json), expected);
+}
+
+#[test]
+fn root_values_and_escaped_keys() {
+    let parser = JsonStreamParser::new();
+    assert_eq!(parser.state(), ExpectingValue);
+    assert_eq!(parser.current_path(), "");
+
+    let steps = trace(JsonStreamParser::new(), "12 ");
+    assert_eq!(steps[1], (InNumber, String::new()));
+    assert_eq!(steps[2], (Closed, String::new()));
+
+    let steps = trace(JsonStreamParser::new(), r#"{"a/b~": ["#);
+    assert_eq!(
+        steps.last().unwrap(),
+        &(ExpectingValue, "/a~1b~0".to_string())
+    );
+}
+
+#[test]
+fn recovered_values_are_left_behind() {
+    let mut parser = JsonStreamParser::new().recovery(Recovery::Replace(json!(null)));
+    parser.add_str(r#"{"a": [1, x"#).unwrap();
+    assert_eq!(parser.state(), AfterValue);
+    assert_eq!(parser.current_path(), "/a");
+
+    // a failed chunk leaves the cursor where it was
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": "b"#).unwrap();
+    assert!(parser.add_str("\", 1").is_err());
+    assert_eq!(parser.state(), InString);
+    assert_eq!(parser.current_path(), "/a");
+}
@@ -0,0 +1,202 @@
+use json_stream_parser::{
+    Dispatch, DispatchState, ErrorKind, JsonStreamParser, PartialTarget, Typed,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+struct Search {
+    query: String,
+    limit: u32,
+}
+
+fn feed<T: PartialTarget>(json: &str, dispatch: &mut Dispatch<T>) -> Vec<(usize, DispatchState)> {
+    let mut parser = JsonStreamParser::new();
+    let mut states = vec![];
+    for (i, c) in json.chars().enumerate() {
+        parser.add_char(c).unwrap();
+        states.push((i, dispatch.update(&parser).unwrap()));
+    }
+    states
+}
+
+#[test]
+fn fires_once_when_tag_closes() {
+    let json = r#"{"type": "search", "query": "rust"}"#;
+    let mut dispatch = Dispatch::new("/type")
+        .variant("search", Typed::<Search>::new())
+        .variant("fetch", Typed::<Search>::new());
+    let states = feed(json, &mut dispatch);
+
+    let selected: Vec<usize> = states
+        .iter()
+        .filter(|(_, state)| *state == DispatchState::Selected)
+        .map(|(i, _)| *i)
+        .collect();
+    assert_eq!(
+        selected,
+        vec![json.find("search").unwrap() + "search".len()]
+    );
+    assert_eq!(dispatch.tag(), Some("search"));
+    assert_eq!(
+        dispatch.into_target().unwrap().into_inner(),
+        Some(Search {
+            query: "rust".to_string(),
+            limit: 0
+        })
+    );
+}
+
+#[test]
+fn does_not_fire_on_partial_tag() {
+    let mut dispatch = Dispatch::new("/type").variant("search", Typed::<Search>::new());
+    let states = feed(r#"{"type": "sea"#, &mut dispatch);
+    assert!(states
+        .iter()
+        .all(|(_, state)| *state == DispatchState::Pending));
+    assert!(dispatch.target().is_none());
+}
+
+#[test]
+fn tag_after_other_fields() {
+    let json = r#"{"limit": 3, "query": "x", "type": "search"}"#;
+    let mut dispatch = Dispatch::new("/type").variant("search", Typed::<Search>::new());
+    feed(json, &mut dispatch);
+    assert_eq!(
+        dispatch.target().unwrap().get(),
+        Some(&Search {
+            query: "x".to_string(),
+            limit: 3
+        })
+    );
+}
+
+#[test]
+fn nested_tag_and_closure_targets() {
+    let json = r#"{"call": {"tool": "calc", "args": {"x": 1}}}"#;
+    let mut seen = vec![];
+    {
+        let mut dispatch: Dispatch<Box<dyn PartialTarget + '_>> = Dispatch::new("/call/tool")
+            .variant(
+                "calc",
+                Box::new(|partial: &Value| {
+                    seen.push(partial.clone());
+                    Ok(())
+                }),
+            );
+        feed(json, &mut dispatch);
+        assert_eq!(dispatch.tag(), Some("calc"));
+    }
+    assert_eq!(
+        seen.last(),
+        Some(&json!({"call": {"tool": "calc", "args": {"x": 1}}}))
+    );
+}
+
+#[test]
+fn typed_target_at_pointer() {
+    let json = r#"{"tool": "search", "args": {"query": "q", "limit": 5}}"#;
+    let mut dispatch = Dispatch::new("/tool").variant("search", Typed::<Search>::at("/args"));
+    feed(json, &mut dispatch);
+    assert_eq!(
+        dispatch.target().unwrap().get(),
+        Some(&Search {
+            query: "q".to_string(),
+            limit: 5
+        })
+    );
+}
+
+// Counts how many times it is deserialized.
+#[derive(Debug)]
+struct Counted;
+
+static DESERIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+impl<'de> Deserialize<'de> for Counted {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Counted, D::Error> {
+        DESERIALIZED.fetch_add(1, Ordering::SeqCst);
+        Value::deserialize(deserializer).map(|_| Counted)
+    }
+}
+
+#[test]
+fn typed_target_skips_unchanged_values() {
+    let json = r#"{"tool": "search", "args": {"q": 1}, "rest": "abcdefghij"}"#;
+    let mut parser = JsonStreamParser::new();
+    let mut dispatch = Dispatch::new("/tool").variant("search", Typed::<Counted>::at("/args"));
+    for c in json.chars() {
+        parser.add_char(c).unwrap();
+        dispatch.update(&parser).unwrap();
+        // nothing new was parsed
+        dispatch.update(&parser).unwrap();
+    }
+    // once per char from the `"args"` key closing to its value closing, and none after
+    let updates = json.find('}').unwrap() + 1 - (json.find("args").unwrap() + "args".len());
+    assert_eq!(DESERIALIZED.load(Ordering::SeqCst), updates);
+}
+
+#[test]
+fn unknown_tag_uses_fallback_or_errors() {
+    let json = r#"{"type": "other"}"#;
+    let mut dispatch = Dispatch::new("/type")
+        .variant("search", Typed::<Value>::new())
+        .fallback(Typed::<Value>::new());
+    feed(json, &mut dispatch);
+    assert_eq!(dispatch.tag(), Some("other"));
+    assert_eq!(
+        dispatch.target().unwrap().get(),
+        Some(&json!({"type": "other"}))
+    );
+
+    let mut parser = JsonStreamParser::new();
+    let mut dispatch = Dispatch::new("/type").variant("search", Typed::<Value>::new());
+    let mut result = Ok(DispatchState::Pending);
+    for c in json.chars() {
+        parser.add_char(c).unwrap();
+        result = dispatch.update(&parser);
+        if result.is_err() {
+            break;
+        }
+    }
+    let error = result.unwrap_err();
+    assert_eq!(
+        error.kind(),
+        &ErrorKind::Target("no variant registered for tag other".into())
+    );
+    // where the tag closed
+    assert_eq!(error.offset(), json.rfind('"').unwrap() + 1);
+}
+
+#[test]
+fn typed_target_sees_input_fed_again_after_a_rollback() {
+    let mut parser = JsonStreamParser::new();
+    let mut dispatch = Dispatch::new("/tool").variant("search", Typed::<Value>::at("/args"));
+    parser.add_str(r#"{"tool": "search", "args": "#).unwrap();
+    dispatch.update(&parser).unwrap();
+    let checkpoint = parser.checkpoint();
+    parser.add_str(r#""a""#).unwrap();
+    dispatch.update(&parser).unwrap();
+    assert_eq!(dispatch.target().unwrap().get(), Some(&json!("a")));
+
+    // the same offset with other input
+    parser.rollback(checkpoint).unwrap();
+    parser.add_str(r#""b""#).unwrap();
+    dispatch.update(&parser).unwrap();
+    assert_eq!(dispatch.target().unwrap().get(), Some(&json!("b")));
+}
+
+#[test]
+fn completed_value_tracks_open_values() {
+    let mut parser = JsonStreamParser::new();
+    for c in r#"{"a": "x", "b": [1, {"c": "y"#.chars() {
+        parser.add_char(c).unwrap();
+    }
+    assert_eq!(parser.completed_value("/a"), Some(&json!("x")));
+    assert_eq!(parser.completed_value("/b/1/c"), None);
+    assert_eq!(parser.completed_value("/b"), None);
+    assert_eq!(parser.completed_value(""), None);
+    assert_eq!(parser.completed_value("/missing"), None);
+}
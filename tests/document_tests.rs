@@ -0,0 +1,202 @@
+use json_stream_parser::{Document, DuplicateKeys, JsonStreamParser, ParseError, Recovery};
+use proptest::prelude::*;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+// Parses `text` from scratch the way a document does: up to its first error.
+fn reparse(text: &str) -> (Value, Option<ParseError>) {
+    reparse_with(JsonStreamParser::new(), text)
+}
+
+fn reparse_with(mut parser: JsonStreamParser, text: &str) -> (Value, Option<ParseError>) {
+    match parser.add_str(text) {
+        Ok(()) => (parser.into_result(), None),
+        Err(error) => {
+            parser.add_str(&text[..error.offset()]).unwrap();
+            (parser.into_result(), Some(error))
+        }
+    }
+}
+
+fn paths(update: json_stream_parser::Update) -> Vec<String> {
+    update.changed.into_iter().collect()
+}
+
+// Whether every path in `changed` is one whose value differs between `before` and `after`.
+fn only_changes(before: &Value, after: &Value, changed: &BTreeSet<String>) -> bool {
+    changed
+        .iter()
+        .all(|path| before.pointer(path) != after.pointer(path))
+}
+
+#[test]
+fn edits_report_the_values_they_change() {
+    let mut document = Document::new();
+    let update = document.insert(0, r#"{"a": 1, "b": [true, "x"], "c/d": null}"#);
+    assert_eq!(
+        update.value,
+        &json!({"a": 1, "b": [true, "x"], "c/d": null})
+    );
+    assert_eq!(paths(update), vec![""]);
+
+    // "1" becomes "12"
+    assert_eq!(paths(document.insert(7, "2")), vec!["/a"]);
+    // "x" becomes "xy"
+    assert_eq!(paths(document.insert(24, "y")), vec!["/b/1"]);
+    // true is removed, so "xy" moves to index 0 and index 1 is gone
+    assert_eq!(paths(document.delete(16..22)), vec!["/b/0", "/b/1"]);
+    // the key with a slash is escaped
+    let update = document.replace(30..34, "0");
+    assert_eq!(update.value, &json!({"a": 12, "b": ["xy"], "c/d": 0}));
+    assert_eq!(paths(update), vec!["/c~1d"]);
+    assert_eq!(paths(document.insert(0, " ")), Vec::<String>::new());
+}
+
+#[test]
+fn texts_with_errors_are_parsed_up_to_the_error() {
+    let mut document = Document::new();
+    document.insert(0, r#"[1, 2, 3]"#);
+    let update = document.replace(4..5, "x");
+    assert_eq!(update.value, &json!([1]));
+    assert_eq!(paths(update), vec!["/1", "/2"]);
+    assert_eq!(document.error().map(ParseError::offset), Some(4));
+
+    // an edit after the error does not fix it
+    document.insert(7, "4, ");
+    assert_eq!(document.error().map(ParseError::offset), Some(4));
+    assert_eq!(document.value(), &json!([1]));
+
+    document.replace(4..5, "2");
+    assert!(document.error().is_none());
+    assert_eq!(document.text(), "[1, 2, 4, 3]");
+    assert_eq!(document.value(), &json!([1, 2, 4, 3]));
+}
+
+#[test]
+fn parser_settings_are_kept() {
+    let mut document =
+        Document::with_parser(JsonStreamParser::new().recovery(Recovery::Replace(json!("?"))));
+    document.insert(0, "[1, x, 3]");
+    assert_eq!(document.value(), &json!([1, "?", 3]));
+    document.replace(4..5, "2");
+    assert_eq!(document.value(), &json!([1, 2, 3]));
+}
+
+#[test]
+fn edits_at_the_end_of_a_large_document() {
+    let items: Vec<String> = (0..10_000).map(|item| item.to_string()).collect();
+    let text = format!("[{}]", items.join(", "));
+    let mut document = Document::new();
+    document.insert(0, &text);
+    let end = text.len() - 1;
+    assert_eq!(paths(document.insert(end, ", 7")), vec!["/10000"]);
+    assert_eq!(document.value()[10_000], json!(7));
+    assert_eq!(paths(document.insert(end + 3, "2")), vec!["/10000"]);
+    assert_eq!(paths(document.delete(end..end + 4)), vec!["/10000"]);
+
+    // typing at the end of a document that is not closed yet
+    let mut document = Document::new();
+    let text = format!(r#"{{"items": [{}], "note": {{"text": ""#, items.join(", "));
+    document.insert(0, &text);
+    for (index, c) in "hi".chars().enumerate() {
+        let update = document.insert(text.len() + index, &c.to_string());
+        assert_eq!(paths(update), vec!["/note/text"]);
+    }
+    assert_eq!(document.value()["note"], json!({"text": "hi"}));
+}
+
+#[test]
+fn edits_that_move_a_container_compare_the_whole_value() {
+    let mut document = Document::new();
+    document.insert(0, r#"{"a": [1, 2], "b": 3}"#);
+    // a later member with the same key and value replaces the one the edit is in
+    let update = document.insert(11, r#"], "a": [1, 2"#);
+    assert_eq!(update.value, &json!({"a": [1, 2], "b": 3}));
+    assert!(update.changed.is_empty());
+    // and a member before the edit is replaced by one after it
+    let update = document.insert(33, r#", "b": 4"#);
+    assert_eq!(update.value, &json!({"a": [1, 2], "b": 4}));
+    assert_eq!(paths(update), vec!["/b"]);
+}
+
+#[test]
+#[should_panic(expected = "out of bounds")]
+fn edits_out_of_bounds_panic() {
+    Document::new().insert(1, "1");
+}
+
+proptest! {
+    #[test]
+    fn edits_match_a_full_reparse(
+        edits in prop::collection::vec(
+            (any::<prop::sample::Index>(), 0..4usize, "[\\[\\]{}\",: a1é\\\\]{0,6}"),
+            1..40,
+        ),
+    ) {
+        let mut document = Document::with_parser(JsonStreamParser::new().rewindable(3));
+        let mut text = String::new();
+        for (at, len, insert) in edits {
+            let chars: Vec<char> = text.chars().collect();
+            let start = at.index(chars.len() + 1);
+            let end = (start + len).min(chars.len());
+            text = chars[..start]
+                .iter()
+                .chain(insert.chars().collect::<Vec<_>>().iter())
+                .chain(&chars[end..])
+                .collect();
+
+            let before = document.value().clone();
+            let update = document.replace(start..end, &insert);
+            let (value, error) = reparse(&text);
+            prop_assert_eq!(update.value, &value);
+            prop_assert_eq!(update.changed.is_empty(), before == value);
+            prop_assert!(only_changes(&before, &value, &update.changed));
+            prop_assert_eq!(document.error(), error.as_ref());
+            prop_assert_eq!(document.text(), text.as_str());
+        }
+    }
+}
+
+fn duplicate_keys() -> impl Strategy<Value = DuplicateKeys> {
+    prop_oneof![
+        Just(DuplicateKeys::LastWins),
+        Just(DuplicateKeys::FirstWins),
+        Just(DuplicateKeys::Collect),
+        Just(DuplicateKeys::Error),
+    ]
+}
+
+proptest! {
+    // edits inside the containers of a document only reparse those containers
+    #[test]
+    fn nested_edits_match_a_full_reparse(
+        policy in duplicate_keys(),
+        edits in prop::collection::vec(
+            (any::<prop::sample::Index>(), 0..3usize, "[\\[\\]{}\",: a1]{0,3}"),
+            1..20,
+        ),
+    ) {
+        let parser = || JsonStreamParser::new().duplicate_keys(policy);
+        let mut document = Document::with_parser(parser());
+        let mut text = r#"{"a": [1, {"b": "x", "c": [2]}, [3, []]], "d": {"a": {"e": 4}}}"#.to_string();
+        document.insert(0, &text);
+        for (at, len, insert) in edits {
+            let chars: Vec<char> = text.chars().collect();
+            let start = at.index(chars.len() + 1);
+            let end = (start + len).min(chars.len());
+            text = chars[..start]
+                .iter()
+                .chain(insert.chars().collect::<Vec<_>>().iter())
+                .chain(&chars[end..])
+                .collect();
+
+            let before = document.value().clone();
+            let update = document.replace(start..end, &insert);
+            let (value, error) = reparse_with(parser(), &text);
+            prop_assert_eq!(update.value, &value);
+            prop_assert_eq!(update.changed.is_empty(), before == value);
+            prop_assert!(only_changes(&before, &value, &update.changed));
+            prop_assert_eq!(document.error(), error.as_ref());
+        }
+    }
+}
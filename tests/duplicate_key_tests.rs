@@ -0,0 +1,94 @@
+use json_stream_parser::{DuplicateKeys, ErrorKind, JsonStreamParser, ParseError};
+use serde_json::{json, Value};
+
+const DOCUMENT: &str =
+    r#"{"a": 1, "b": {"c": [1], "c": {"d": 2}}, "a": [true, {"e": 3}], "a": "x"}"#;
+
+// Parses `json` in one chunk and char by char, and checks that both give the same result.
+fn parse(json: &str, policy: DuplicateKeys) -> Result<Value, ParseError> {
+    let mut parser = JsonStreamParser::new().duplicate_keys(policy);
+    parser.add_str(json)?;
+    let mut per_char = JsonStreamParser::new().duplicate_keys(policy);
+    for c in json.chars() {
+        per_char.add_char(c)?;
+    }
+    assert_eq!(parser.get_result(), per_char.get_result());
+    Ok(parser.into_result())
+}
+
+#[test]
+fn last_value_wins_by_default() {
+    let expected: Value = serde_json::from_str(DOCUMENT).unwrap();
+    assert_eq!(parse(DOCUMENT, DuplicateKeys::LastWins).unwrap(), expected);
+    assert_eq!(
+        json_stream_parser::parse_stream(DOCUMENT).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn first_value_wins() {
+    assert_eq!(
+        parse(DOCUMENT, DuplicateKeys::FirstWins).unwrap(),
+        json!({"a": 1, "b": {"c": [1]}})
+    );
+
+    // the skipped value is left out as it streams in
+    let mut parser = JsonStreamParser::new().duplicate_keys(DuplicateKeys::FirstWins);
+    parser.add_str(r#"{"a": "x", "a": ["y", {"z": "#).unwrap();
+    assert_eq!(parser.get_result(), &json!({"a": "x"}));
+    assert_eq!(parser.completed_value("/a"), Some(&json!("x")));
+}
+
+#[test]
+fn duplicate_keys_can_be_rejected() {
+    let error = parse(DOCUMENT, DuplicateKeys::Error).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::DuplicateKey("c".to_string()));
+    // at the closing quote of the second "c"
+    assert_eq!(error.offset(), DOCUMENT.find(r#""c": {"#).unwrap() + 2);
+    assert!(parse(r#"[{"a": 1}, {"a": 2}]"#, DuplicateKeys::Error).is_ok());
+}
+
+#[test]
+fn duplicate_values_can_be_collected() {
+    assert_eq!(
+        parse(DOCUMENT, DuplicateKeys::Collect).unwrap(),
+        json!({"a": [1, [true, {"e": 3}], "x"], "b": {"c": [[1], {"d": 2}]}})
+    );
+
+    let mut parser = JsonStreamParser::new().duplicate_keys(DuplicateKeys::Collect);
+    parser.add_str(r#"{"a": 1, "a": "#).unwrap();
+    assert_eq!(parser.get_result(), &json!({"a": [1, null]}));
+    parser.add_str(r#""tw"#).unwrap();
+    assert_eq!(parser.get_result(), &json!({"a": [1, "tw"]}));
+}
+
+#[cfg(feature = "preserve_order")]
+#[test]
+fn keys_keep_their_order() {
+    let json = r#"{"z": 1, "a": {"y": 2, "b": 3}, "m": 4, "z": 5}"#;
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(json).unwrap();
+    let result = parser.into_result();
+    let keys: Vec<&String> = result.as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["z", "a", "m"]);
+    let keys: Vec<&String> = result["a"].as_object().unwrap().keys().collect();
+    assert_eq!(keys, ["y", "b"]);
+    assert_eq!(result["z"], json!(5));
+}
+
+#[cfg(feature = "shared")]
+#[test]
+fn shared_snapshots_follow_the_policy() {
+    for policy in [DuplicateKeys::FirstWins, DuplicateKeys::Collect] {
+        for split in (0..DOCUMENT.len()).filter(|&split| DOCUMENT.is_char_boundary(split)) {
+            let mut parser = JsonStreamParser::new().duplicate_keys(policy);
+            parser.add_str(&DOCUMENT[..split]).unwrap();
+            let mut parser = parser.shared_snapshots();
+            for c in DOCUMENT[split..].chars() {
+                parser.add_char(c).unwrap();
+                assert_eq!(&parser.snapshot().to_value(), parser.get_result());
+            }
+        }
+    }
+}
@@ -1,4 +1,8 @@
-use json_stream_parser::parse_stream_with_limits;
+use json_stream_parser::{
+    parse_reader, parse_stream, parse_stream_with_limits, ErrorKind, JsonStreamParser, Limits,
+    ParseError,
+};
+use serde_json::json;
 
 #[test]
 fn depth_limit_exceeded() {
@@ -13,3 +17,181 @@ fn length_limit_exceeded() {
     let result = parse_stream_with_limits(json, None, Some(5));
     assert!(result.is_err());
 }
+
+// The error for `json` under `limits`, which must be the same whether the input arrives in one
+// chunk or char by char.
+fn limit_error(json: &str, limits: Limits) -> ParseError {
+    let mut parser = JsonStreamParser::new().limits(limits);
+    let error = parser.add_str(json).unwrap_err();
+    let mut parser = JsonStreamParser::new().limits(limits);
+    let per_char = json.chars().find_map(|c| parser.add_char(c).err()).unwrap();
+    assert_eq!(error, per_char, "{json}");
+    error
+}
+
+#[test]
+fn each_limit_has_its_own_error() {
+    let cases = [
+        (
+            r#"[[1]]"#,
+            Limits {
+                max_depth: Some(1),
+                ..Limits::default()
+            },
+            ErrorKind::MaxDepth,
+            1,
+        ),
+        (
+            r#"["abcdé"]"#,
+            Limits {
+                max_string_length: Some(5),
+                ..Limits::default()
+            },
+            ErrorKind::MaxStringLength,
+            6,
+        ),
+        (
+            r#"{"ok": 1, "key": 2}"#,
+            Limits {
+                max_key_length: Some(2),
+                ..Limits::default()
+            },
+            ErrorKind::MaxKeyLength,
+            13,
+        ),
+        (
+            r#"[12, -1234]"#,
+            Limits {
+                max_number_length: Some(3),
+                ..Limits::default()
+            },
+            ErrorKind::MaxNumberLength,
+            8,
+        ),
+        (
+            r#"[1, [2, 3], 4]"#,
+            Limits {
+                max_array_length: Some(2),
+                ..Limits::default()
+            },
+            ErrorKind::MaxArrayLength,
+            12,
+        ),
+        (
+            r#"{"a": 1, "a": 2}"#,
+            Limits {
+                max_object_members: Some(1),
+                ..Limits::default()
+            },
+            ErrorKind::MaxObjectMembers,
+            9,
+        ),
+        (
+            r#"{"a": [1, {}], "b": null}"#,
+            Limits {
+                max_nodes: Some(4),
+                ..Limits::default()
+            },
+            ErrorKind::MaxNodes,
+            20,
+        ),
+        (
+            r#"[1, 2, 3]"#,
+            Limits {
+                max_length: Some(4),
+                ..Limits::default()
+            },
+            ErrorKind::MaxLength,
+            4,
+        ),
+    ];
+    for (json, limits, kind, offset) in cases {
+        let error = limit_error(json, limits);
+        assert_eq!((error.kind(), error.offset()), (&kind, offset), "{json}");
+    }
+}
+
+#[test]
+fn memory_budget() {
+    let json = format!(r#"["{}", "{}"]"#, "a".repeat(1000), "b".repeat(1000));
+    let limits = Limits {
+        max_memory: Some(1500),
+        ..Limits::default()
+    };
+    let error = limit_error(&json, limits);
+    assert_eq!(error.kind(), &ErrorKind::MaxMemory);
+    // within the second string
+    assert!(error.offset() > 1006 && error.offset() < 2010, "{error}");
+
+    let mut parser = JsonStreamParser::new().limits(Limits {
+        max_memory: Some(3000),
+        ..Limits::default()
+    });
+    parser.add_str(&json).unwrap();
+}
+
+#[test]
+fn limits_within_bounds_accept_the_input() {
+    let json = r#"{"key": ["abc", 123, {"x": null}]}"#;
+    let limits = Limits {
+        max_depth: Some(3),
+        max_length: Some(json.len()),
+        max_string_length: Some(3),
+        max_key_length: Some(3),
+        max_number_length: Some(3),
+        max_array_length: Some(3),
+        max_object_members: Some(1),
+        max_nodes: Some(6),
+        max_memory: Some(1024),
+    };
+    let mut parser = JsonStreamParser::new().limits(limits);
+    parser.add_str(json).unwrap();
+    assert!(parser.is_complete());
+}
+
+#[test]
+fn syntax_errors_have_positions() {
+    let error = parse_stream(r#"{"a": [1, }"#).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::Syntax(_)));
+    assert_eq!(error.offset(), 10);
+    assert_eq!(
+        error.to_string(),
+        "Invalid character } where a value was expected at byte 10"
+    );
+    let error = parse_stream(r#"[tru}"#).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid character } in literal `true` at byte 4"
+    );
+    let error = parse_stream("[01]").unwrap_err();
+    assert_eq!(error.to_string(), "Invalid number 01 at byte 3");
+}
+
+#[test]
+fn root_numbers_are_checked_at_the_end_of_the_input() {
+    for json in ["1.2.3", "01", "-", "1e"] {
+        let error = parse_stream(json).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Syntax(_)), "{json}");
+        assert_eq!(error.offset(), json.len(), "{json}");
+        assert!(
+            parse_stream_with_limits(json, None, None).is_err(),
+            "{json}"
+        );
+        assert!(parse_reader(json.as_bytes()).is_err(), "{json}");
+    }
+    assert_eq!(parse_stream(" 12 ").unwrap(), json!(12));
+}
+
+#[test]
+fn partial_root_numbers_do_not_depend_on_chunks() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str("12.").unwrap();
+    assert_eq!(parser.get_result(), &json!(12));
+    let mut parser = JsonStreamParser::new();
+    parser.add_str("1").unwrap();
+    parser.add_str("2").unwrap();
+    parser.add_str(".").unwrap();
+    assert_eq!(parser.get_result(), &json!(12));
+    parser.add_str("5e").unwrap();
+    assert_eq!(parser.get_result(), &json!(12.5));
+}
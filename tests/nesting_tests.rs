@@ -0,0 +1,141 @@
+use json_stream_parser::{parse_stream, Document, JsonStreamParser, PartialNode, PartialValue};
+use serde_json::{json, Value};
+
+const DEPTH: usize = 100_000;
+
+// `Value` drops, compares and formats itself recursively, so deep values are walked and taken apart
+// with an explicit loop.
+fn depth_of(mut value: &Value) -> usize {
+    let mut depth = 0;
+    loop {
+        value = match value {
+            Value::Array(arr) if arr.len() <= 1 => match arr.first() {
+                Some(child) => child,
+                None => return depth + 1,
+            },
+            Value::Object(map) if map.len() <= 1 => match map.values().next() {
+                Some(child) => child,
+                None => return depth + 1,
+            },
+            _ => return depth,
+        };
+        depth += 1;
+    }
+}
+
+fn dismantle(value: Value) {
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Array(arr) => pending.extend(arr),
+            Value::Object(map) => pending.extend(map.into_iter().map(|(_, value)| value)),
+            _ => {}
+        }
+    }
+}
+
+fn dismantle_partial(value: PartialValue) {
+    let mut pending = vec![value];
+    while let Some(value) = pending.pop() {
+        match value.node {
+            PartialNode::Array(items) => pending.extend(items),
+            PartialNode::Object(members) => {
+                pending.extend(members.into_iter().map(|(_, value)| value))
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn very_deep_arrays() {
+    let json = format!("{}{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+    let value = parse_stream(&json).unwrap();
+    assert_eq!(depth_of(&value), DEPTH);
+    dismantle(value);
+}
+
+#[test]
+fn very_deep_objects() {
+    let json = format!("{}1{}", r#"{"a":"#.repeat(DEPTH), "}".repeat(DEPTH));
+    let value = parse_stream(&json).unwrap();
+    assert_eq!(depth_of(&value), DEPTH);
+    dismantle(value);
+}
+
+#[test]
+fn dropping_a_deep_partial_document() {
+    let mut parser = JsonStreamParser::new();
+    for _ in 0..10 {
+        parser.add_str(&r#"[{"a": "#.repeat(DEPTH / 20)).unwrap();
+    }
+    assert!(!parser.is_complete());
+    drop(parser);
+}
+
+#[test]
+fn open_nested_containers_are_visible() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": {"b": [1, {"c": "x"#).unwrap();
+    assert_eq!(parser.get_result(), &json!({"a": {"b": [1, {"c": "x"}]}}));
+    parser.add_str(r#"y"}, [tr"#).unwrap();
+    assert_eq!(
+        parser.get_result(),
+        &json!({"a": {"b": [1, {"c": "xy"}, [null]]}})
+    );
+    parser.add_str("ue]]}, \"d\": [").unwrap();
+    assert_eq!(
+        parser.get_result(),
+        &json!({"a": {"b": [1, {"c": "xy"}, [true]]}, "d": []})
+    );
+    assert_eq!(
+        parser.completed_value("/a"),
+        Some(&json!({"b": [1, {"c": "xy"}, [true]]}))
+    );
+    assert_eq!(parser.completed_value("/d"), None);
+}
+
+#[test]
+fn deep_partial_values() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(&r#"[{"a": "#.repeat(DEPTH / 2)).unwrap();
+    let partial = parser.partial_value();
+    let value = partial.to_value();
+    assert_eq!(depth_of(&value), DEPTH);
+    dismantle(value);
+    dismantle_partial(partial);
+}
+
+#[test]
+fn editing_a_deep_document() {
+    let mut document = Document::new();
+    document.insert(0, &format!("{}1{}", "[".repeat(DEPTH), "]".repeat(DEPTH)));
+    assert_eq!(depth_of(document.value()), DEPTH);
+
+    // only the innermost array is compared again
+    let update = document.insert(DEPTH + 1, "2");
+    let changed: Vec<String> = update.changed.into_iter().collect();
+    assert_eq!(changed, vec!["/0".repeat(DEPTH)]);
+
+    // without its first bracket the whole text is parsed again, up to the extra closing one
+    document.delete(0..1);
+    assert_eq!(
+        document.error().map(|error| error.offset()),
+        Some(2 * DEPTH)
+    );
+    assert_eq!(depth_of(document.value()), DEPTH - 1);
+}
+
+#[test]
+fn editing_next_to_a_deep_value() {
+    let mut document = Document::new();
+    let deep = format!("{}1{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+    document.insert(0, &format!("[0, {deep}]"));
+
+    // the deep value after the edit is kept to compare against
+    let update = document.replace(1..2, "5");
+    let changed: Vec<String> = update.changed.into_iter().collect();
+    assert_eq!(changed, vec!["/0"]);
+    assert_eq!(document.value()[0], json!(5));
+    assert_eq!(depth_of(&document.value()[1]), DEPTH);
+}
@@ -0,0 +1,99 @@
+use json_stream_parser::{DuplicateKeys, JsonStreamParser, PartialNode, PartialValue};
+use serde_json::json;
+
+// Checks every node of `value` against the spans: a node is complete once its last char has been
+// received.
+fn check(parser: &JsonStreamParser, value: &PartialValue, path: &mut String) {
+    let span = parser.spans().unwrap().get(path);
+    let ended = span.map_or(false, |span| span.end.is_some());
+    assert_eq!(value.complete, ended, "{path}");
+    let len = path.len();
+    match &value.node {
+        PartialNode::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                path.push_str(&format!("/{index}"));
+                check(parser, item, path);
+                path.truncate(len);
+            }
+        }
+        PartialNode::Object(members) => {
+            for (key, member) in members {
+                path.push_str(&format!("/{key}"));
+                check(parser, member, path);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn nodes_are_complete_once_their_last_char_arrives() {
+    let json = r#"{"a": "hel", "b": [1, 2.5, {"c": true, "d": null}, []], "e": {}, "f": -12}"#;
+    let mut parser = JsonStreamParser::new().record_spans();
+    for c in json.chars() {
+        parser.add_char(c).unwrap();
+        let value = parser.partial_value();
+        assert_eq!(&value.to_value(), parser.get_result());
+        check(&parser, &value, &mut String::new());
+    }
+    assert!(parser.partial_value().complete);
+
+    let mut parser = JsonStreamParser::new().record_spans();
+    for c in "-12.5 ".chars() {
+        parser.add_char(c).unwrap();
+        check(&parser, &parser.partial_value(), &mut String::new());
+    }
+}
+
+#[test]
+fn finished_and_streaming_strings_differ() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": "hel"#).unwrap();
+    let streaming = parser.partial_value();
+    parser.add_str(r#"", "b": "#).unwrap();
+    let finished = parser.partial_value();
+    assert_eq!(streaming.to_value(), json!({"a": "hel"}));
+
+    assert_eq!(
+        serde_json::to_value(&streaming).unwrap(),
+        json!({"value": {"a": {"value": "hel", "complete": false}}, "complete": false})
+    );
+    // the member whose value has not started is an incomplete null
+    assert_eq!(
+        serde_json::to_value(&finished).unwrap(),
+        json!({
+            "value": {
+                "a": {"value": "hel", "complete": true},
+                "b": {"value": null, "complete": false},
+            },
+            "complete": false,
+        })
+    );
+}
+
+#[test]
+fn duplicate_keys_are_marked_where_they_end_up() {
+    let mut parser = JsonStreamParser::new().duplicate_keys(DuplicateKeys::Collect);
+    parser.add_str(r#"{"a": 1, "a": [2, "#).unwrap();
+    let value = parser.partial_value();
+    assert_eq!(value.to_value(), json!({"a": [1, [2]]}));
+    let PartialNode::Object(members) = &value.node else {
+        panic!("not an object");
+    };
+    let PartialNode::Array(values) = &members[0].1.node else {
+        panic!("not an array");
+    };
+    assert!(!members[0].1.complete);
+    assert!(values[0].complete);
+    assert!(!values[1].complete);
+
+    // the second value of `a` is left out, and the first one is complete
+    let mut parser = JsonStreamParser::new().duplicate_keys(DuplicateKeys::FirstWins);
+    parser.add_str(r#"{"a": 1, "a": [2, "#).unwrap();
+    let value = parser.partial_value();
+    assert_eq!(
+        serde_json::to_value(&value).unwrap(),
+        json!({"value": {"a": {"value": 1, "complete": true}}, "complete": false})
+    );
+}
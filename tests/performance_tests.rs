@@ -25,14 +25,14 @@ fn performance_compare() {
 
     let start_mem = memory_usage_kb();
     let start_time = Instant::now();
-    let parsed_stream = parse_stream(&json).unwrap();
+    let parsed_stream = parse_stream(json).unwrap();
     let duration_stream = start_time.elapsed();
     let end_mem = memory_usage_kb();
     let mem_used_stream = end_mem.saturating_sub(start_mem);
 
     let start_mem = memory_usage_kb();
     let start_time = Instant::now();
-    let parsed_serde: Value = serde_json::from_str(&json).unwrap();
+    let parsed_serde: Value = serde_json::from_str(json).unwrap();
     let duration_serde = start_time.elapsed();
     let end_mem = memory_usage_kb();
     let mem_used_serde = end_mem.saturating_sub(start_mem);
@@ -0,0 +1,225 @@
+use json_stream_parser::{ErrorKind, JsonStreamParser, Limits, Recovery, Scalar, ValueBuilder};
+use serde_json::{json, Value};
+
+const RESPONSE: &str = r#"{
+    "meta": {"page": 1, "next": "abc"},
+    "items": [
+        {"id": 1, "name": "ada", "tags": ["x", "y"]},
+        {"name": "bob", "id": 2, "extra": {"id": 9}},
+        {"name": "cy"},
+        7
+    ],
+    "links": {"self": "/items"}
+}"#;
+
+// Parses `json` one char at a time and at once, checking that both give the same result.
+fn parse(globs: &[&str], json: &str) -> Value {
+    let mut parser = JsonStreamParser::new().project(globs.iter().copied());
+    for c in json.chars() {
+        parser.add_char(c).unwrap();
+    }
+    let mut whole = JsonStreamParser::new().project(globs.iter().copied());
+    whole.add_str(json).unwrap();
+    assert_eq!(whole.get_result(), parser.get_result());
+    parser.into_result()
+}
+
+#[test]
+fn only_selected_paths_are_built() {
+    assert_eq!(
+        parse(&["/meta", "/items/*/id"], RESPONSE),
+        json!({
+            "meta": {"page": 1, "next": "abc"},
+            "items": [{"id": 1}, {"id": 2}, null, null],
+        })
+    );
+    // elements that are left out keep the others at their index
+    assert_eq!(
+        parse(&["/items/1"], RESPONSE),
+        json!({"items": [null, {"name": "bob", "id": 2, "extra": {"id": 9}}, null, null]})
+    );
+    // containers that hold nothing selected are left out like any other value
+    assert_eq!(
+        parse(&["/**/id"], RESPONSE),
+        json!({"items": [{"id": 1}, {"id": 2, "extra": {"id": 9}}, null, null]})
+    );
+    assert_eq!(parse(&["/nothing"], RESPONSE), json!({}));
+    assert_eq!(parse(&["/a"], "[1, 2]"), json!([null, null]));
+    assert_eq!(parse(&["/a"], r#""root""#), json!("root"));
+}
+
+#[test]
+fn partial_results_only_hold_selected_paths() {
+    let mut parser = JsonStreamParser::new().project(["/meta/page", "/items/*/id"]);
+    parser
+        .add_str(r#"{"meta": {"page": 1, "next": "ab"#)
+        .unwrap();
+    assert_eq!(parser.get_result(), &json!({"meta": {"page": 1}}));
+    assert_eq!(parser.completed_value("/meta/page"), Some(&json!(1)));
+    parser
+        .add_str(r#"c"}, "items": [{"name": "ada", "id": 1"#)
+        .unwrap();
+    assert_eq!(
+        parser.get_result(),
+        &json!({"meta": {"page": 1}, "items": [{"id": null}]})
+    );
+    parser.add_str("}, {\"name\": \"bob\"").unwrap();
+    // an open container is kept until it turns out to hold nothing selected
+    assert_eq!(
+        parser.get_result(),
+        &json!({"meta": {"page": 1}, "items": [{"id": 1}, {}]})
+    );
+    parser.add_str(r#"}, {"id": 3}]}"#).unwrap();
+    assert_eq!(
+        parser.get_result(),
+        &json!({"meta": {"page": 1}, "items": [{"id": 1}, null, {"id": 3}]})
+    );
+    assert_eq!(parser.completed_value("/items/0/id"), Some(&json!(1)));
+    assert_eq!(parser.completed_value("/items/2/id"), Some(&json!(3)));
+}
+
+#[cfg(feature = "shared")]
+#[test]
+fn shared_snapshots_follow_the_projection() {
+    use json_stream_parser::SharedValueBuilder;
+
+    let globs = ["/**/id"];
+    let mut parser = JsonStreamParser::with_builder(SharedValueBuilder::new()).project(globs);
+    let mut serde = JsonStreamParser::new().project(globs);
+    for c in RESPONSE.chars() {
+        parser.add_char(c).unwrap();
+        serde.add_char(c).unwrap();
+        assert_eq!(&parser.builder().snapshot().to_value(), serde.get_result());
+    }
+}
+
+#[test]
+fn skipped_values_are_still_checked() {
+    let mut parser = JsonStreamParser::new().project(["/id"]);
+    let error = parser
+        .add_str(r#"{"id": 1, "rest": [1, {"a": tru}]}"#)
+        .unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::Syntax(_)));
+
+    let limits = Limits {
+        max_depth: Some(3),
+        max_string_length: Some(4),
+        ..Limits::default()
+    };
+    let mut parser = JsonStreamParser::new().limits(limits).project(["/id"]);
+    let error = parser.add_str(r#"{"rest": [[[1]]]}"#).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::MaxDepth);
+    let mut parser = JsonStreamParser::new().limits(limits).project(["/id"]);
+    let error = parser.add_str(r#"{"rest": ["abcde"]}"#).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::MaxStringLength);
+}
+
+// Logs every builder call.
+#[derive(Default)]
+struct Calls(Vec<String>);
+
+impl ValueBuilder for Calls {
+    type Output = Vec<String>;
+
+    fn start_object(&mut self) {
+        self.0.push("{".into());
+    }
+    fn start_array(&mut self) {
+        self.0.push("[".into());
+    }
+    fn end_container(&mut self) {
+        self.0.push("end".into());
+    }
+    fn insert_key(&mut self, key: &str) {
+        self.0.push(format!("key {key}"));
+    }
+    fn push_item(&mut self) {
+        self.0.push("item".into());
+    }
+    fn start_string(&mut self) {
+        self.0.push("string".into());
+    }
+    fn append_string(&mut self, s: &str) {
+        self.0.push(format!("append {s}"));
+    }
+    fn set_scalar(&mut self, scalar: Scalar) {
+        self.0.push(format!("{:?}", Value::from(scalar)));
+    }
+    fn end_value(&mut self) {
+        self.0.push("value".into());
+    }
+    fn discard(&mut self) {
+        self.0.push("discard".into());
+    }
+    fn into_output(self) -> Vec<String> {
+        self.0
+    }
+}
+
+#[test]
+fn skipped_subtrees_make_no_builder_calls() {
+    let mut parser = JsonStreamParser::with_builder(Calls::default()).project(["/a/*/id"]);
+    parser
+        .add_str(r#"{"b": {"c": [1, "x"]}, "a": [{"id": 1, "n": "y"}, 2]}"#)
+        .unwrap();
+    assert_eq!(
+        parser.into_output(),
+        vec![
+            "{",
+            "key a",
+            "[",
+            "item",
+            "{",
+            "key id",
+            "Number(1)",
+            "value",
+            "end",
+            "item",
+            "value",
+            "end",
+            "end",
+        ]
+    );
+}
+
+#[test]
+fn spans_follow_the_projection() {
+    let json = r#"{"a": {"x": 1}, "b": [2, "z"], "c": 3}"#;
+    let mut parser = JsonStreamParser::new().record_spans().project(["/b/0"]);
+    parser.add_str(json).unwrap();
+    let spans = parser.spans().unwrap();
+    assert_eq!(spans.get("/b").unwrap().start.char, json.find('[').unwrap());
+    assert_eq!(
+        spans.get("/b/0").unwrap().start.char,
+        json.find('2').unwrap()
+    );
+    assert!(spans.get("/a").is_none() && spans.get("/c").is_none());
+    // an element that is left out is null, but has its span
+    let json = r#"{"b": [{"x": [1]}, "zz", {"y": 2}], "c": {"d": 1}}"#;
+    let mut parser = JsonStreamParser::new()
+        .record_spans()
+        .project(["/b/2", "/c/e"]);
+    parser.add_str(json).unwrap();
+    assert_eq!(parser.get_result(), &json!({"b": [null, null, {"y": 2}]}));
+    let spans = parser.spans().unwrap();
+    let start = |pattern| json.find(pattern).unwrap();
+    assert_eq!(spans.get("/b/0").unwrap().start.char, start("{\"x"));
+    assert_eq!(
+        spans.get("/b/1").unwrap().end.unwrap().char,
+        start(", {\"y")
+    );
+    assert_eq!(spans.get("/b/2").unwrap().start.char, start("{\"y"));
+    assert!(spans.get("/b/0/x").is_none() && spans.get("/c").is_none());
+
+    // even when it is malformed
+    let json = r#"[tru, 2]"#;
+    let mut parser = JsonStreamParser::new()
+        .recovery(Recovery::Drop)
+        .record_spans()
+        .project(["/1"]);
+    parser.add_str(json).unwrap();
+    assert_eq!(parser.get_result(), &json!([null, 2]));
+    let spans = parser.spans().unwrap();
+    assert_eq!(spans.get("/0").unwrap().end.unwrap().char, 4);
+    assert_eq!(spans.get("/1").unwrap().start.char, 6);
+}
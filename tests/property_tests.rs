@@ -29,6 +29,24 @@ fn json_value() -> impl Strategy<Value = Value> {
     })
 }
 
+// strings with quotes, backslashes, control characters and multi-byte chars, all of which end or
+// split a run of plain string content
+fn escaped_json_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        json_number().prop_map(Value::Number),
+        string_regex("[a-z\"\\\\\n\t/é🦀]{0,30}")
+            .unwrap()
+            .prop_map(Value::String),
+    ];
+    leaf.prop_recursive(3, 8, 3, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..3).prop_map(Value::Array),
+            prop::collection::btree_map(string_regex("[a-z\"\\\\é]{0,10}").unwrap(), inner, 0..3)
+                .prop_map(|m: BTreeMap<String, Value>| Value::Object(m.into_iter().collect()))
+        ]
+    })
+}
+
 proptest! {
     #[test]
     fn roundtrip_json(value in json_value()) {
@@ -44,3 +62,37 @@ proptest! {
         prop_assert_eq!(parser.get_result(), &expected);
     }
 }
+
+proptest! {
+    #[test]
+    fn chunked_snapshots_match_per_char_snapshots(
+        value in escaped_json_value(),
+        splits in prop::collection::vec(any::<prop::sample::Index>(), 0..8),
+        unicode_escapes in any::<bool>(),
+    ) {
+        let mut json = serde_json::to_string(&value).unwrap();
+        if unicode_escapes {
+            json = json.replace('é', "\\u00e9").replace('🦀', "\\ud83e\\udd80");
+        }
+        let mut boundaries: Vec<usize> = splits
+            .iter()
+            .map(|index| index.index(json.len() + 1))
+            .filter(|&boundary| json.is_char_boundary(boundary))
+            .collect();
+        boundaries.push(json.len());
+        boundaries.sort_unstable();
+
+        let mut chunked = JsonStreamParser::new();
+        let mut per_char = JsonStreamParser::new();
+        let mut start = 0;
+        for end in boundaries {
+            chunked.add_str(&json[start..end]).unwrap();
+            for c in json[start..end].chars() {
+                per_char.add_char(c).unwrap();
+            }
+            prop_assert_eq!(chunked.get_result(), per_char.get_result());
+            start = end;
+        }
+        prop_assert_eq!(chunked.get_result(), &value);
+    }
+}
@@ -0,0 +1,130 @@
+use json_stream_parser::{parse_reader, Granularity, JsonStreamParser, ReaderSnapshots, Snapshot};
+use serde_json::{json, Value};
+use std::io::{self, Read};
+
+// Hands out at most one byte per read, like a slow pipe.
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.split_first() {
+            Some((byte, rest)) if !buf.is_empty() => {
+                buf[0] = *byte;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+fn values(snapshots: impl Iterator<Item = io::Result<Snapshot>>) -> Vec<Value> {
+    snapshots.map(|snapshot| snapshot.unwrap().value).collect()
+}
+
+#[test]
+fn parse_reader_matches_parse_stream() {
+    let json = include_str!("large_array.json");
+    let parsed = parse_reader(json.as_bytes()).unwrap();
+    let expected: Value = serde_json::from_str(json).unwrap();
+    assert_eq!(parsed, expected);
+
+    let partial = parse_reader(Trickle(r#"{"name": "Zoë", "tags": ["a""#.as_bytes())).unwrap();
+    // open nested containers are part of the result, see CHANGELOG.md
+    assert_eq!(partial, json!({"name": "Zoë", "tags": ["a"]}));
+}
+
+#[test]
+fn value_granularity() {
+    let json = r#"{"a": 1, "b": "x", "c": true}"#;
+    let snapshots = values(JsonStreamParser::from_reader(Trickle(json.as_bytes())));
+    assert_eq!(
+        snapshots,
+        vec![
+            json!({"a": 1}),
+            json!({"a": 1, "b": "x"}),
+            // `true` completes at its last letter, before the object closes
+            json!({"a": 1, "b": "x", "c": true}),
+            json!({"a": 1, "b": "x", "c": true}),
+        ]
+    );
+}
+
+#[test]
+fn token_granularity() {
+    let json = r#"{"a": [1, 2], "b": "x"}"#;
+    let snapshots: Vec<Snapshot> = JsonStreamParser::from_reader(json.as_bytes())
+        .granularity(Granularity::Token)
+        .map(|snapshot| snapshot.unwrap())
+        .collect();
+    let offsets: Vec<usize> = snapshots.iter().map(|snapshot| snapshot.offset).collect();
+    // {  "a"  :  [  1,  2]  ,  "b"  :  "x"  }
+    assert_eq!(offsets, vec![1, 4, 5, 7, 9, 12, 13, 17, 18, 22, 23]);
+    assert_eq!(
+        snapshots.last().unwrap().value,
+        json!({"a": [1, 2], "b": "x"})
+    );
+}
+
+#[test]
+fn snapshots_do_not_depend_on_read_sizes() {
+    let json =
+        r#"{"a": [1, true, null, -2.5e3], "b": {"c": "Zoë \u00e9 🦀", "d": []}, "e": false} "#;
+    for granularity in [
+        Granularity::Token,
+        Granularity::Value,
+        Granularity::Bytes(1),
+        Granularity::Bytes(7),
+    ] {
+        let whole: Vec<Snapshot> = JsonStreamParser::from_reader(json.as_bytes())
+            .granularity(granularity)
+            .map(|snapshot| snapshot.unwrap())
+            .collect();
+        let trickled: Vec<Snapshot> = JsonStreamParser::from_reader(Trickle(json.as_bytes()))
+            .granularity(granularity)
+            .map(|snapshot| snapshot.unwrap())
+            .collect();
+        assert_eq!(whole, trickled, "{granularity:?}");
+    }
+}
+
+#[test]
+fn byte_granularity_and_final_snapshot() {
+    let json = r#"["abcdef", 12345]"#;
+    let snapshots: Vec<Snapshot> = JsonStreamParser::from_reader(Trickle(json.as_bytes()))
+        .granularity(Granularity::Bytes(5))
+        .buffer_size(2)
+        .map(|snapshot| snapshot.unwrap())
+        .collect();
+    let offsets: Vec<usize> = snapshots.iter().map(|snapshot| snapshot.offset).collect();
+    assert_eq!(offsets, vec![5, 10, 15, 17]);
+    assert_eq!(snapshots[0].value, json!(["abc"]));
+    assert_eq!(snapshots[3].value, json!(["abcdef", 12345]));
+}
+
+#[test]
+fn utf8_split_by_small_buffer() {
+    let json = r#"["🦀", "é"]"#;
+    let snapshots = values(
+        JsonStreamParser::from_reader(Trickle(json.as_bytes())).granularity(Granularity::Value),
+    );
+    assert_eq!(snapshots.last().unwrap(), &json!(["🦀", "é"]));
+}
+
+#[test]
+fn errors_end_the_iteration() {
+    let mut snapshots = JsonStreamParser::from_reader(&b"[1, {]"[..]);
+    let error = snapshots.find_map(|snapshot| snapshot.err()).unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(snapshots.next().is_none());
+
+    let error = parse_reader(&[b'"', 0xE2, 0x82][..]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn limits_from_a_configured_parser() {
+    let parser = JsonStreamParser::with_limits(Some(2), None);
+    let results: Vec<_> = ReaderSnapshots::with_parser(&b"[[[1]]]"[..], parser).collect();
+    assert!(results.last().unwrap().is_err());
+}
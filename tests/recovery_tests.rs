@@ -0,0 +1,117 @@
+use json_stream_parser::{
+    DuplicateKeys, ErrorKind, JsonStreamParser, Limits, ParseError, Recovery,
+};
+use serde_json::{json, Value};
+
+// Parses `json` in recovery mode, which must give the same result and diagnostics whether the
+// input arrives in one chunk or char by char.
+fn recover(json: &str, recovery: Recovery) -> (Value, Vec<ParseError>) {
+    let mut parser = JsonStreamParser::new().recovery(recovery.clone());
+    parser.add_str(json).unwrap();
+    let diagnostics = parser.diagnostics().to_vec();
+    #[cfg(feature = "shared")]
+    let snapshot = JsonStreamParser::new()
+        .shared_snapshots()
+        .recovery(recovery.clone());
+
+    let mut per_char = JsonStreamParser::new().recovery(recovery);
+    for c in json.chars() {
+        per_char.add_char(c).unwrap();
+    }
+    assert_eq!(per_char.get_result(), parser.get_result(), "{json}");
+    assert_eq!(per_char.diagnostics(), diagnostics, "{json}");
+
+    #[cfg(feature = "shared")]
+    {
+        let mut snapshot = snapshot;
+        snapshot.add_str(json).unwrap();
+        assert_eq!(&snapshot.snapshot().to_value(), parser.get_result());
+    }
+    (parser.into_result(), diagnostics)
+}
+
+fn marker() -> Recovery {
+    Recovery::Replace(json!("<invalid>"))
+}
+
+#[test]
+fn malformed_values_are_replaced_or_dropped() {
+    let cases = [
+        ("[1, tru x, 3]", json!([1, "<invalid>", 3]), json!([1, 3])),
+        (
+            r#"{"a": 1, "b": x, "c": 3}"#,
+            json!({"a": 1, "b": "<invalid>", "c": 3}),
+            json!({"a": 1, "c": 3}),
+        ),
+        (
+            r#"{"a": [1, {"b" 2}], "c": 3}"#,
+            json!({"a": [1, {"b": "<invalid>"}], "c": 3}),
+            json!({"a": [1, {}], "c": 3}),
+        ),
+        (
+            r#"["ok", "bad\x", "next"]"#,
+            json!(["ok", "<invalid>", "next"]),
+            json!(["ok", "next"]),
+        ),
+        ("[1, 2.e, 3]", json!([1, "<invalid>", 3]), json!([1, 3])),
+        ("nul!", json!("<invalid>"), Value::Null),
+    ];
+    for (json, replaced, dropped) in cases {
+        let (value, diagnostics) = recover(json, marker());
+        assert_eq!(value, replaced, "{json}");
+        assert_eq!(diagnostics.len(), 1, "{json}");
+        assert_eq!(recover(json, Recovery::Drop).0, dropped, "{json}");
+    }
+}
+
+#[test]
+fn skipped_input_is_balanced() {
+    // the brackets and strings of the malformed member are skipped as a whole
+    let json = r#"[1, {"a\q": [2, "x]"], "k": 4}, 5]"#;
+    assert_eq!(recover(json, marker()).0, json!([1, {"k": 4}, 5]));
+
+    let json = r#"[1 {"a": [2, 3]}, 4]"#;
+    assert_eq!(recover(json, marker()).0, json!([1, 4]));
+}
+
+#[test]
+fn every_diagnostic_is_kept_with_its_position() {
+    let (value, diagnostics) = recover(r#"{"a": tru x, "b": [1 2], "c": 3} 4"#, marker());
+    assert_eq!(value, json!({"a": "<invalid>", "b": [1], "c": 3}));
+    let offsets: Vec<usize> = diagnostics.iter().map(ParseError::offset).collect();
+    assert_eq!(offsets, vec![9, 21, 33]);
+    assert!(diagnostics
+        .iter()
+        .all(|e| matches!(e.kind(), ErrorKind::Syntax(_))));
+}
+
+#[test]
+fn markers_can_be_any_value() {
+    let recovery = Recovery::Replace(json!({"error": [true]}));
+    assert_eq!(recover("[x, 1]", recovery).0, json!([{"error": [true]}, 1]));
+}
+
+#[test]
+fn duplicate_members_that_are_left_out_stay_out() {
+    let mut parser = JsonStreamParser::new()
+        .duplicate_keys(DuplicateKeys::FirstWins)
+        .recovery(marker());
+    parser.add_str(r#"{"a": 1, "a": x, "b": 2}"#).unwrap();
+    assert_eq!(parser.get_result(), &json!({"a": 1, "b": 2}));
+    assert_eq!(parser.diagnostics().len(), 1);
+}
+
+#[test]
+fn limits_still_fail() {
+    let mut parser = JsonStreamParser::new()
+        .limits(Limits {
+            max_depth: Some(1),
+            ..Limits::default()
+        })
+        .recovery(Recovery::Drop);
+    let error = parser.add_str("[x, [1]]").unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::MaxDepth);
+    // the chunk is not applied, so neither is its diagnostic
+    assert!(parser.diagnostics().is_empty());
+    assert_eq!(parser.get_result(), &Value::Null);
+}
@@ -0,0 +1,196 @@
+use json_stream_parser::{
+    DuplicateKeys, ErrorKind, JsonStreamParser, Limits, Recovery, Redaction, RewindError,
+};
+use serde_json::{json, Value};
+
+// Parses `json` one char at a time, checking that no snapshot ever shows `secrets`, and returns
+// the result.
+fn parse(redaction: Redaction, json: &str, secrets: &[&str]) -> Value {
+    let mut parser = JsonStreamParser::new().redact(redaction.clone());
+    for c in json.chars() {
+        parser.add_char(c).unwrap();
+        let snapshot = parser.get_result().to_string();
+        for secret in secrets {
+            assert!(!snapshot.contains(secret), "{snapshot}");
+        }
+    }
+    let mut whole = JsonStreamParser::new().redact(redaction);
+    whole.add_str(json).unwrap();
+    assert_eq!(whole.get_result(), parser.get_result());
+    parser.into_result()
+}
+
+#[test]
+fn paths_are_redacted() {
+    let json = r#"{"user": "ada", "password": "hunter2", "keys": [{"api_key": "sk-1"}],
+        "nested": {"deep": {"api_key": "sk-2"}}, "api_key": "sk-3", "pin": 1234}"#;
+    let redaction = Redaction::new()
+        .path("/password")
+        .path("/**/api_key")
+        .path("/pin");
+    let value = parse(redaction, json, &["hunter", "sk-", "12"]);
+    assert_eq!(
+        value,
+        json!({
+            "user": "ada",
+            "password": "[REDACTED]",
+            "keys": [{"api_key": "[REDACTED]"}],
+            "nested": {"deep": {"api_key": "[REDACTED]"}},
+            "api_key": "[REDACTED]",
+            "pin": "[REDACTED]",
+        })
+    );
+}
+
+#[test]
+fn containers_are_redacted_whole() {
+    let json =
+        r#"{"a": [{"card": {"number": "4111", "cvc": [1, 2, 3]}}, {"card": null}], "b": true}"#;
+    let redaction = Redaction::new()
+        .path("/a/*/card")
+        .placeholder(json!({"redacted": true}));
+    let value = parse(redaction, json, &["4111", "cvc"]);
+    assert_eq!(
+        value,
+        json!({"a": [{"card": {"redacted": true}}, {"card": {"redacted": true}}], "b": true})
+    );
+}
+
+#[test]
+fn the_root_is_never_redacted() {
+    let value = parse(Redaction::new().path("/**"), r#"[1, {"a": 2}]"#, &[]);
+    assert_eq!(value, json!(["[REDACTED]", "[REDACTED]"]));
+    let value = parse(Redaction::new().path(""), r#""secret""#, &[]);
+    assert_eq!(value, json!("secret"));
+}
+
+#[test]
+fn strings_are_held_back_for_the_matcher() {
+    let json = r#"{"to": ["ada@example.com", "bob"], "note": "hi ada@example.com"}"#;
+    let redaction = Redaction::new().matching(|s| s.contains('@'));
+    let mut parser = JsonStreamParser::new().redact(redaction.clone());
+    parser.add_str(r#"{"to": ["ada@exa"#).unwrap();
+    assert_eq!(parser.get_result(), &json!({"to": [null]}));
+    let value = parse(redaction, json, &["example"]);
+    assert_eq!(
+        value,
+        json!({"to": ["[REDACTED]", "bob"], "note": "[REDACTED]"})
+    );
+}
+
+#[test]
+fn redacted_values_are_still_checked() {
+    let redaction = Redaction::new().path("/secret");
+    let mut parser = JsonStreamParser::new().redact(redaction.clone());
+    let error = parser.add_str(r#"{"secret": [07]}"#).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::Syntax(_)));
+    // the error does not show the digits
+    assert!(!error.to_string().contains("07"), "{error}");
+
+    let limits = Limits {
+        max_string_length: Some(3),
+        ..Limits::default()
+    };
+    let mut parser = JsonStreamParser::new()
+        .limits(limits)
+        .redact(redaction.clone());
+    let error = parser.add_str(r#"{"secret": {"a": "abcd"}}"#).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::MaxStringLength);
+
+    let mut parser = JsonStreamParser::new()
+        .recovery(Recovery::Drop)
+        .redact(redaction);
+    parser
+        .add_str(r#"{"secret": {"a": tru, "b": 2}, "c": 3}"#)
+        .unwrap();
+    assert_eq!(
+        parser.get_result(),
+        &json!({"secret": "[REDACTED]", "c": 3})
+    );
+    assert_eq!(parser.diagnostics().len(), 1);
+}
+
+#[test]
+fn keys_in_redacted_values_are_only_checked_for_length() {
+    let redaction = Redaction::new().path("/secret");
+    let mut parser = JsonStreamParser::new()
+        .duplicate_keys(DuplicateKeys::Error)
+        .redact(redaction.clone());
+    // they are not kept, so duplicates are not looked for
+    parser
+        .add_str(r#"{"secret": {"a": 1, "a": 2}, "b": 3}"#)
+        .unwrap();
+    assert_eq!(
+        parser.get_result(),
+        &json!({"secret": "[REDACTED]", "b": 3})
+    );
+
+    let limits = Limits {
+        max_key_length: Some(3),
+        ..Limits::default()
+    };
+    let mut parser = JsonStreamParser::new().limits(limits).redact(redaction);
+    let error = parser.add_str(r#"{"secret": {"abcd": 1}}"#).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::MaxKeyLength);
+}
+
+#[test]
+fn redacting_parsers_only_rewind_to_their_copies() {
+    let redaction = Redaction::new().matching(|s| s.starts_with("sk-"));
+    let mut parser = JsonStreamParser::new().redact(redaction);
+    parser.add_str(r#"{"a": "x", "#).unwrap();
+    let before = parser.checkpoint();
+    parser.add_str(r#""key": "sk"#).unwrap();
+    let held = parser.checkpoint();
+    parser.add_str("-1\"").unwrap();
+    // the input is not kept to be replayed
+    assert_eq!(
+        parser.truncate_to(before.offset() + 2),
+        Err(RewindError::Forgotten)
+    );
+    // nor is the copy holding the start of the secret
+    assert_eq!(parser.rollback(held), Err(RewindError::Forgotten));
+    parser.rollback(before).unwrap();
+    parser.add_str(r#""b": 1}"#).unwrap();
+    assert_eq!(parser.get_result(), &json!({"a": "x", "b": 1}));
+}
+
+#[test]
+fn failed_chunks_leave_redaction_as_it_was() {
+    let redaction = Redaction::new()
+        .path("/secret")
+        .matching(|s| s.starts_with("sk-"));
+    let mut parser = JsonStreamParser::new().redact(redaction);
+    parser.add_str(r#"{"secret": {"a": "#).unwrap();
+    assert!(parser.add_str(r#"1}, "b": y"#).is_err());
+    parser.add_str(r#"1}, "key": "sk"#).unwrap();
+    assert!(parser.add_str(r#"-1", "b": "sk-2", "c": x"#).is_err());
+    parser.add_str(r#"-1", "b": "sk-2", "c": "sk"#).unwrap();
+    assert!(parser.add_str(r#"-3", "d": tx"#).is_err());
+    parser.add_str(r#"-3", "d": "ok"}"#).unwrap();
+    assert_eq!(
+        parser.get_result(),
+        &json!({
+            "secret": "[REDACTED]",
+            "key": "[REDACTED]",
+            "b": "[REDACTED]",
+            "c": "[REDACTED]",
+            "d": "ok",
+        })
+    );
+}
+
+#[test]
+fn redacted_values_have_a_span_but_what_is_in_them_does_not() {
+    let json = r#"{"secret": {"a": [1]}, "b": 2}"#;
+    let mut parser = JsonStreamParser::new()
+        .record_spans()
+        .redact(Redaction::new().path("/secret"));
+    parser.add_str(json).unwrap();
+    let spans = parser.spans().unwrap();
+    let span = spans.get("/secret").unwrap();
+    assert_eq!(span.start.char, json.find('{').map(|i| i + 11).unwrap());
+    assert_eq!(span.end.unwrap().char, json.find(", \"b\"").unwrap());
+    assert!(spans.get("/secret/a").is_none());
+    assert_eq!(spans.get("/b").unwrap().start.char, json.len() - 2);
+}
@@ -0,0 +1,233 @@
+use json_stream_parser::{parse_stream, JsonStreamParser, RewindError};
+use serde_json::{json, Value};
+
+const JSON: &str = r#"{"title": "Zoë é🦀", "items": [{"id": 1, "tags": ["a", []]}, {"id": -2.5e3, "ok": true}], "rest": null}"#;
+
+fn prefix(json: &str, chars: usize) -> &str {
+    json.char_indices()
+        .nth(chars)
+        .map_or(json, |(index, _)| &json[..index])
+}
+
+fn parsed(json: &str) -> Value {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(json).unwrap();
+    parser.into_result()
+}
+
+#[test]
+fn drafts_are_rolled_back() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"answer": "#).unwrap();
+    let checkpoint = parser.checkpoint();
+    assert_eq!(checkpoint.offset(), 11);
+
+    for draft in [r#""maybe", "x": [1"#, "[1, 2], ", "tru"] {
+        for c in draft.chars() {
+            parser.add_char(c).unwrap();
+        }
+        parser.rollback(checkpoint).unwrap();
+        assert_eq!(parser.get_result(), &json!({"answer": null}));
+        assert_eq!(parser.char_offset(), 11);
+    }
+    parser.add_str(r#"42, "done": true}"#).unwrap();
+    assert_eq!(
+        parser.into_result(),
+        parse_stream(r#"{"answer": 42, "done": true}"#).unwrap()
+    );
+}
+
+#[test]
+fn truncate_to_matches_a_parser_fed_the_prefix() {
+    let chars = JSON.chars().count();
+    let mut full = JsonStreamParser::new().rewindable(4);
+    for c in JSON.chars() {
+        full.add_char(c).unwrap();
+    }
+    for offset in 0..=chars {
+        let mut parser = full.clone();
+        parser.truncate_to(offset).unwrap();
+        assert_eq!(parser.char_offset(), offset);
+        assert_eq!(
+            parser.get_result(),
+            &parsed(prefix(JSON, offset)),
+            "{offset}"
+        );
+
+        let rest = &JSON[prefix(JSON, offset).len()..];
+        parser.add_str(rest).unwrap();
+        assert!(parser.is_complete());
+        assert_eq!(parser.get_result(), full.get_result(), "{offset}");
+    }
+}
+
+#[test]
+fn truncating_twice_goes_further_back() {
+    let mut parser = JsonStreamParser::new().rewindable(8);
+    parser.add_str(JSON).unwrap();
+    parser.truncate_to(40).unwrap();
+    parser.truncate_to(12).unwrap();
+    assert_eq!(parser.get_result(), &parsed(prefix(JSON, 12)));
+    parser.add_str(r#"x", "n": 1}"#).unwrap();
+    assert_eq!(parser.get_result(), &json!({"title": "Zx", "n": 1}));
+}
+
+#[test]
+fn offsets_that_are_not_kept_are_refused() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str("[1, 2").unwrap();
+    assert_eq!(parser.truncate_to(3), Err(RewindError::Forgotten));
+    assert_eq!(parser.truncate_to(9), Err(RewindError::Ahead));
+
+    let first = parser.checkpoint();
+    parser.add_str(", 3").unwrap();
+    let second = parser.checkpoint();
+    parser.add_str(", 4").unwrap();
+    assert_eq!(parser.truncate_to(3), Err(RewindError::Forgotten));
+
+    // a checkpoint that was rewound past is gone, an earlier one is kept
+    parser.rollback(first).unwrap();
+    assert_eq!(parser.rollback(second), Err(RewindError::Forgotten));
+    parser.add_str("]").unwrap();
+    assert_eq!(parser.get_result(), &json!([1, 2]));
+    parser.rollback(first).unwrap();
+    assert_eq!(parser.get_result(), &json!([1, null]));
+    assert!(!parser.is_complete());
+}
+
+#[test]
+fn failed_chunks_are_not_kept() {
+    let mut parser = JsonStreamParser::new().rewindable(1);
+    parser.add_str("[1, ").unwrap();
+    assert!(parser.add_str("2 x").is_err());
+    parser.add_str("3]").unwrap();
+    parser.truncate_to(5).unwrap();
+    parser.add_str("4]").unwrap();
+    assert_eq!(parser.get_result(), &json!([1, 34]));
+}
+
+#[test]
+fn forks_go_on_independently() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": [1, "#).unwrap();
+    let mut fork = parser.clone();
+    parser.add_str("2]}").unwrap();
+    fork.add_str(r#""two", 3], "b": null}"#).unwrap();
+    assert_eq!(parser.into_result(), json!({"a": [1, 2]}));
+    assert_eq!(fork.into_result(), json!({"a": [1, "two", 3], "b": null}));
+}
+
+#[cfg(feature = "shared")]
+#[test]
+fn snapshots_follow_a_rewind() {
+    let mut parser = JsonStreamParser::new().shared_snapshots().rewindable(4);
+    parser.add_str(r#"{"a": [1, {"b": "c"#).unwrap();
+    let before = parser.snapshot();
+    parser.add_str(r#"d"}], "e": 2}"#).unwrap();
+    parser.truncate_to(10).unwrap();
+    assert_eq!(parser.snapshot().to_value(), json!({"a": [1]}));
+    assert_eq!(before.to_value(), json!({"a": [1, {"b": "c"}]}));
+    parser.add_str("5]}").unwrap();
+    assert_eq!(parser.snapshot().to_value(), json!({"a": [1, 5]}));
+}
+
+#[test]
+fn replayed_chunks_keep_their_tokens_and_times() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let tokens = [
+        r#"{"na"#,
+        r#"me": "A"#,
+        r#"da", "#,
+        r#""tags": ["#,
+        r#""x"]"#,
+        "}",
+    ];
+    let now = Arc::new(AtomicU64::new(0));
+    let timed = |now: &Arc<AtomicU64>| {
+        let now = now.clone();
+        JsonStreamParser::new()
+            .timed_with(move || Duration::from_millis(now.load(Ordering::SeqCst)))
+            .rewindable(1000)
+    };
+    let mut parser = timed(&now);
+    for (id, token) in tokens.iter().enumerate() {
+        now.store(10 * id as u64, Ordering::SeqCst);
+        parser.add_token(token, id).unwrap();
+    }
+    // the clock has moved on, and is not read again for the chunks kept
+    now.store(1000, Ordering::SeqCst);
+    parser.truncate_to(20).unwrap();
+
+    let mut expected = timed(&now);
+    for (id, token) in [r#"{"na"#, r#"me": "A"#, r#"da", "#, r#""tag"#]
+        .iter()
+        .enumerate()
+    {
+        now.store(10 * id as u64, Ordering::SeqCst);
+        expected.add_token(token, id).unwrap();
+    }
+    assert_eq!(parser.get_result(), expected.get_result());
+    assert_eq!(parser.spans(), expected.spans());
+    assert_eq!(parser.timings(), expected.timings());
+    assert_eq!(parser.token_range(""), Some(0..=3));
+    assert_eq!(parser.token_paths(2), vec!["", "/name"]);
+    assert_eq!(
+        parser.timing("/name").unwrap().finished,
+        Some(Duration::from_millis(20))
+    );
+}
+
+#[test]
+fn rollback_undoes_every_change() {
+    use json_stream_parser::{DuplicateKeys, Recovery};
+
+    let documents = [
+        (
+            DuplicateKeys::LastWins,
+            r#"{"a": {"b": [1, 2]}, "c": "d", "a": [3, {"e": null}], "f": 4, "c": 5}"#,
+        ),
+        (
+            DuplicateKeys::Collect,
+            r#"{"a": 1, "b": [2], "a": {"c": 3}, "a": [4], "b": "five"}"#,
+        ),
+        (
+            DuplicateKeys::FirstWins,
+            r#"[{"a": 1, "a": [2, 3]}, [[["deep"]]], -1.5e3, "sé"]"#,
+        ),
+    ];
+    for (policy, json) in documents {
+        let new = || {
+            JsonStreamParser::new()
+                .duplicate_keys(policy)
+                .recovery(Recovery::Drop)
+                .record_spans()
+        };
+        let chars: Vec<char> = json.chars().collect();
+        for at in 0..chars.len() {
+            let mut parser = new();
+            let mut expected = new();
+            for (index, c) in chars.iter().enumerate() {
+                if index == at {
+                    expected = parser.clone();
+                    let checkpoint = parser.checkpoint();
+                    for &c in &chars[at..] {
+                        parser.add_char(c).unwrap();
+                    }
+                    // malformed input recovered from is undone too
+                    parser.add_str(" x").unwrap();
+                    parser.rollback(checkpoint).unwrap();
+                    assert_eq!(parser.get_result(), expected.get_result(), "{at}");
+                    assert_eq!(parser.spans(), expected.spans(), "{at}");
+                    assert_eq!(parser.diagnostics(), expected.diagnostics(), "{at}");
+                }
+                parser.add_char(*c).unwrap();
+                expected.add_char(*c).unwrap();
+            }
+            assert_eq!(parser.get_result(), expected.get_result(), "{at}");
+            assert_eq!(parser.spans(), expected.spans(), "{at}");
+        }
+    }
+}
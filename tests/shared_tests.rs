@@ -0,0 +1,117 @@
+#![cfg(feature = "shared")]
+
+use std::sync::{mpsc, Arc};
+
+use json_stream_parser::{JsonStreamParser, SharedValue};
+use serde_json::{json, Value};
+
+const DOCUMENT: &str = r#"{"title": "Zoë", "items": [{"id": 1, "tags": ["a", "b"]}, {"id": 2.5, "ok": true}], "rest": null, "x": [[], {}]}"#;
+
+#[test]
+fn snapshots_match_get_result() {
+    let mut parser = JsonStreamParser::new().shared_snapshots();
+    assert_eq!(parser.snapshot(), SharedValue::Null);
+    for c in DOCUMENT.chars() {
+        parser.add_char(c).unwrap();
+        assert_eq!(
+            &parser.snapshot().to_value(),
+            parser.get_result(),
+            "after {c:?}"
+        );
+    }
+
+    for root in [r#""abc""#, "-12.5 ", "tru"] {
+        let mut parser = JsonStreamParser::new().shared_snapshots();
+        for c in root.chars() {
+            parser.add_char(c).unwrap();
+            assert_eq!(&parser.snapshot().to_value(), parser.get_result());
+        }
+    }
+}
+
+#[test]
+fn enabled_after_input_was_parsed() {
+    for split in (0..DOCUMENT.len()).filter(|&split| DOCUMENT.is_char_boundary(split)) {
+        let mut parser = JsonStreamParser::new();
+        parser.add_str(&DOCUMENT[..split]).unwrap();
+        let mut parser = parser.shared_snapshots();
+        assert_eq!(&parser.snapshot().to_value(), parser.get_result());
+        parser.add_str(&DOCUMENT[split..]).unwrap();
+        assert_eq!(
+            parser.snapshot().to_value(),
+            serde_json::from_str::<Value>(DOCUMENT).unwrap()
+        );
+    }
+}
+
+#[test]
+fn completed_subtrees_are_shared() {
+    let mut parser = JsonStreamParser::new().shared_snapshots();
+    parser.add_str(r#"{"a": ["long text"], "b": ["#).unwrap();
+    let first = parser.snapshot();
+    parser.add_str(r#"1, 2"#).unwrap();
+    let second = parser.snapshot();
+
+    let text = |snapshot: &SharedValue| match snapshot {
+        SharedValue::Object(map) => match map.get("a") {
+            Some(SharedValue::Array(arr)) => match arr.front() {
+                Some(SharedValue::String(s)) => s.clone(),
+                _ => panic!("not a string"),
+            },
+            _ => panic!("not an array"),
+        },
+        _ => panic!("not an object"),
+    };
+    assert!(Arc::ptr_eq(&text(&first), &text(&second)));
+    assert_eq!(
+        second.to_value(),
+        json!({"a": ["long text"], "b": [1, null]})
+    );
+}
+
+#[cfg(feature = "preserve_order")]
+#[test]
+fn snapshots_keep_the_order_of_the_stream() {
+    let json = r#"{"z": 1, "a": {"y": 2, "b": 3}, "m": [4], "c": 5, "z": 6}"#;
+    let mut parser = JsonStreamParser::new()
+        .recovery(json_stream_parser::Recovery::Drop)
+        .shared_snapshots();
+    for c in json.chars() {
+        parser.add_char(c).unwrap();
+        assert_eq!(
+            parser.snapshot().to_value().to_string(),
+            parser.get_result().to_string()
+        );
+    }
+    // a dropped member leaves the others in order
+    let mut parser = JsonStreamParser::new()
+        .recovery(json_stream_parser::Recovery::Drop)
+        .shared_snapshots();
+    parser.add_str(r#"{"z": 1, "q": tru, "a": 2}"#).unwrap();
+    assert_eq!(parser.snapshot().to_value().to_string(), r#"{"z":1,"a":2}"#);
+    match parser.snapshot() {
+        SharedValue::Object(map) => {
+            assert_eq!(map.keys().collect::<Vec<_>>(), ["z", "a"]);
+        }
+        _ => panic!("not an object"),
+    }
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn snapshots_cross_threads() {
+    assert_send_sync::<SharedValue>();
+    let (sender, receiver) = mpsc::channel::<SharedValue>();
+    let reader =
+        std::thread::spawn(move || receiver.iter().last().map(|snapshot| snapshot.to_value()));
+
+    let mut parser = JsonStreamParser::new().shared_snapshots();
+    let chars: Vec<char> = DOCUMENT.chars().collect();
+    for chunk in chars.chunks(7) {
+        parser.add_str(&chunk.iter().collect::<String>()).unwrap();
+        sender.send(parser.snapshot()).unwrap();
+    }
+    drop(sender);
+    assert_eq!(reader.join().unwrap().unwrap(), parser.get_result().clone());
+}
@@ -0,0 +1,172 @@
+use json_stream_parser::{DuplicateKeys, JsonStreamParser, Location, Recovery, Span};
+use serde_json::json;
+
+fn at(byte: usize, char: usize, line: usize, column: usize) -> Location {
+    Location {
+        byte,
+        char,
+        line,
+        column,
+    }
+}
+
+// The spans of `json` fed as a whole, which must be the same when it is fed char by char.
+fn parse(parser: fn() -> JsonStreamParser, json: &str) -> JsonStreamParser {
+    let mut whole = parser();
+    whole.add_str(json).unwrap();
+    let mut per_char = parser();
+    for c in json.chars() {
+        per_char.add_char(c).unwrap();
+    }
+    assert_eq!(per_char.spans(), whole.spans(), "{json}");
+    whole
+}
+
+fn range(span: Option<&Span>) -> Option<(usize, Option<usize>)> {
+    span.map(|span| (span.start.byte, span.end.map(|end| end.byte)))
+}
+
+#[test]
+fn values_and_keys_have_spans() {
+    let json = "{\n  \"név\": [1, \"é🦀\", true],\n  \"b\": {\"c\": null}\n}";
+    let parser = parse(|| JsonStreamParser::new().record_spans(), json);
+    let spans = parser.spans().unwrap();
+
+    assert_eq!(
+        spans.get(""),
+        Some(&Span {
+            start: at(0, 0, 1, 1),
+            end: Some(at(53, 48, 4, 2)),
+        })
+    );
+    assert_eq!(
+        spans.key("/név"),
+        Some(&Span {
+            start: at(4, 4, 2, 3),
+            end: Some(at(10, 9, 2, 8)),
+        })
+    );
+    assert_eq!(
+        spans.get("/név/1"),
+        Some(&Span {
+            start: at(16, 15, 2, 14),
+            end: Some(at(24, 19, 2, 18)),
+        })
+    );
+    assert_eq!(range(spans.get("/név")), Some((12, Some(31))));
+    assert_eq!(range(spans.get("/név/2")), Some((26, Some(30))));
+    assert_eq!(range(spans.get("/b/c")), Some((46, Some(50))));
+    assert_eq!(spans.get("/b").unwrap().start.line, 3);
+    assert_eq!(spans.key(""), None);
+    assert_eq!(spans.get("/x"), None);
+    assert_eq!(spans.get("/név/3"), None);
+}
+
+#[test]
+fn open_values_have_no_end() {
+    let parser = parse(
+        || JsonStreamParser::new().record_spans(),
+        r#"{"a": [10, "st"#,
+    );
+    let spans = parser.spans().unwrap();
+    assert_eq!(range(spans.get("")), Some((0, None)));
+    assert_eq!(range(spans.get("/a")), Some((6, None)));
+    assert_eq!(range(spans.get("/a/0")), Some((7, Some(9))));
+    assert_eq!(range(spans.get("/a/1")), Some((11, None)));
+
+    // a number only ends when something follows it
+    let parser = parse(|| JsonStreamParser::new().record_spans(), "[12");
+    assert_eq!(range(parser.spans().unwrap().get("/0")), Some((1, None)));
+    assert!(JsonStreamParser::new().spans().is_none());
+}
+
+#[test]
+fn duplicate_keys_follow_the_policy() {
+    let json = r#"{"a": 1, "a": [2], "b": 3}"#;
+    let parser = parse(|| JsonStreamParser::new().record_spans(), json);
+    assert_eq!(
+        range(parser.spans().unwrap().get("/a")),
+        Some((14, Some(17)))
+    );
+
+    let parser = parse(
+        || {
+            JsonStreamParser::new()
+                .duplicate_keys(DuplicateKeys::FirstWins)
+                .record_spans()
+        },
+        json,
+    );
+    let spans = parser.spans().unwrap();
+    assert_eq!(range(spans.get("/a")), Some((6, Some(7))));
+    assert_eq!(range(spans.key("/b")), Some((19, Some(22))));
+
+    let parser = parse(
+        || {
+            JsonStreamParser::new()
+                .duplicate_keys(DuplicateKeys::Collect)
+                .record_spans()
+        },
+        json,
+    );
+    assert_eq!(parser.get_result(), &json!({"a": [1, [2]], "b": 3}));
+    let spans = parser.spans().unwrap();
+    assert_eq!(range(spans.get("/a/0")), Some((6, Some(7))));
+    assert_eq!(range(spans.get("/a/1")), Some((14, Some(17))));
+    assert_eq!(range(spans.get("/a/1/0")), Some((15, Some(16))));
+    assert_eq!(range(spans.key("/a/1")), Some((9, Some(12))));
+}
+
+#[test]
+fn recovered_values_span_the_malformed_input() {
+    let json = r#"{"a": tru x, "b" 2, "c": [x, 3]}"#;
+    let parser = parse(
+        || {
+            JsonStreamParser::new()
+                .recovery(Recovery::Replace(json!("?")))
+                .record_spans()
+        },
+        json,
+    );
+    assert_eq!(
+        parser.get_result(),
+        &json!({"a": "?", "b": "?", "c": ["?", 3]})
+    );
+    let spans = parser.spans().unwrap();
+    assert_eq!(range(spans.get("/a")), Some((6, Some(9))));
+    assert_eq!(range(spans.get("/b")), Some((17, Some(17))));
+    assert_eq!(range(spans.get("/c/0")), Some((26, Some(26))));
+    assert_eq!(range(spans.get("/c/1")), Some((29, Some(30))));
+    assert_eq!(range(spans.get("")), Some((0, Some(32))));
+
+    let parser = parse(
+        || {
+            JsonStreamParser::new()
+                .recovery(Recovery::Drop)
+                .record_spans()
+        },
+        json,
+    );
+    assert_eq!(parser.get_result(), &json!({"c": [3]}));
+    let spans = parser.spans().unwrap();
+    assert_eq!(spans.get("/a"), None);
+    assert_eq!(spans.get("/b"), None);
+    assert_eq!(range(spans.get("/c/0")), Some((29, Some(30))));
+}
+
+#[test]
+fn failed_chunks_leave_spans_untouched() {
+    let mut parser = JsonStreamParser::new().record_spans();
+    parser.add_str("[1, \n").unwrap();
+    let before = parser.spans().cloned();
+    assert!(parser.add_str("\"a\"\n, x").is_err());
+    assert_eq!(parser.spans().cloned(), before);
+    parser.add_str("\"b\"]").unwrap();
+    assert_eq!(
+        parser.spans().unwrap().get("/1"),
+        Some(&Span {
+            start: at(5, 5, 2, 1),
+            end: Some(at(8, 8, 2, 4)),
+        })
+    );
+}
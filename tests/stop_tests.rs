@@ -0,0 +1,116 @@
+use json_stream_parser::{Feed, JsonStreamParser, StopWhen};
+use serde_json::{json, Value};
+
+// Feeds `json` one char at a time, and returns how many chars it took to stop and the finished
+// value at that point.
+fn stop_at(mut parser: JsonStreamParser, json: &str) -> Option<(usize, Value)> {
+    for (index, c) in json.chars().enumerate() {
+        if parser.feed(&c.to_string()).unwrap() == Feed::Stop {
+            return Some((index + 1, parser.finish()));
+        }
+    }
+    None
+}
+
+#[test]
+fn stops_once_the_paths_are_complete() {
+    let json = r#"{"id": 7, "name": "ab", "rest": [1, 2, 3]}"#;
+    let parser = JsonStreamParser::new().stop_when(StopWhen::complete(["/id", "/name"]));
+    let (consumed, value) = stop_at(parser, json).unwrap();
+    assert_eq!(consumed, json.find("\"ab\"").unwrap() + 4);
+    assert_eq!(value, json!({"id": 7, "name": "ab"}));
+}
+
+#[test]
+fn stops_once_the_root_array_has_enough_items() {
+    let json = r#"[{"a": 1}, {"a": 2}, {"a": 3}]"#;
+    let parser = JsonStreamParser::new().stop_when(StopWhen::Items(2));
+    let (consumed, value) = stop_at(parser, json).unwrap();
+    assert_eq!(consumed, json.find("2}").unwrap() + 2);
+    assert_eq!(value, json!([{"a": 1}, {"a": 2}]));
+
+    // a number is only complete once something follows it
+    let (consumed, value) = stop_at(
+        JsonStreamParser::new().stop_when(StopWhen::Items(1)),
+        "[12, 3]",
+    )
+    .unwrap();
+    assert_eq!(consumed, 4);
+    assert_eq!(value, json!([12]));
+}
+
+#[test]
+fn stops_when_the_predicate_holds() {
+    let json = r#"{"status": "error", "detail": "a long explanation"}"#;
+    let parser = JsonStreamParser::new()
+        .stop_when(StopWhen::Items(1))
+        .stop_when(StopWhen::predicate(|value| value["status"] == "error"));
+    // the predicate sees a string as far as it has arrived
+    let (consumed, value) = stop_at(parser, json).unwrap();
+    assert_eq!(consumed, json.find("error").unwrap() + 5);
+    assert_eq!(value, json!({}));
+}
+
+#[test]
+fn keeps_going_without_a_condition_that_holds() {
+    let json = r#"{"a": [1, 2]}"#;
+    assert_eq!(stop_at(JsonStreamParser::new(), json), None);
+    let parser = JsonStreamParser::new().stop_when(StopWhen::complete(["/b"]));
+    assert_eq!(stop_at(parser, json), None);
+}
+
+#[test]
+fn finish_leaves_out_what_has_not_arrived() {
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": [1, "x", {"b": tr"#).unwrap();
+    assert_eq!(parser.finish(), json!({"a": [1, "x", {}]}));
+
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": "hel"#).unwrap();
+    assert_eq!(parser.finish(), json!({}));
+
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": 1, "b":"#).unwrap();
+    assert_eq!(parser.finish(), json!({"a": 1}));
+
+    let mut parser = JsonStreamParser::new();
+    parser.add_str("12").unwrap();
+    assert_eq!(parser.finish(), Value::Null);
+
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(r#"{"a": 1}"#).unwrap();
+    assert_eq!(parser.finish(), json!({"a": 1}));
+}
+
+#[test]
+fn errors_are_returned_by_feed() {
+    let mut parser = JsonStreamParser::new().stop_when(StopWhen::Items(1));
+    assert!(parser.feed("[1}").is_err());
+}
+
+#[test]
+fn items_zero_holds_once_the_root_array_opens() {
+    let parser = JsonStreamParser::new().stop_when(StopWhen::Items(0));
+    assert_eq!(stop_at(parser, "[1, 2]"), Some((1, json!([]))));
+}
+
+#[test]
+fn finish_handles_deep_documents() {
+    const DEPTH: usize = 100_000;
+    let mut parser = JsonStreamParser::new();
+    parser.add_str(&r#"[{"a": "#.repeat(DEPTH / 2)).unwrap();
+    parser.add_str("\"unfinished").unwrap();
+    let mut pending = vec![parser.finish()];
+    let mut containers = 0;
+    // `Value` drops itself recursively, so it is taken apart with an explicit loop
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Array(items) => pending.extend(items),
+            Value::Object(map) => pending.extend(map.into_iter().map(|(_, value)| value)),
+            Value::String(_) => panic!("the unfinished string was kept"),
+            _ => continue,
+        }
+        containers += 1;
+    }
+    assert_eq!(containers, DEPTH);
+}
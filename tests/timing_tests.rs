@@ -0,0 +1,121 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use json_stream_parser::{DuplicateKeys, JsonStreamParser, Timing};
+
+// A parser whose clock reads the number of milliseconds in `now`.
+fn timed(now: &Arc<AtomicU64>) -> JsonStreamParser {
+    let now = now.clone();
+    JsonStreamParser::new().timed_with(move || Duration::from_millis(now.load(Ordering::SeqCst)))
+}
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[test]
+fn values_are_stamped_when_their_chunks_arrive() {
+    let now = Arc::new(AtomicU64::new(0));
+    let mut parser = timed(&now);
+    for (time, chunk) in [
+        (10, r#"{"id": 7, "na"#),
+        (25, r#"me": "Ad"#),
+        (40, r#"a", "tags": ["#),
+        (70, r#""x"]}"#),
+    ] {
+        now.store(time, Ordering::SeqCst);
+        parser.add_str(chunk).unwrap();
+    }
+
+    assert_eq!(
+        parser.timing("/name"),
+        Some(Timing {
+            started: ms(25),
+            finished: Some(ms(40)),
+            start: 18,
+            end: Some(23),
+        })
+    );
+    assert_eq!(
+        parser.timing("/id"),
+        Some(Timing {
+            started: ms(10),
+            finished: Some(ms(10)),
+            start: 7,
+            end: Some(8),
+        })
+    );
+    let timings: Vec<(String, Duration, Option<Duration>)> = parser
+        .timings()
+        .into_iter()
+        .map(|(path, timing)| (path, timing.started, timing.finished))
+        .collect();
+    assert_eq!(
+        timings,
+        vec![
+            (String::new(), ms(10), Some(ms(70))),
+            ("/id".to_string(), ms(10), Some(ms(10))),
+            ("/name".to_string(), ms(25), Some(ms(40))),
+            ("/tags".to_string(), ms(40), Some(ms(70))),
+            ("/tags/0".to_string(), ms(70), Some(ms(70))),
+        ]
+    );
+}
+
+#[test]
+fn open_values_have_not_finished() {
+    let now = Arc::new(AtomicU64::new(5));
+    let mut parser = timed(&now);
+    parser.add_str(r#"[1, "tw"#).unwrap();
+    now.store(9, Ordering::SeqCst);
+    // a failed chunk is not stamped
+    assert!(parser.add_str("o\", x").is_err());
+    assert_eq!(parser.timing("/1").unwrap().finished, None);
+    parser.add_str("o\"").unwrap();
+    assert_eq!(parser.timing("/1").unwrap().finished, Some(ms(9)));
+    assert_eq!(parser.timing("").unwrap().finished, None);
+    assert_eq!(parser.timing("/2"), None);
+
+    assert!(JsonStreamParser::new().timings().is_empty());
+    let mut untimed = JsonStreamParser::new().record_spans();
+    untimed.add_str("[1]").unwrap();
+    assert_eq!(untimed.timing(""), None);
+}
+
+#[test]
+fn duplicate_keys_are_listed_as_they_are_looked_up() {
+    let now = Arc::new(AtomicU64::new(1));
+    let mut parser = timed(&now).duplicate_keys(DuplicateKeys::Collect);
+    parser.add_str(r#"{"a": 1, "b": 2, "a": 3}"#).unwrap();
+    let paths: Vec<String> = parser.timings().into_iter().map(|(path, _)| path).collect();
+    assert_eq!(paths, vec!["", "/b", "/a/0", "/a/1"]);
+
+    let mut parser = timed(&now);
+    parser.add_str(r#"{"a": 1, "b": 2, "a": 3}"#).unwrap();
+    let paths: Vec<String> = parser.timings().into_iter().map(|(path, _)| path).collect();
+    assert_eq!(paths, vec!["", "/b", "/a"]);
+    assert_eq!(parser.timing("/a").unwrap().start, 22);
+}
+
+#[test]
+fn large_documents_are_listed_in_order() {
+    let now = Arc::new(AtomicU64::new(1));
+    let members: Vec<String> = (0..20_000).map(|i| format!(r#""k{i}": [{i}]"#)).collect();
+    let mut parser = timed(&now);
+    parser
+        .add_str(&format!("{{{}}}", members.join(", ")))
+        .unwrap();
+    let paths: Vec<String> = parser.timings().into_iter().map(|(path, _)| path).collect();
+    assert_eq!(paths.len(), 1 + 2 * members.len());
+    assert_eq!(paths[..5], ["", "/k0", "/k0/0", "/k1", "/k1/0"]);
+    assert_eq!(paths.last().unwrap(), "/k19999/0");
+}
+
+#[test]
+fn the_default_clock_measures_from_the_start() {
+    let mut parser = JsonStreamParser::new().timed();
+    parser.add_str("[1]").unwrap();
+    let timing = parser.timing("").unwrap();
+    assert!(timing.finished.unwrap() >= timing.started);
+}
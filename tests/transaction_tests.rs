@@ -0,0 +1,116 @@
+use json_stream_parser::{DuplicateKeys, JsonStreamParser, Limits};
+use serde_json::json;
+use std::io::Write as _;
+
+fn parser(policy: DuplicateKeys, limits: Limits) -> JsonStreamParser {
+    JsonStreamParser::new()
+        .duplicate_keys(policy)
+        .limits(limits)
+}
+
+// `bad` fails somewhere after the prefix it shares with `good`. Wherever the input is split
+// before the error, the chunk with the error must leave the parser as it was, and sending the
+// rest of `good` instead must give the same result as `good` in one chunk.
+fn check(bad: &str, good: &str, policy: DuplicateKeys, limits: Limits) {
+    let mut expected = parser(policy, limits);
+    expected.add_str(good).unwrap();
+    let expected = expected.into_result();
+
+    let common = bad
+        .char_indices()
+        .zip(good.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(good.len(), |((index, _), _)| index);
+    for split in (0..=common).filter(|&split| bad.is_char_boundary(split)) {
+        let mut parser = parser(policy, limits);
+        parser.add_str(&bad[..split]).unwrap();
+        let before = parser.get_result().clone();
+        let complete = parser.is_complete();
+
+        assert!(parser.add_str(&bad[split..]).is_err(), "{bad} at {split}");
+        assert_eq!(parser.get_result(), &before, "{bad} at {split}");
+        assert_eq!(parser.is_complete(), complete);
+        #[cfg(feature = "shared")]
+        assert_eq!(parser.snapshot().to_value(), before);
+
+        parser.add_str(&good[split..]).unwrap();
+        assert_eq!(parser.get_result(), &expected, "{bad} at {split}");
+    }
+}
+
+#[test]
+fn failed_chunks_leave_the_parser_untouched() {
+    let cases = [
+        (
+            r#"{"a": [1, {"b": "xéy"}], "c": tru}"#,
+            r#"{"a": [1, {"b": "xéy"}], "c": true}"#,
+        ),
+        (r#"[[[{"k": [1]}]], x]"#, r#"[[[{"k": [1]}]], 2]"#),
+        (
+            r#"{"k\"ey": {"a": 1}, "b" 2}"#,
+            r#"{"k\"ey": {"a": 1}, "b": 2}"#,
+        ),
+        ("-12.5e+x", "-12.5e+3 "),
+        (r#""ab\u12G4""#, r#""abሴ""#),
+        ("[1, 2] 3", "[1, 2] "),
+    ];
+    for (bad, good) in cases {
+        check(bad, good, DuplicateKeys::default(), Limits::default());
+    }
+}
+
+#[test]
+fn failed_chunks_keep_the_keys_seen_so_far() {
+    check(
+        r#"{"a": 1, "a": 2, "a": [3}"#,
+        r#"{"a": 1, "a": 2, "a": [3]}"#,
+        DuplicateKeys::Collect,
+        Limits::default(),
+    );
+    check(
+        r#"{"a": 1, "a": {"x": 2}, "b" 3}"#,
+        r#"{"a": 1, "a": {"x": 2}, "b": 3}"#,
+        DuplicateKeys::FirstWins,
+        Limits::default(),
+    );
+    check(
+        r#"{"a": 1, "b": 2, "a": 3}"#,
+        r#"{"a": 1, "b": 2, "c": 3}"#,
+        DuplicateKeys::Error,
+        Limits::default(),
+    );
+}
+
+#[test]
+fn failed_chunks_do_not_count_against_limits() {
+    let limits = Limits {
+        max_nodes: Some(4),
+        max_memory: Some(1000),
+        ..Limits::default()
+    };
+    check(
+        "[1, 2, 3, 4]",
+        "[1, 2, 3]",
+        DuplicateKeys::default(),
+        limits,
+    );
+
+    let mut parser = JsonStreamParser::with_limits(None, Some(10));
+    parser.add_str(r#"["abc", "#).unwrap();
+    // the chunk is rejected as a whole rather than cut at the limit
+    assert!(parser.add_str(r#""defg"]"#).is_err());
+    assert_eq!(parser.get_result(), &json!(["abc"]));
+    parser.add_str("1]").unwrap();
+    assert_eq!(parser.get_result(), &json!(["abc", 1]));
+}
+
+#[test]
+fn failed_writes_leave_the_parser_untouched() {
+    let mut parser = JsonStreamParser::new();
+    parser.write_all(&"[\"é".as_bytes()[..3]).unwrap();
+    // the rest of the split sequence, followed by invalid UTF-8
+    assert!(parser.write(&[0xA9, b'"', 0xFF]).is_err());
+    assert_eq!(parser.get_result(), &json!([""]));
+    parser.write_all(&[0xA9, b'"', b']']).unwrap();
+    assert_eq!(parser.get_result(), &json!(["é"]));
+}
@@ -0,0 +1,95 @@
+use json_stream_parser::JsonStreamParser;
+use serde_json::json;
+use std::io::{self, Write as _};
+
+#[test]
+fn io_copy_into_parser() {
+    let json = r#"{"name": "Zoë", "emoji": "🦀", "list": [1, 2, 3]}"#;
+    let mut parser = JsonStreamParser::new();
+    let copied = io::copy(&mut json.as_bytes(), &mut parser).unwrap();
+    assert_eq!(copied, json.len() as u64);
+    assert_eq!(
+        parser.get_result(),
+        &json!({"name": "Zoë", "emoji": "🦀", "list": [1, 2, 3]})
+    );
+}
+
+#[test]
+fn io_write_handles_split_utf8() {
+    let json = r#"["🦀é"]"#.as_bytes();
+    let mut parser = JsonStreamParser::new();
+    for byte in json {
+        assert_eq!(parser.write(&[*byte]).unwrap(), 1);
+    }
+    assert_eq!(parser.get_result(), &json!(["🦀é"]));
+}
+
+#[test]
+fn io_write_errors_are_invalid_data() {
+    let mut parser = JsonStreamParser::new();
+    let error = parser.write_all(b"{]").unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.get_ref().is_some());
+
+    let mut parser = JsonStreamParser::new();
+    let error = parser.write_all(&[b'"', 0xFF]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    // nothing before the invalid byte is parsed either
+    let mut parser = JsonStreamParser::new();
+    parser.write_all(b"[1, ").unwrap();
+    assert!(parser.write(b"2, \xFF").is_err());
+    assert_eq!(parser.get_result(), &json!([1]));
+    parser.write_all(b"3]").unwrap();
+    assert_eq!(parser.get_result(), &json!([1, 3]));
+}
+
+// `io::Write` and `fmt::Write` both provide `write_fmt`, so only one of them can be in scope
+mod fmt_write {
+    use json_stream_parser::JsonStreamParser;
+    use serde_json::json;
+    use std::fmt::Write as _;
+
+    #[test]
+    fn write_macro() {
+        let mut parser = JsonStreamParser::new();
+        let (count, label) = (3, "three");
+        write!(parser, "{{\"count\": {count}, \"label\": \"{label}\"}}").unwrap();
+        assert_eq!(parser.get_result(), &json!({"count": 3, "label": "three"}));
+        assert!(parser.take_error().is_none());
+
+        let mut parser = JsonStreamParser::new();
+        assert!(write!(parser, "{{]").is_err());
+        assert!(parser.take_error().is_some());
+
+        let mut parser = JsonStreamParser::new();
+        write!(parser, "{{").unwrap();
+        assert!(write!(parser, "]").is_err());
+        // like `extend`, input is ignored while the error has not been taken
+        assert!(write!(parser, "\"a\": 1}}").is_err());
+        assert_eq!(parser.get_result(), &json!({}));
+        assert!(parser.take_error().is_some());
+        write!(parser, "\"a\": 1}}").unwrap();
+        assert_eq!(parser.get_result(), &json!({"a": 1}));
+    }
+}
+
+#[test]
+fn extend_with_chars_and_strs() {
+    let mut parser = JsonStreamParser::new();
+    parser.extend("[1, ".chars());
+    parser.extend(vec!["2, ", "\"three\"]"]);
+    assert_eq!(parser.get_result(), &json!([1, 2, "three"]));
+    assert!(parser.take_error().is_none());
+}
+
+#[test]
+fn extend_stops_at_first_error() {
+    let mut parser = JsonStreamParser::new();
+    parser.extend("{]".chars());
+    // input is ignored while the error has not been taken
+    parser.extend(vec!["\"a\": 1}"]);
+    assert_eq!(parser.get_result(), &json!({}));
+    assert!(parser.take_error().is_some());
+    assert!(parser.take_error().is_none());
+}